@@ -0,0 +1,151 @@
+// Statistically sound timings of the query paths, with confidence intervals, to complement the quick `time!` smoke tests in `src/main.rs`. Run with `cargo bench`.
+//
+// For hardware counters such as cache misses, run a single benchmark under `perf`. `cargo bench --no-run` prints the path of the built executable, e.g. `target/release/deps/queries-<hash>`, which is then run directly (a `queries-*` glob would also match its `.d` file):
+//   cargo bench --no-run
+//   perf stat -e cache-references,cache-misses,branch-misses target/release/deps/queries-<hash> --bench --profile-time 10 entities/fastrie
+use std::collections::HashMap;
+use std::fs::File;
+
+use criterion::measurement::WallTime;
+use criterion::{black_box, criterion_group, criterion_main, BatchSize, BenchmarkGroup, Criterion, Throughput};
+use fastrie::{Fastrie, FastrieBuilderNode, FixedFastrie, IndexWidth};
+use serde::Deserialize;
+
+#[derive(Deserialize)]
+struct Entity {
+    characters: String,
+}
+
+fn entities(c: &mut Criterion) {
+    let entities: HashMap<String, Entity> = serde_json::from_reader(File::open("entities.json").unwrap()).unwrap();
+    let entity_reps: Vec<&[u8]> = entities.keys().map(|rep| rep.as_bytes()).collect();
+    let mut fastrie_builder: FastrieBuilderNode<&str> = FastrieBuilderNode::new(IndexWidth(3));
+    for (rep, Entity { characters }) in entities.iter() {
+        fastrie_builder.add(rep.as_bytes(), characters.as_str());
+    };
+    let fastrie_built = fastrie_builder.prebuild().unwrap();
+    let fastrie = Fastrie::from_prebuilt(fastrie_built.index_width, fastrie_built.values.as_slice(), fastrie_built.data.as_slice()).unwrap();
+
+    let mut group = c.benchmark_group("entities");
+    group.throughput(Throughput::Elements(entity_reps.len() as u64));
+    group.bench_function("fastrie", |b| b.iter(|| {
+        for rep in entity_reps.iter() {
+            black_box(fastrie.longest_matching_prefix(black_box(rep)));
+        };
+    }));
+    group.bench_function("fastrie_fn", |b| b.iter(|| {
+        for rep in entity_reps.iter() {
+            black_box(fastrie.with_longest_matching_prefix(black_box(rep), |end, value| (end, *value)));
+        };
+    }));
+    group.bench_function("hashmap", |b| {
+        let hashmap: HashMap<&[u8], &str> = entities.iter().map(|(rep, e)| (rep.as_bytes(), e.characters.as_str())).collect();
+        b.iter(|| {
+            for rep in entity_reps.iter() {
+                black_box(hashmap.get(black_box(rep)));
+            };
+        })
+    });
+    group.finish();
+
+    // Includes adding the keys and dropping the builder, which is where the `arena` feature changes allocation. Compare with `cargo bench --features arena entities/build`.
+    c.bench_function("entities/build", |b| b.iter(|| {
+        let mut builder: FastrieBuilderNode<&str> = FastrieBuilderNode::new(IndexWidth(3));
+        for (rep, Entity { characters }) in entities.iter() {
+            builder.add(rep.as_bytes(), characters.as_str());
+        };
+        builder.prebuild().unwrap()
+    }));
+    c.bench_function("entities/prebuild", |b| b.iter_batched(
+        || {
+            let mut builder: FastrieBuilderNode<&str> = FastrieBuilderNode::new(IndexWidth(3));
+            for (rep, Entity { characters }) in entities.iter() {
+                builder.add(rep.as_bytes(), characters.as_str());
+            };
+            builder
+        },
+        |mut builder| builder.prebuild().unwrap(),
+        BatchSize::LargeInput,
+    ));
+}
+
+fn build_entities(entities: &HashMap<String, Entity>, index_width: IndexWidth) -> (Vec<&str>, Vec<u8>) {
+    let mut fastrie_builder: FastrieBuilderNode<&str> = FastrieBuilderNode::new(index_width);
+    for (rep, Entity { characters }) in entities.iter() {
+        fastrie_builder.add(rep.as_bytes(), characters.as_str());
+    };
+    let fastrie_built = fastrie_builder.prebuild().unwrap();
+    (fastrie_built.values, fastrie_built.data)
+}
+
+fn bench_fixed<const W: usize>(group: &mut BenchmarkGroup<WallTime>, entities: &HashMap<String, Entity>, entity_reps: &[&[u8]]) {
+    let (values, data) = build_entities(entities, IndexWidth(W));
+    let fastrie = FixedFastrie::<_, W>::from_prebuilt(&values, &data).unwrap();
+    group.bench_function(format!("fixed/{}", W), |b| b.iter(|| {
+        for rep in entity_reps.iter() {
+            black_box(fastrie.longest_matching_prefix(black_box(rep)));
+        };
+    }));
+}
+
+// `dynamic` reads indices with a loop over the run-time index width; `fixed` uses `FixedFastrie`, whose width is a compile-time constant. `longest_matching_prefix` walks with a cursor, so `dynamic_walk` uses `longest_matching_prefix_at`, which walks with the same walker as `fixed`, for a like-for-like comparison.
+fn index_width(c: &mut Criterion) {
+    let entities: HashMap<String, Entity> = serde_json::from_reader(File::open("entities.json").unwrap()).unwrap();
+    let entity_reps: Vec<&[u8]> = entities.keys().map(|rep| rep.as_bytes()).collect();
+
+    let mut group = c.benchmark_group("index_width");
+    group.throughput(Throughput::Elements(entity_reps.len() as u64));
+    for &index_width in &[IndexWidth(3), IndexWidth(4), IndexWidth(5)] {
+        let (values, data) = build_entities(&entities, index_width);
+        let fastrie = Fastrie::from_prebuilt(index_width, values.as_slice(), data.as_slice()).unwrap();
+        group.bench_function(format!("dynamic/{}", index_width.0), |b| b.iter(|| {
+            for rep in entity_reps.iter() {
+                black_box(fastrie.longest_matching_prefix(black_box(rep)));
+            };
+        }));
+        group.bench_function(format!("dynamic_walk/{}", index_width.0), |b| b.iter(|| {
+            for rep in entity_reps.iter() {
+                black_box(fastrie.longest_matching_prefix_at(black_box(rep), 0));
+            };
+        }));
+    };
+    bench_fixed::<3>(&mut group, &entities, &entity_reps);
+    bench_fixed::<4>(&mut group, &entities, &entity_reps);
+    bench_fixed::<5>(&mut group, &entities, &entity_reps);
+    group.finish();
+}
+
+fn small(c: &mut Criterion) {
+    let values: Vec<&[u8]> = vec![b"anne", b"ane", b"anna", b"ana", b"anene"];
+    let mut fastrie_builder: FastrieBuilderNode<bool> = FastrieBuilderNode::new(IndexWidth(3));
+    for &v in values.iter() {
+        fastrie_builder.add(v, true);
+    };
+    let fastrie_built = fastrie_builder.prebuild().unwrap();
+    let fastrie = Fastrie::from_prebuilt(fastrie_built.index_width, fastrie_built.values.as_slice(), fastrie_built.data.as_slice()).unwrap();
+
+    let mut group = c.benchmark_group("small");
+    group.throughput(Throughput::Elements(values.len() as u64));
+    group.bench_function("fastrie", |b| b.iter(|| {
+        for v in values.iter() {
+            black_box(fastrie.longest_matching_prefix(black_box(v)));
+        };
+    }));
+    group.finish();
+}
+
+fn long_input(c: &mut Criterion) {
+    let keys: Vec<&[u8]> = vec![b"a", b"ab", b"abc", b"b", b"bc"];
+    let mut fastrie_builder: FastrieBuilderNode<usize> = FastrieBuilderNode::new(IndexWidth(1));
+    for (i, &k) in keys.iter().enumerate() {
+        fastrie_builder.add(k, i);
+    };
+    let fastrie_built = fastrie_builder.prebuild().unwrap();
+    let fastrie = Fastrie::from_prebuilt(fastrie_built.index_width, fastrie_built.values.as_slice(), fastrie_built.data.as_slice()).unwrap();
+    let query: Vec<u8> = b"abc".iter().chain([b'x'; 4096].iter()).copied().collect();
+
+    c.bench_function("long_input/fastrie", |b| b.iter(|| fastrie.longest_matching_prefix(black_box(&query))));
+}
+
+criterion_group!(benches, entities, index_width, small, long_input);
+criterion_main!(benches);
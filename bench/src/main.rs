@@ -1,5 +1,8 @@
-use fastrie::{Fastrie, FastrieBuilderNode};
+use aho_corasick::{AhoCorasickBuilder, MatchKind};
+use fastrie::{ByteSource, ChunkedFastrie, Fastrie, FastrieBuilderNode, FixedLenFastrie, IndexWidth};
 use serde::{Deserialize, Serialize};
+use std::borrow::Cow;
+use std::cell::Cell;
 use std::collections::HashMap;
 use std::fs::File;
 use std::time::Instant;
@@ -35,20 +38,19 @@ macro_rules! time {
 fn test_large() {
     println!("test_large");
     let entities: HashMap<String, Entity> = read_json("entities");
-    let mut fastrie_builder: FastrieBuilderNode<String> = FastrieBuilderNode::new();
     let mut hashmap_trie: HashMapTrieNode<String> = HashMapTrieNode::new();
     let mut direct_trie: DirectTrieNode<String> = DirectTrieNode::new();
     let mut hashmap: HashMap<&[u8], String> = HashMap::new();
-    let mut entity_reps: Vec<Vec<u8>> = Vec::new();
+    // Keys are borrowed from `entities` instead of copied.
+    let mut entity_reps: Vec<&[u8]> = Vec::new();
     for (rep, Entity { characters, .. }) in entities.iter() {
-        entity_reps.push(rep.as_bytes().to_vec());
-        fastrie_builder.add(&rep.as_bytes(), characters.clone());
+        entity_reps.push(rep.as_bytes());
         hashmap_trie.add(&rep.as_bytes(), characters.clone());
         direct_trie.add(&rep.as_bytes(), characters.clone());
         hashmap.insert(&rep.as_bytes(), characters.clone());
     };
-    let fastrie_built = fastrie_builder.prebuild();
-    let fastrie = Fastrie::from_prebuilt(fastrie_built.values.as_slice(), fastrie_built.data.as_slice());
+    let fastrie_built = FastrieBuilderNode::from_entries(entities.iter().map(|(rep, entity)| (rep.as_bytes(), entity.characters.clone())), IndexWidth(3));
+    let fastrie = Fastrie::from_prebuilt(fastrie_built.index_width, fastrie_built.values.as_slice(), fastrie_built.data.as_slice()).unwrap();
 
     let iterations = 1000;
     println!("{} iterations", iterations);
@@ -56,7 +58,7 @@ fn test_large() {
     time!("fastrie", fastrie.memory_size(), {
         for _ in 0..iterations {
             for rep in entity_reps.iter() {
-                let _ = fastrie.longest_matching_prefix(rep.as_slice());
+                let _ = fastrie.longest_matching_prefix(*rep);
             };
         };
     });
@@ -64,7 +66,7 @@ fn test_large() {
     time!("hashmap_trie", hashmap_trie.memory_size(), {
         for _ in 0..iterations {
             for rep in entity_reps.iter() {
-                let _ = hashmap_trie.longest_matching_prefix(rep.as_slice());
+                let _ = hashmap_trie.longest_matching_prefix(*rep);
             };
         };
     });
@@ -72,7 +74,7 @@ fn test_large() {
     time!("direct_trie", direct_trie.memory_size(), {
         for _ in 0..iterations {
             for rep in entity_reps.iter() {
-                let _ = direct_trie.longest_matching_prefix(rep.as_slice());
+                let _ = direct_trie.longest_matching_prefix(*rep);
             };
         };
     });
@@ -80,7 +82,7 @@ fn test_large() {
     time!("hashmap", 0, {
         for _ in 0..iterations {
             for rep in entity_reps.iter() {
-                let _ = hashmap.get(rep.as_slice());
+                let _ = hashmap.get(*rep);
             };
         };
     });
@@ -88,7 +90,7 @@ fn test_large() {
     time!("phf_map", 0, {
         for _ in 0..iterations {
             for rep in entity_reps.iter() {
-                let _ = STATIC_MAP.get(rep.as_slice());
+                let _ = STATIC_MAP.get(*rep);
             };
         };
     });
@@ -97,7 +99,7 @@ fn test_large() {
 fn test_small() {
     println!("test_small");
     let values: Vec<&[u8]> = vec![b"anne", b"ane", b"anna", b"ana", b"anene"];
-    let mut fastrie_builder: FastrieBuilderNode<bool> = FastrieBuilderNode::new();
+    let mut fastrie_builder: FastrieBuilderNode<bool> = FastrieBuilderNode::new(IndexWidth(3));
     let mut hashmap_trie: HashMapTrieNode<bool> = HashMapTrieNode::new();
     let mut direct_trie: DirectTrieNode<bool> = DirectTrieNode::new();
     for &v in values.iter() {
@@ -105,8 +107,8 @@ fn test_small() {
         hashmap_trie.add(v, true);
         direct_trie.add(v, true);
     };
-    let fastrie_built = fastrie_builder.prebuild();
-    let fastrie = Fastrie::from_prebuilt(fastrie_built.values.as_slice(), fastrie_built.data.as_slice());
+    let fastrie_built = fastrie_builder.prebuild().unwrap();
+    let fastrie = Fastrie::from_prebuilt(fastrie_built.index_width, fastrie_built.values.as_slice(), fastrie_built.data.as_slice()).unwrap();
 
     let iterations = 100000;
     println!("{} iterations", iterations);
@@ -119,6 +121,14 @@ fn test_small() {
         };
     });
 
+    time!("fastrie_fn", fastrie.memory_size(), {
+        for _ in 0..iterations {
+            for v in values.iter() {
+                let _ = fastrie.with_longest_matching_prefix(v, |end, value| (end, *value));
+            };
+        };
+    });
+
     time!("hashmap_trie", hashmap_trie.memory_size(), {
         for _ in 0..iterations {
             for v in values.iter() {
@@ -165,7 +175,272 @@ fn test_small() {
     });
 }
 
+// Longest key in `map` that is a prefix of `text`, as the inclusive end and the key's value.
+fn fst_longest_matching_prefix(map: &fst::Map<Vec<u8>>, text: &[u8]) -> Option<(usize, u64)> {
+    let fst = map.as_fst();
+    let mut node = fst.root();
+    let mut output = fst::raw::Output::zero();
+    let mut mat = None;
+    for (i, &c) in text.iter().enumerate() {
+        let transition = match node.find_input(c) {
+            Some(t) => node.transition(t),
+            None => break,
+        };
+        output = output.cat(transition.out);
+        node = fst.node(transition.addr);
+        if node.is_final() {
+            mat = Some((i, output.cat(node.final_output()).value()));
+        };
+    };
+    mat
+}
+
+fn test_crates() {
+    println!("test_crates");
+    let entities: HashMap<String, Entity> = read_json("entities");
+    let mut entity_reps: Vec<&str> = entities.keys().map(|rep| rep.as_str()).collect();
+    entity_reps.sort();
+    let characters: Vec<&str> = entity_reps.iter().map(|rep| entities[*rep].characters.as_str()).collect();
+
+    let (fastrie_built, fastrie_build_duration) = {
+        let start = Instant::now();
+        let mut fastrie_builder: FastrieBuilderNode<&str> = FastrieBuilderNode::new(IndexWidth(3));
+        for (rep, characters) in entity_reps.iter().zip(characters.iter()) {
+            fastrie_builder.add(rep.as_bytes(), *characters);
+        };
+        (fastrie_builder.prebuild().unwrap(), start.elapsed().as_secs_f64())
+    };
+    let fastrie = Fastrie::from_prebuilt(fastrie_built.index_width, fastrie_built.values.as_slice(), fastrie_built.data.as_slice()).unwrap();
+
+    // Anchored leftmost-longest finds the longest pattern that is a prefix of the haystack, like `longest_matching_prefix`.
+    let (aho_corasick, aho_corasick_build_duration) = {
+        let start = Instant::now();
+        let aho_corasick = AhoCorasickBuilder::new()
+            .match_kind(MatchKind::LeftmostLongest)
+            .anchored(true)
+            .build(&entity_reps);
+        (aho_corasick, start.elapsed().as_secs_f64())
+    };
+
+    // `fst::Map` values are `u64`, so they're indices into `characters`. Keys must be inserted in sorted order.
+    let (fst_map, fst_build_duration) = {
+        let start = Instant::now();
+        let fst_map = fst::Map::from_iter(entity_reps.iter().enumerate().map(|(i, rep)| (rep, i as u64))).unwrap();
+        (fst_map, start.elapsed().as_secs_f64())
+    };
+
+    println!("{:>15}: {:>10.5} seconds build", "fastrie", fastrie_build_duration);
+    println!("{:>15}: {:>10.5} seconds build", "aho_corasick", aho_corasick_build_duration);
+    println!("{:>15}: {:>10.5} seconds build", "fst", fst_build_duration);
+
+    let iterations = 1000;
+    println!("{} iterations", iterations);
+
+    time!("fastrie", fastrie.memory_size(), {
+        for _ in 0..iterations {
+            for rep in entity_reps.iter() {
+                let _ = fastrie.longest_matching_prefix(rep.as_bytes()).map(|m| m.value);
+            };
+        };
+    });
+
+    time!("aho_corasick", aho_corasick.heap_bytes(), {
+        for _ in 0..iterations {
+            for rep in entity_reps.iter() {
+                let _ = aho_corasick.find(rep).map(|m| characters[m.pattern()]);
+            };
+        };
+    });
+
+    time!("fst", fst_map.as_fst().size(), {
+        for _ in 0..iterations {
+            for rep in entity_reps.iter() {
+                let _ = fst_longest_matching_prefix(&fst_map, rep.as_bytes()).map(|(_, i)| characters[i as usize]);
+            };
+        };
+    });
+}
+
+fn test_index_width() {
+    println!("test_index_width");
+    let entities: HashMap<String, Entity> = read_json("entities");
+    let entity_reps: Vec<&[u8]> = entities.keys().map(|rep| rep.as_bytes()).collect();
+
+    let iterations = 1000;
+    println!("{} iterations", iterations);
+
+    // Every width uses the same per-byte loop, so differences come from the size of the data.
+    for &index_width in &[IndexWidth(3), IndexWidth(4), IndexWidth(5)] {
+        let mut fastrie_builder: FastrieBuilderNode<&str> = FastrieBuilderNode::new(index_width);
+        for (rep, Entity { characters, .. }) in entities.iter() {
+            fastrie_builder.add(rep.as_bytes(), characters.as_str());
+        };
+        let fastrie_built = fastrie_builder.prebuild().unwrap();
+        let fastrie = Fastrie::from_prebuilt(fastrie_built.index_width, fastrie_built.values.as_slice(), fastrie_built.data.as_slice()).unwrap();
+
+        let start = Instant::now();
+        for _ in 0..iterations {
+            for rep in entity_reps.iter() {
+                let _ = fastrie.longest_matching_prefix(rep);
+            };
+        };
+        let duration = start.elapsed().as_secs_f64();
+        println!("{:>15}: {:>10.5} seconds {:>10} size", format!("IndexWidth({})", index_width.0), duration, fastrie.memory_size());
+    };
+}
+
+fn test_long_input() {
+    println!("test_long_input");
+    let keys: Vec<&[u8]> = vec![b"a", b"ab", b"abc", b"b", b"bc"];
+    let mut fastrie_builder: FastrieBuilderNode<usize> = FastrieBuilderNode::new(IndexWidth(1));
+    for (i, &k) in keys.iter().enumerate() {
+        fastrie_builder.add(k, i);
+    };
+    let fastrie_built = fastrie_builder.prebuild().unwrap();
+    let fastrie = Fastrie::from_prebuilt(fastrie_built.index_width, fastrie_built.values.as_slice(), fastrie_built.data.as_slice()).unwrap();
+    // Queries are much longer than the deepest key, so the walk should stop as soon as it reaches a leaf.
+    let queries: Vec<Vec<u8>> = vec![
+        b"abc".iter().chain([b'x'; 4096].iter()).copied().collect(),
+        b"bc".iter().chain([b'x'; 4096].iter()).copied().collect(),
+    ];
+
+    let iterations = 1000000;
+    println!("{} iterations", iterations);
+
+    time!("fastrie", fastrie.memory_size(), {
+        for _ in 0..iterations {
+            for q in queries.iter() {
+                let _ = fastrie.longest_matching_prefix(q);
+            };
+        };
+    });
+}
+
+/// Data that counts the bytes read from it.
+struct CountingSource<'d> {
+    data: &'d [u8],
+    bytes_read: &'d Cell<usize>,
+}
+
+impl ByteSource for CountingSource<'_> {
+    fn read(&self, pos: usize, len: usize) -> Cow<'_, [u8]> {
+        self.bytes_read.set(self.bytes_read.get() + len);
+        Cow::Borrowed(&self.data[pos..pos + len])
+    }
+}
+
+fn test_exact_get() {
+    println!("test_exact_get");
+    // Long keys, where each directory is a key and a prefix of the key of the file in it.
+    let mut keys: Vec<Vec<u8>> = Vec::new();
+    for i in 0..10000 {
+        let dir = format!("/home/user/projects/{}/src/module-{}", "x".repeat(i % 40), i);
+        keys.push(format!("{}/mod.rs", dir).into_bytes());
+        keys.push(dir.into_bytes());
+    };
+    let mut fastrie_builder: FastrieBuilderNode<usize> = FastrieBuilderNode::new(IndexWidth(2));
+    for (i, k) in keys.iter().enumerate() {
+        fastrie_builder.add(k, i);
+    };
+    let fastrie_built = fastrie_builder.prebuild().unwrap();
+    let fastrie = Fastrie::from_prebuilt(fastrie_built.index_width, fastrie_built.values.as_slice(), fastrie_built.data.as_slice()).unwrap();
+
+    // `ChunkedFastrie` shares the walk, and reads through a `ByteSource`, so it can count the bytes `get` reads.
+    let bytes_read = Cell::new(0);
+    let counted = ChunkedFastrie::new(fastrie_built.index_width, fastrie_built.values.as_slice(), CountingSource {
+        data: fastrie_built.data.as_slice(),
+        bytes_read: &bytes_read,
+    }).unwrap();
+    bytes_read.set(0);
+    for k in keys.iter() {
+        let _ = counted.get(k);
+    };
+    println!("{:.1} bytes read per get", bytes_read.get() as f64 / keys.len() as f64);
+
+    let iterations = 100;
+    println!("{} iterations", iterations);
+
+    time!("fastrie", fastrie.memory_size(), {
+        for _ in 0..iterations {
+            for k in keys.iter() {
+                let _ = fastrie.contains_key(k);
+            };
+        };
+    });
+}
+
+fn test_root_dispatch() {
+    println!("test_root_dispatch");
+    // The root is a single cluster spanning a..=z, and queries are one char, so this times looking up a child in the root.
+    let mut fastrie_builder: FastrieBuilderNode<u8> = FastrieBuilderNode::new(IndexWidth(2));
+    for c in b'a'..=b'z' {
+        fastrie_builder.add(&[c], c);
+        fastrie_builder.add(&[c, c], c);
+    };
+    let fastrie_built = fastrie_builder.prebuild().unwrap();
+    let fastrie = Fastrie::from_prebuilt(fastrie_built.index_width, fastrie_built.values.as_slice(), fastrie_built.data.as_slice()).unwrap();
+    let queries: Vec<[u8; 1]> = (0..26).map(|i| [b'a' + (i * 7 % 26) as u8]).collect();
+
+    let iterations = 1000000;
+    println!("{} iterations", iterations);
+
+    time!("fastrie", fastrie.memory_size(), {
+        for _ in 0..iterations {
+            for q in queries.iter() {
+                let _ = fastrie.longest_matching_prefix(q);
+            };
+        };
+    });
+}
+
+fn test_fixed_len() {
+    println!("test_fixed_len");
+    // Pseudo-random 6-byte keys, like MAC addresses or truncated hashes.
+    let mut state: u64 = 0x9E37_79B9_7F4A_7C15;
+    let mut next_key = || {
+        state = state.wrapping_mul(6364136223846793005).wrapping_add(1442695040888963407);
+        state.to_le_bytes()[2..8].to_vec()
+    };
+    let keys: Vec<Vec<u8>> = (0..1_000_000).map(|_| next_key()).collect();
+    // Half of the queries are keys, and half are (almost certainly) not.
+    let queries: Vec<Vec<u8>> = keys.iter().step_by(2).cloned().chain((0..keys.len() / 2).map(|_| next_key())).collect();
+    let mut general_builder: FastrieBuilderNode<usize> = FastrieBuilderNode::new(IndexWidth(3));
+    let mut fixed_len_builder: FastrieBuilderNode<usize> = FastrieBuilderNode::new(IndexWidth(3));
+    for (i, k) in keys.iter().enumerate() {
+        general_builder.add(k, i);
+        fixed_len_builder.add(k, i);
+    };
+    let general_built = general_builder.prebuild().unwrap();
+    let general = Fastrie::from_prebuilt(general_built.index_width, general_built.values.as_slice(), general_built.data.as_slice()).unwrap();
+    let fixed_len_built = fixed_len_builder.prebuild_fixed_len(6).unwrap();
+    let fixed_len = FixedLenFastrie::from_prebuilt(fixed_len_built.values.as_slice(), fixed_len_built.data.as_slice()).unwrap();
+
+    let iterations = 10;
+    println!("{} iterations", iterations);
+
+    time!("general", general.memory_size(), {
+        for _ in 0..iterations {
+            for q in queries.iter() {
+                let _ = general.get(q);
+            };
+        };
+    });
+    time!("fixed_len", fixed_len.memory_size(), {
+        for _ in 0..iterations {
+            for q in queries.iter() {
+                let _ = fixed_len.get(q);
+            };
+        };
+    });
+}
+
 fn main() {
+    test_index_width();
+    test_crates();
     test_large();
     test_small();
+    test_long_input();
+    test_exact_get();
+    test_root_dispatch();
+    test_fixed_len();
 }
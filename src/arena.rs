@@ -0,0 +1,20 @@
+use std::alloc::Layout;
+use std::ptr::NonNull;
+use std::rc::Rc;
+
+use allocator_api2::alloc::{AllocError, Allocator};
+use bumpalo::Bump;
+
+/// Shared handle to the arena of a root builder, which its nodes' child maps allocate from. The arena is freed when the last map holding a handle is dropped, so freeing maps in between is a no-op, except for the last allocation.
+#[derive(Clone, Default)]
+pub(crate) struct Arena(Rc<Bump>);
+
+unsafe impl Allocator for Arena {
+    fn allocate(&self, layout: Layout) -> Result<NonNull<[u8]>, AllocError> {
+        (&*self.0).allocate(layout)
+    }
+
+    unsafe fn deallocate(&self, ptr: NonNull<u8>, layout: Layout) {
+        (&*self.0).deallocate(ptr, layout)
+    }
+}
@@ -0,0 +1,135 @@
+use std::borrow::Cow;
+use std::marker::PhantomData;
+
+use crate::walk::Walker;
+use crate::{FastrieError, FastrieMatch, IndexWidth, Symbol};
+
+/// Storage of built data that can be read by position. Implementations other than `[u8]` allow data to be backed by something other than one contiguous slice, such as several memory mappings.
+pub trait ByteSource {
+    /// Returns the `len` bytes of the data starting at `pos`. Panics if they're out of bounds.
+    fn read(&self, pos: usize, len: usize) -> Cow<'_, [u8]>;
+}
+
+impl ByteSource for [u8] {
+    #[inline(always)]
+    fn read(&self, pos: usize, len: usize) -> Cow<'_, [u8]> {
+        Cow::Borrowed(&self[pos..pos + len])
+    }
+}
+
+/// Data split into consecutive chunks, which are read as if they were concatenated. Reads within a chunk borrow from it; reads spanning chunks are copied.
+pub struct Chunks<'d> {
+    chunks: &'d [&'d [u8]],
+    // Position of the start of each chunk in the concatenated data.
+    starts: Vec<usize>,
+}
+
+impl<'d> Chunks<'d> {
+    /// Returns an error if the chunks have no bytes at all, including if there are no chunks, as built data always starts with its header byte. Other chunks may be empty.
+    pub fn new(chunks: &'d [&'d [u8]]) -> Result<Chunks<'d>, FastrieError> {
+        let mut starts = Vec::with_capacity(chunks.len());
+        let mut start = 0;
+        for chunk in chunks.iter() {
+            starts.push(start);
+            start += chunk.len();
+        };
+        if start == 0 {
+            return Err(FastrieError::TruncatedData { pos: 0 });
+        };
+        Ok(Chunks { chunks, starts })
+    }
+
+    /// Returns the length of the concatenated data.
+    pub fn len(&self) -> usize {
+        self.starts.last().map_or(0, |start| start + self.chunks.last().unwrap().len())
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+}
+
+impl ByteSource for Chunks<'_> {
+    fn read(&self, pos: usize, len: usize) -> Cow<'_, [u8]> {
+        // Empty chunks share their start with the next chunk, so this finds the last chunk starting at or before `pos`, which is the non-empty one. The first chunk starts at 0, which `Chunks::new` ensures exists, so there always is one.
+        let mut chunk_idx = match self.starts.binary_search(&pos) {
+            Ok(mut i) => {
+                while i + 1 < self.starts.len() && self.starts[i + 1] == pos {
+                    i += 1;
+                };
+                i
+            },
+            Err(i) => i - 1,
+        };
+        let offset = pos - self.starts[chunk_idx];
+        let chunk = self.chunks[chunk_idx];
+        if offset + len <= chunk.len() {
+            return Cow::Borrowed(&chunk[offset..offset + len]);
+        };
+        let mut bytes = chunk[offset..].to_vec();
+        while bytes.len() < len {
+            chunk_idx += 1;
+            let chunk = self.chunks[chunk_idx];
+            bytes.extend_from_slice(&chunk[..chunk.len().min(len - bytes.len())]);
+        };
+        Cow::Owned(bytes)
+    }
+}
+
+/// A trie whose built data is read from a [`ByteSource`] instead of a slice, e.g. [`Chunks`] for data that is too large for one contiguous mapping. Queries walk the data exactly like [`crate::Fastrie`].
+///
+/// # Example
+///
+/// ```
+/// use fastrie::*;
+///
+/// let mut builder = FastrieBuilderNode::new(IndexWidth(2));
+/// builder.add(b"hell", 1);
+/// builder.add(b"hello", 2);
+/// builder.add(b"world", 3);
+/// let build = builder.prebuild().unwrap();
+///
+/// let (a, b) = build.data.split_at(5);
+/// let chunks = [a, b];
+/// let trie = ChunkedFastrie::new(build.index_width, &build.values, Chunks::new(&chunks).unwrap()).unwrap();
+/// assert_eq!(trie.longest_matching_prefix(b"hello!").unwrap().value, &2);
+/// assert_eq!(trie.get(b"world"), Some(&3));
+/// assert_eq!(trie.get(b"wor"), None);
+/// ```
+pub struct ChunkedFastrie<'v, V, B, K = u8> {
+    source: B,
+    index_width: IndexWidth,
+    values: &'v [V],
+    symbol_type: PhantomData<K>,
+}
+
+impl<'v, V, B: ByteSource, K: Symbol> ChunkedFastrie<'v, V, B, K> {
+    /// Creates a trie from the values and data of a [`crate::FastrieBuild`], checking that the data starts with the header for `index_width`. Like [`crate::Fastrie::from_prebuilt`], the rest of the data isn't checked.
+    pub fn new(index_width: IndexWidth, values: &'v [V], source: B) -> Result<ChunkedFastrie<'v, V, B, K>, FastrieError> {
+        index_width.check_header(&source.read(0, 1))?;
+        Ok(ChunkedFastrie {
+            source,
+            index_width,
+            values,
+            symbol_type: PhantomData,
+        })
+    }
+
+    fn walker(&self) -> Walker<'_, B, K> {
+        Walker {
+            data: &self.source,
+            index_width: self.index_width,
+            symbol_type: PhantomData,
+        }
+    }
+
+    /// Like [`crate::Fastrie::longest_matching_prefix`].
+    pub fn longest_matching_prefix(&self, text: &[K]) -> Option<FastrieMatch<'v, V>> {
+        self.walker().longest_matching_prefix_counted(text, &mut ()).0.map(|(len, value_idx)| FastrieMatch::new(0, len, &self.values[value_idx], value_idx))
+    }
+
+    /// Like [`crate::Fastrie::get`].
+    pub fn get(&self, key: &[K]) -> Option<&'v V> {
+        self.walker().exact_value_idx(key).map(|value_idx| &self.values[value_idx])
+    }
+}
@@ -0,0 +1,93 @@
+use crate::records::{patch_record, record_len};
+use crate::{Fastrie, FastrieBuild, FastrieError, ROOT_POS};
+
+impl<V> FastrieBuild<V> {
+    /// Returns whether each value is referred to by some node, after checking that every node refers to an existing value.
+    fn referenced_values(&self) -> Result<Vec<bool>, FastrieError> {
+        let mut referenced = vec![false; self.values.len()];
+        let mut pos = ROOT_POS;
+        while pos < self.data.len() {
+            let stored_value_idx = self.index_width.read_idx(&self.data, pos);
+            if stored_value_idx != 0 {
+                let value_idx = stored_value_idx - 1;
+                *referenced.get_mut(value_idx).ok_or(FastrieError::ValueIndexOutOfRange { idx: value_idx, len: self.values.len() })? = true;
+            };
+            pos += record_len(&self.data, self.index_width, pos);
+        };
+        Ok(referenced)
+    }
+
+    /// Checks that the data is valid as by [`Fastrie::verify`], that every value index in the data refers to an existing value, and that every value is referred to by some node, i.e. none are orphaned. This only supports builds of byte keys.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use fastrie::*;
+    ///
+    /// let mut builder = FastrieBuilderNode::new(IndexWidth(2));
+    /// builder.add(b"hell", 1);
+    /// builder.add(b"hello", 2);
+    /// let mut build = builder.prebuild().unwrap();
+    /// assert_eq!(build.verify_values(), Ok(()));
+    ///
+    /// build.values.push(3);
+    /// assert_eq!(build.verify_values(), Err(FastrieError::OrphanedValue { value_idx: 2 }));
+    /// build.values.truncate(1);
+    /// assert_eq!(build.verify_values(), Err(FastrieError::ValueIndexOutOfRange { idx: 1, len: 1 }));
+    /// ```
+    pub fn verify_values(&self) -> Result<(), FastrieError> {
+        Fastrie::from_prebuilt(self.index_width, &self.values, &self.data)?.verify()?;
+        match self.referenced_values()?.iter().position(|referenced| !referenced) {
+            Some(value_idx) => Err(FastrieError::OrphanedValue { value_idx }),
+            None => Ok(()),
+        }
+    }
+
+    /// Removes values that no node refers to, and rewrites the value indices in the data to match. Remaining values keep their order. This only supports builds of byte keys.
+    ///
+    /// Panics if the data refers to a value that doesn't exist.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use fastrie::*;
+    ///
+    /// let mut builder = FastrieBuilderNode::new(IndexWidth(2));
+    /// builder.add(b"hell", "hell");
+    /// builder.add(b"hello", "hello");
+    /// let mut build = builder.prebuild().unwrap();
+    /// // Pretend "hell" is no longer a key by clearing the value index and value bit of its node, which follows the root's path, orphaning its value.
+    /// let layout = Layout::new(build.index_width);
+    /// let hell_pos = Layout::HEADER_LEN + layout.node_header_len() + layout.path_len(4);
+    /// assert_eq!(build.data[hell_pos], 1);
+    /// build.data[hell_pos] = 0;
+    /// build.data[hell_pos + layout.node_header_len() - 1] &= !Layout::HAS_VALUE;
+    /// assert_eq!(build.verify_values(), Err(FastrieError::OrphanedValue { value_idx: 0 }));
+    ///
+    /// build.compact();
+    /// assert_eq!(build.values, vec!["hello"]);
+    /// assert_eq!(build.verify_values(), Ok(()));
+    /// let trie = Fastrie::from_prebuilt(build.index_width, &build.values, &build.data).unwrap();
+    /// assert_eq!(trie.get(b"hell"), None);
+    /// assert_eq!(trie.get(b"hello"), Some(&"hello"));
+    /// ```
+    pub fn compact(&mut self) {
+        let referenced = self.referenced_values().expect("data refers to a value that doesn't exist");
+        // Stored value indices are one more than the value index, so `new_stored_value_idx[stored]` is the new stored value index of a referenced value.
+        let mut new_stored_value_idx = vec![0; self.values.len() + 1];
+        let mut kept = 0;
+        for (value_idx, &referenced) in referenced.iter().enumerate() {
+            if referenced {
+                kept += 1;
+                new_stored_value_idx[value_idx + 1] = kept;
+            };
+        };
+        let mut pos = ROOT_POS;
+        while pos < self.data.len() {
+            patch_record(&mut self.data, self.index_width, pos, &|child_idx| child_idx, &|stored_value_idx| new_stored_value_idx[stored_value_idx]);
+            pos += record_len(&self.data, self.index_width, pos);
+        };
+        let mut referenced = referenced.into_iter();
+        self.values.retain(|_| referenced.next().unwrap());
+    }
+}
@@ -0,0 +1,129 @@
+use crate::records::{patch_record, record_len};
+use crate::{FastrieBuild, FastrieBuilderNode, IndexWidth, Layout, ROOT_POS};
+
+impl<V> FastrieBuild<V> {
+    /// Assembles byte-keyed builds of shards into one build, where each shard's keys are prefixed by its prefix, without reconstructing keys. A small routing trie is built over the prefixes, and each shard's data is copied beneath it with its value indices rebased. Values are concatenated in order of prefix, so they remain ordered by key.
+    ///
+    /// Panics if there are no shards, the shards don't all have the same index width, a prefix is a prefix of another shard's prefix, or the assembled build has too many values for the index width.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use fastrie::*;
+    ///
+    /// let mut html = FastrieBuilderNode::new(IndexWidth(2));
+    /// html.add(b"amp;", "&");
+    /// html.add(b"lt;", "<");
+    /// let mut xml = FastrieBuilderNode::new(IndexWidth(2));
+    /// xml.add(b"apos;", "'");
+    /// let build = FastrieBuild::concat(vec![
+    ///     (b"&".to_vec(), html.prebuild().unwrap()),
+    ///     (b"xml:&".to_vec(), xml.prebuild().unwrap()),
+    /// ]);
+    /// assert_eq!(build.values, vec!["&", "<", "'"]);
+    ///
+    /// let trie = Fastrie::from_prebuilt(build.index_width, &build.values, &build.data).unwrap();
+    /// assert_eq!(trie.verify(), Ok(()));
+    /// assert_eq!(trie.get(b"&lt;"), Some(&"<"));
+    /// assert_eq!(trie.get(b"xml:&apos;"), Some(&"'"));
+    /// assert_eq!(trie.get(b"&apos;"), None);
+    /// ```
+    pub fn concat(mut shards: Vec<(Vec<u8>, FastrieBuild<V>)>) -> FastrieBuild<V> {
+        assert!(!shards.is_empty(), "no shards to concatenate");
+        let index_width = shards[0].1.index_width;
+        assert!(shards.iter().all(|(_, shard)| shard.index_width == index_width), "shards have different index widths");
+        // Sorted, a prefix of another prefix sorts immediately before some prefix it's a prefix of.
+        let mut prefixes = shards.iter().map(|(prefix, _)| prefix.as_slice()).collect::<Vec<&[u8]>>();
+        prefixes.sort_unstable();
+        assert!(prefixes.windows(2).all(|w| !w[1].starts_with(w[0])), "shard prefixes overlap");
+        // Empty shards have no keys to route to, and their root would be a leaf without a value.
+        shards.retain(|(_, shard)| !shard.is_empty());
+
+        let build_router = |min_child_width: IndexWidth| {
+            let mut router: FastrieBuilderNode<usize> = FastrieBuilderNode::new(index_width);
+            for (i, (prefix, _)) in shards.iter().enumerate() {
+                router.add(prefix, i);
+            };
+            let mut values = Vec::new();
+            router._assign_value_indices(&mut |value| {
+                values.push(value);
+                values.len() - 1
+            });
+            let mut data = vec![index_width.header_byte()];
+            router._write_records(index_width, min_child_width, router.cluster_options(), &mut data).unwrap();
+            FastrieBuild {
+              data,
+              index_width,
+              values,
+            }
+        };
+        // Routing nodes' children move away from them by the length of the shards spliced in between, so their child indices must be wide enough for any distance within the assembled data, which is no longer than the routing trie and every shard.
+        let shards_len = shards.iter().map(|(_, shard)| shard.data.len() - ROOT_POS).sum::<usize>();
+        let router = (1..=8).map(IndexWidth).find_map(|min_child_width| {
+            let router = build_router(min_child_width);
+            if min_child_width.can_represent(router.data.len() + shards_len) { Some(router) } else { None }
+        }).unwrap();
+
+        // Every routing node with a value is a leaf, which is replaced by the records of the shard it routes to.
+        let leaf_len = Layout::new(index_width).node_header_len();
+        // Positions of the replaced leaves, and the total bytes added by replacing each and those before it.
+        let mut spliced_leaves: Vec<(usize, usize)> = Vec::new();
+        let mut added = 0;
+        let mut pos = ROOT_POS;
+        while pos < router.data.len() {
+            let value_idx = index_width.read_idx(&router.data, pos);
+            if value_idx != 0 {
+                let shard_idx = router.values[value_idx - 1];
+                added += shards[shard_idx].1.data.len() - ROOT_POS - leaf_len;
+                spliced_leaves.push((pos, added));
+            };
+            pos += record_len(&router.data, index_width, pos);
+        };
+        let map_router_pos = |pos: usize| match spliced_leaves.partition_point(|(leaf_pos, _)| *leaf_pos < pos) {
+            0 => pos,
+            i => pos + spliced_leaves[i - 1].1,
+        };
+
+        // The routing trie's values are the shards in order of prefix, which is the order their values are concatenated in.
+        let mut shards = shards.into_iter().map(|(_, shard)| Some(shard)).collect::<Vec<_>>();
+        let mut value_offsets = Vec::with_capacity(router.values.len());
+        let mut values_len = 0;
+        for &shard_idx in router.values.iter() {
+            value_offsets.push(values_len);
+            values_len += shards[shard_idx].as_ref().unwrap().values.len();
+        };
+
+        let mut data = router.data[..ROOT_POS].to_vec();
+        let mut shard_values = Vec::with_capacity(router.values.len());
+        let mut pos = ROOT_POS;
+        while pos < router.data.len() {
+            let len = record_len(&router.data, index_width, pos);
+            let value_idx = index_width.read_idx(&router.data, pos);
+            if value_idx == 0 {
+                let new_pos = data.len();
+                data.extend_from_slice(&router.data[pos..pos + len]);
+                patch_record(&mut data, index_width, new_pos, &|child_idx| map_router_pos(pos + child_idx) - new_pos, &|_| unreachable!());
+            } else {
+                let shard = shards[router.values[value_idx - 1]].take().unwrap();
+                let value_offset = value_offsets[value_idx - 1];
+                let mut record_pos = data.len();
+                data.extend_from_slice(&shard.data[ROOT_POS..]);
+                while record_pos < data.len() {
+                    // Child indices are relative, so they're the same wherever the shard is.
+                    patch_record(&mut data, index_width, record_pos, &|child_idx| child_idx, &|value_idx| value_idx + value_offset);
+                    record_pos += record_len(&data, index_width, record_pos);
+                };
+                shard_values.push((value_idx, shard.values));
+            };
+            pos += len;
+        };
+        shard_values.sort_unstable_by_key(|(value_idx, _)| *value_idx);
+        let values = shard_values.into_iter().flat_map(|(_, values)| values).collect::<Vec<V>>();
+        assert!(index_width.can_represent(values.len()), "concatenated build has too many values for the index width");
+        FastrieBuild {
+          data,
+          index_width,
+          values,
+        }
+    }
+}
@@ -0,0 +1,74 @@
+use std::collections::HashMap;
+use std::hash::Hash;
+
+use crate::{FastrieBuild, FastrieBuilderNode, Symbol};
+
+impl<V: Hash + Eq> FastrieBuild<V> {
+    /// Returns how many times each distinct value occurs in `values`. Unless built by [`FastrieBuilderNode::prebuild_dedup`], this is how many keys have each value.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use fastrie::*;
+    ///
+    /// let mut builder = FastrieBuilderNode::new(IndexWidth(2));
+    /// builder.add(b"amp", "&");
+    /// builder.add(b"AMP", "&");
+    /// builder.add(b"lt", "<");
+    /// let build = builder.prebuild().unwrap();
+    /// let histogram = build.value_histogram();
+    /// assert_eq!(histogram[&"&"], 2);
+    /// assert_eq!(histogram[&"<"], 1);
+    /// ```
+    pub fn value_histogram(&self) -> HashMap<&V, usize> {
+        let mut histogram = HashMap::new();
+        for value in self.values.iter() {
+            *histogram.entry(value).or_insert(0) += 1;
+        };
+        histogram
+    }
+}
+
+impl<V: Hash + Eq, K: Symbol> FastrieBuilderNode<V, K> {
+    /// Returns pairs of different keys that have equal values, to check for keys that shouldn't share a value. Each key having the same value as a lexicographically earlier key is paired with the first key having it, so a value shared by `n` keys produces `n - 1` pairs. Pairs are ordered by their second key. Values are consumed by building, so this should be called before building.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use fastrie::*;
+    ///
+    /// let mut builder = FastrieBuilderNode::new(IndexWidth(2));
+    /// builder.add(b"amp", "&");
+    /// builder.add(b"AMP", "&");
+    /// builder.add(b"Amp", "&");
+    /// builder.add(b"lt", "<");
+    /// assert_eq!(builder.duplicate_values(), vec![
+    ///     (b"AMP".to_vec(), b"Amp".to_vec()),
+    ///     (b"AMP".to_vec(), b"amp".to_vec()),
+    /// ]);
+    /// ```
+    pub fn duplicate_values(&self) -> Vec<(Vec<K>, Vec<K>)> {
+        let mut first_keys: HashMap<&V, Vec<K>> = HashMap::new();
+        let mut duplicates = Vec::new();
+        let mut key = Vec::new();
+        // Visited like `_for_each_node_mut`. Each node is stacked with its char and the length of its parent's key, which `key` is truncated to before appending the char.
+        let mut stack: Vec<(&FastrieBuilderNode<V, K>, Option<K>, usize)> = vec![(self, None, 0)];
+        while let Some((node, c, parent_key_len)) = stack.pop() {
+            key.truncate(parent_key_len);
+            key.extend(c);
+            if let Some(value) = &node.value {
+                match first_keys.get(value) {
+                    Some(first_key) => duplicates.push((first_key.clone(), key.clone())),
+                    None => {
+                        first_keys.insert(value, key.clone());
+                    }
+                };
+            };
+            let mut children = node.children.iter().collect::<Vec<_>>();
+            // Popped in ascending order of char, so keys are visited in lexicographic order.
+            children.sort_by(|(a, _), (b, _)| b.cmp(a));
+            stack.extend(children.into_iter().map(|(&c, child)| (child, Some(c), key.len())));
+        };
+        duplicates
+    }
+}
@@ -0,0 +1,67 @@
+use std::marker::PhantomData;
+
+use crate::walk::{ConstIndexWidth, Walker};
+use crate::{FastrieError, FastrieMatch, IndexWidth, Symbol};
+
+/// A trie whose index width is the compile-time constant `W`, so the compiler can unroll index reads for it instead of looping over a run-time [`IndexWidth`]. Data must have been built with `IndexWidth(W)`. Queries walk the data exactly like [`crate::Fastrie`].
+///
+/// Index reads are a small part of a query, and on the HTML entities benchmark this isn't measurably faster than `Fastrie` with the same width, so only use it if the `index_width` group of the bench crate shows a win for your data and target.
+///
+/// Data built with `IndexWidth(4)` stores every value index as a little endian `u32`, which is simple for other languages to read, and its header records the width, so `FixedFastrie::<_, 4>` rejects data built with any other width.
+///
+/// # Example
+///
+/// ```
+/// use fastrie::*;
+///
+/// let mut builder = FastrieBuilderNode::new(IndexWidth(3));
+/// builder.add(b"hell", 1);
+/// builder.add(b"hello", 2);
+/// let build = builder.prebuild().unwrap();
+///
+/// let trie = FixedFastrie::<_, 3>::from_prebuilt(&build.values, &build.data).unwrap();
+/// assert_eq!(trie.longest_matching_prefix(b"hello!").unwrap().value, &2);
+/// assert_eq!(trie.get(b"hell"), Some(&1));
+/// assert_eq!(trie.get(b"hel"), None);
+///
+/// let err = FixedFastrie::<_, 4>::from_prebuilt(&build.values, &build.data).err();
+/// assert_eq!(err, Some(FastrieError::IndexWidthMismatch { given: IndexWidth(4), built: IndexWidth(3) }));
+/// ```
+pub struct FixedFastrie<'v, 'd, V, const W: usize, K = u8> {
+    data: &'d [u8],
+    values: &'v [V],
+    symbol_type: PhantomData<K>,
+}
+
+impl<'v, 'd, V, const W: usize, K: Symbol> FixedFastrie<'v, 'd, V, W, K> {
+    /// Like [`crate::Fastrie::from_prebuilt`], with the index width `W`.
+    pub fn from_prebuilt(values: &'v [V], data: &'d [u8]) -> Result<FixedFastrie<'v, 'd, V, W, K>, FastrieError> {
+        if !(1..=8).contains(&W) {
+            return Err(FastrieError::InvalidIndexWidth(W));
+        };
+        IndexWidth(W).check_header(data)?;
+        Ok(FixedFastrie {
+            data,
+            values,
+            symbol_type: PhantomData,
+        })
+    }
+
+    fn walker(&self) -> Walker<'d, [u8], K, ConstIndexWidth<W>> {
+        Walker {
+            data: self.data,
+            index_width: ConstIndexWidth,
+            symbol_type: PhantomData,
+        }
+    }
+
+    /// Like [`crate::Fastrie::longest_matching_prefix`].
+    pub fn longest_matching_prefix(&self, text: &[K]) -> Option<FastrieMatch<'v, V>> {
+        self.walker().longest_matching_prefix_counted(text, &mut ()).0.map(|(len, value_idx)| FastrieMatch::new(0, len, &self.values[value_idx], value_idx))
+    }
+
+    /// Like [`crate::Fastrie::get`].
+    pub fn get(&self, key: &[K]) -> Option<&'v V> {
+        self.walker().exact_value_idx(key).map(|value_idx| &self.values[value_idx])
+    }
+}
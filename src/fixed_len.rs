@@ -0,0 +1,224 @@
+use std::cmp::{Ordering, Reverse};
+use std::convert::TryInto;
+
+use crate::{AlreadyBuilt, FastrieBuilderNode, FastrieError};
+
+/// Header byte at the start of data built by [`FastrieBuilderNode::prebuild_fixed_len`]. Its high bits differ from those of data built by [`FastrieBuilderNode::prebuild`], so neither loads as the other.
+const FIXED_LEN_MAGIC: u8 = 0xE0;
+/// Maximum amount of leading levels that are flattened into the offset table, which has 256 entries per level.
+const MAX_PREFIX_LEN: usize = 2;
+
+// Fixed length layout, where all integers are u32 little endian:
+// - FIXED_LEN_MAGIC
+// - key length (1 byte)
+// - prefix length (1 byte), the amount of leading key bytes resolved by the offset table
+// - 256^prefix length + 1 offsets, where the keys whose leading bytes are `b` as a big endian integer are the tails `offsets[b]..offsets[b + 1]`
+// - tails, which are the remaining bytes of every key, in key order
+//
+// Every key ends at the last level, so no node needs a value index or a children-present byte, and a key's value index is its position in key order, which is the order values are built in.
+const HEADER_LEN: usize = 3;
+
+fn read_u32(bytes: &[u8], pos: usize) -> usize {
+    u32::from_le_bytes(bytes[pos..pos + 4].try_into().unwrap()) as usize
+}
+
+fn bucket_count(prefix_len: usize) -> usize {
+    1 << (8 * prefix_len)
+}
+
+fn bucket_of(prefix: &[u8]) -> usize {
+    prefix.iter().fold(0, |bucket, &c| (bucket << 8) | c as usize)
+}
+
+fn data_len(key_len: usize, prefix_len: usize, key_count: usize) -> usize {
+    HEADER_LEN + (bucket_count(prefix_len) + 1) * 4 + key_count * (key_len - prefix_len)
+}
+
+/// Writes the data of `keys`, which must be sorted and all `key_len` bytes.
+fn build_fixed_len(key_len: usize, keys: &[Vec<u8>]) -> Vec<u8> {
+    // Flatten as many leading levels as make the data smallest, preferring more for fewer comparisons.
+    let prefix_len = (0..=key_len.min(MAX_PREFIX_LEN)).min_by_key(|&p| (data_len(key_len, p, keys.len()), Reverse(p))).unwrap();
+    let mut data = Vec::with_capacity(data_len(key_len, prefix_len, keys.len()));
+    data.push(FIXED_LEN_MAGIC);
+    data.push(key_len as u8);
+    data.push(prefix_len as u8);
+    let mut key_idx = 0;
+    for bucket in 0..=bucket_count(prefix_len) {
+        while key_idx < keys.len() && bucket_of(&keys[key_idx][..prefix_len]) < bucket {
+            key_idx += 1;
+        };
+        let offset: u32 = key_idx.try_into().expect("too many keys");
+        data.extend_from_slice(&offset.to_le_bytes());
+    };
+    for key in keys {
+        data.extend_from_slice(&key[prefix_len..]);
+    };
+    data
+}
+
+/// Data and values built by [`FastrieBuilderNode::prebuild_fixed_len`], which can be loaded with [`FixedLenFastrie::from_prebuilt`].
+pub struct FixedLenBuild<V> {
+    pub data: Vec<u8>,
+    pub values: Vec<V>,
+}
+
+impl<V> FastrieBuilderNode<V> {
+    /// Builds the trie like [`FastrieBuilderNode::prebuild`], but for keys that are all `key_len` bytes, such as short hashes or MAC addresses, into the layout of [`FixedLenFastrie`]. Values are ordered as by `prebuild`. Returns an error if this builder has already been built.
+    ///
+    /// As every key ends at the same depth, no node stores whether it has a value, and a key's value index is its position in key order. The leading levels are flattened into a table of offsets indexed by the first bytes of the key, and the rest of each key is stored as a sorted tail, so there are no per-node headers, clusters, or child indices.
+    ///
+    /// Panics if a key isn't `key_len` bytes, or `key_len` is more than 255.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use fastrie::*;
+    ///
+    /// let mut builder = FastrieBuilderNode::new(IndexWidth(2));
+    /// builder.add(&[0x00, 0x1b, 0x63, 0x84, 0x45, 0xe6], "apple");
+    /// builder.add(&[0x3c, 0x5a, 0xb4, 0x00, 0x00, 0x01], "google");
+    /// let build = builder.prebuild_fixed_len(6).unwrap();
+    ///
+    /// let trie = FixedLenFastrie::from_prebuilt(&build.values, &build.data).unwrap();
+    /// assert_eq!(trie.get(&[0x3c, 0x5a, 0xb4, 0x00, 0x00, 0x01]), Some(&"google"));
+    /// assert_eq!(trie.get(&[0x3c, 0x5a, 0xb4, 0x00, 0x00, 0x02]), None);
+    /// ```
+    pub fn prebuild_fixed_len(&mut self, key_len: usize) -> Result<FixedLenBuild<V>, AlreadyBuilt> {
+        if self.built {
+            return Err(AlreadyBuilt);
+        };
+        assert!(key_len <= 255, "key length is more than 255");
+        // Keys in the order values are assigned, which is key order.
+        let mut keys = Vec::new();
+        let mut stack = vec![(Vec::new(), &*self)];
+        while let Some((key, node)) = stack.pop() {
+            if node.has_value() {
+                assert_eq!(key.len(), key_len, "key isn't {} bytes", key_len);
+                keys.push(key.clone());
+            };
+            let mut children = node.children.iter().collect::<Vec<_>>();
+            children.sort_by(|(a, _), (b, _)| b.cmp(a));
+            for (&c, child) in children {
+                let mut child_key = key.clone();
+                child_key.push(c);
+                stack.push((child_key, child));
+            };
+        };
+        let mut values = Vec::with_capacity(keys.len());
+        self._assign_value_indices(&mut |value| {
+            values.push(value);
+            values.len() - 1
+        });
+        self._set_built(true);
+        Ok(FixedLenBuild {
+            data: build_fixed_len(key_len, &keys),
+            values,
+        })
+    }
+}
+
+/// A trie of keys that are all the same length, built by [`FastrieBuilderNode::prebuild_fixed_len`]. It's smaller than the general layout for such keys, and a lookup is one offset table read and a binary search of a few tails.
+///
+/// Queries only take keys of the built length, and panic otherwise.
+pub struct FixedLenFastrie<'v, 'd, V> {
+    key_len: usize,
+    prefix_len: usize,
+    offsets: &'d [u8],
+    tails: &'d [u8],
+    values: &'v [V],
+}
+
+impl<'v, 'd, V> FixedLenFastrie<'v, 'd, V> {
+    /// Loads data built by [`FastrieBuilderNode::prebuild_fixed_len`], checking that its offsets are consistent. Like [`crate::Fastrie::from_prebuilt`], the values aren't checked against the data.
+    pub fn from_prebuilt(values: &'v [V], data: &'d [u8]) -> Result<FixedLenFastrie<'v, 'd, V>, FastrieError> {
+        if data.len() < HEADER_LEN {
+            return Err(FastrieError::TruncatedData { pos: 0 });
+        };
+        if data[0] != FIXED_LEN_MAGIC {
+            return Err(FastrieError::BadMagic);
+        };
+        let key_len = data[1] as usize;
+        let prefix_len = data[2] as usize;
+        if prefix_len > key_len.min(MAX_PREFIX_LEN) {
+            return Err(FastrieError::MalformedData { pos: 2 });
+        };
+        let offsets_end = HEADER_LEN + (bucket_count(prefix_len) + 1) * 4;
+        if data.len() < offsets_end {
+            return Err(FastrieError::TruncatedData { pos: data.len() });
+        };
+        let offsets = &data[HEADER_LEN..offsets_end];
+        let tails = &data[offsets_end..];
+        let tail_len = key_len - prefix_len;
+        // Offsets must start at zero and be non-decreasing, and the last must be the amount of tails. Without tails, a bucket is a whole key, so it has at most one.
+        let mut last_offset = 0;
+        for bucket in 0..=bucket_count(prefix_len) {
+            let offset = read_u32(offsets, bucket * 4);
+            if offset < last_offset || (bucket == 0 && offset != 0) || (tail_len == 0 && offset - last_offset > 1) {
+                return Err(FastrieError::MalformedData { pos: HEADER_LEN + bucket * 4 });
+            };
+            last_offset = offset;
+        };
+        if last_offset * tail_len != tails.len() {
+            return Err(FastrieError::MalformedData { pos: offsets_end });
+        };
+        Ok(FixedLenFastrie {
+            key_len,
+            prefix_len,
+            offsets,
+            tails,
+            values,
+        })
+    }
+
+    /// Returns the length of every key.
+    pub fn key_len(&self) -> usize {
+        self.key_len
+    }
+
+    /// Returns the amount of keys.
+    pub fn len(&self) -> usize {
+        read_u32(self.offsets, self.offsets.len() - 4)
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    pub fn memory_size(&self) -> usize {
+        HEADER_LEN + self.offsets.len() + self.tails.len()
+    }
+
+    /// Returns the zero-based value index of `key`, which is its position in key order, or `None` if it isn't a key.
+    ///
+    /// Panics if `key` isn't [`FixedLenFastrie::key_len`] bytes.
+    pub fn key_index(&self, key: &[u8]) -> Option<usize> {
+        assert_eq!(key.len(), self.key_len, "key isn't {} bytes", self.key_len);
+        let (prefix, tail) = key.split_at(self.prefix_len);
+        let bucket = bucket_of(prefix);
+        let (mut lo, mut hi) = (read_u32(self.offsets, bucket * 4), read_u32(self.offsets, (bucket + 1) * 4));
+        let tail_len = tail.len();
+        while lo < hi {
+            let mid = lo + (hi - lo) / 2;
+            match self.tails[mid * tail_len..(mid + 1) * tail_len].cmp(tail) {
+                Ordering::Less => lo = mid + 1,
+                Ordering::Greater => hi = mid,
+                Ordering::Equal => return Some(mid),
+            };
+        };
+        None
+    }
+
+    /// Like [`crate::Fastrie::get`].
+    ///
+    /// Panics if `key` isn't [`FixedLenFastrie::key_len`] bytes.
+    pub fn get(&self, key: &[u8]) -> Option<&'v V> {
+        self.key_index(key).map(|value_idx| &self.values[value_idx])
+    }
+
+    /// Like [`crate::Fastrie::contains_key`].
+    ///
+    /// Panics if `key` isn't [`FixedLenFastrie::key_len`] bytes.
+    pub fn contains_key(&self, key: &[u8]) -> bool {
+        self.key_index(key).is_some()
+    }
+}
@@ -0,0 +1,71 @@
+use crate::{Fastrie, FastrieBuilderNode, FastrieMatch, IndexWidth};
+
+/// A prebuilt [`Fastrie`] combined with a small mutable overlay of keys added after the build, queried as a union.
+///
+/// The base stays in its compact flat form; every key added afterwards lives in the overlay, which is an unbuilt [`FastrieBuilderNode`] holding one `HashMap` per node. This avoids a full rebuild for each addition, but the overlay costs far more memory per key than the base and every query walks both structures, so it's intended for a slowly growing number of keys. Once the overlay becomes large, rebuild a new base from all keys.
+///
+/// # Example
+///
+/// ```
+/// use fastrie::*;
+///
+/// let mut builder = FastrieBuilderNode::new(IndexWidth(2));
+/// builder.add(b"hell", 1);
+/// builder.add(b"hello", 2);
+/// let build = builder.prebuild().unwrap();
+///
+/// let mut trie = GrowableFastrie::new(Fastrie::from_prebuilt(build.index_width, &build.values, &build.data).unwrap());
+/// trie.add(b"hello", 20);
+/// trie.add(b"hello world", 30);
+///
+/// // The overlay shadows the base for the same key.
+/// let mat = trie.longest_matching_prefix(b"hello!").unwrap();
+/// assert_eq!(mat.end, 4);
+/// assert_eq!(mat.value, &20);
+/// // Keys only in the base are still found.
+/// assert_eq!(trie.longest_matching_prefix(b"hell's").unwrap().value, &1);
+/// // The longest match wins across both.
+/// assert_eq!(trie.longest_matching_prefix(b"hello world!").unwrap().value, &30);
+/// assert!(trie.contains_key(b"hell"));
+/// assert!(trie.contains_key(b"hello world"));
+/// assert!(!trie.contains_key(b"hello w"));
+/// ```
+pub struct GrowableFastrie<'v, 'd, V> {
+    base: Fastrie<'v, 'd, V>,
+    overlay: FastrieBuilderNode<V>,
+}
+
+impl<'v, 'd, V> GrowableFastrie<'v, 'd, V> {
+    pub fn new(base: Fastrie<'v, 'd, V>) -> GrowableFastrie<'v, 'd, V> {
+        GrowableFastrie {
+            base,
+            // The overlay is never built, so its index width is irrelevant.
+            overlay: FastrieBuilderNode::new(IndexWidth(1)),
+        }
+    }
+
+    /// Adds a key to the overlay. If the key also exists in the base, the overlay's value takes precedence.
+    pub fn add(&mut self, pattern: &[u8], value: V) {
+        self.overlay.add(pattern, value);
+    }
+
+    pub fn base(&self) -> &Fastrie<'v, 'd, V> {
+        &self.base
+    }
+
+    pub fn contains_key(&self, key: &[u8]) -> bool {
+        self.overlay._longest_matching_prefix(key).filter(|(len, _)| *len == key.len()).is_some()
+            || self.base.contains_key(key)
+    }
+
+    /// Returns the longest match across the base and the overlay. If both match with the same length, the overlay's match is returned.
+    pub fn longest_matching_prefix(&self, text: &[u8]) -> Option<FastrieMatch<'_, V>> {
+        let overlay_match = self.overlay._longest_matching_prefix(text);
+        let base_match = self.base.longest_matching_prefix(text);
+        match (overlay_match, base_match) {
+            (Some((len, _)), Some(b)) if b.len > len => Some(b),
+            (Some((len, value)), _) => Some(FastrieMatch::new(0, len, value, usize::MAX)),
+            (None, b) => b,
+        }
+    }
+}
@@ -0,0 +1,115 @@
+use crate::{cluster_bitmap_len, IndexWidth, Symbol};
+
+/// Describes the layout of built data, so that external tools can compute offsets without re-deriving them from the query code.
+///
+/// Value indices are `index_width` bytes, and child indices are the child width of their node, both little endian. Chars are bytes, or [`Symbol::WIDTH`] bytes little endian for tries keyed by wider symbols; the lengths here are for bytes. Built data starts with a header byte, which is `0xF0` ORed with the index width, followed by a sequence of nodes starting with the root at position [`Layout::HEADER_LEN`]. Each node is:
+///
+/// - value index: one plus the index into the values, or zero if the node has no value;
+/// - children-present byte: the low three bits are 1 if clusters follow, 2 if a single child follows, 4 if a path follows, 0 if the node has no children; the next bit ([`Layout::HAS_VALUE`]) is set if the node has a value, so readers can skip its value index otherwise; if clusters or a single child follow, the next three bits are one less than the node's child width (see [`Layout::child_width`]), which is the smallest width that can represent the node's largest child index;
+/// - if the node starts a chain of two or more chars through nodes without values that each have exactly one child, a path of at most 255 chars, which is its length as one byte followed by its chars, immediately followed by the node at the end of the path (which has no index, as it always follows);
+/// - if the node has exactly one child otherwise, its char and its child index;
+/// - if the node has more than one child, one or more clusters, each of which is:
+///   - next cluster distance: one byte, the distance from the start of this cluster to the next cluster of the same node, or zero if it's the last, so clusters are at most 255 bytes long;
+///   - min char and max char (inclusive) covered by the cluster;
+///   - bitmap of present chars in `min..=max`, one bit per char starting from the least significant bit of the first byte, omitted if `min == max`;
+///   - child index for each present char, in char order.
+///
+/// A child index is the distance from the start of the node to the start of the child, as children always follow their parent. Nodes deep in the trie only point past their own small subtrees, so most child indices are narrower than the index width.
+///
+/// Clusters of a node are contiguous and immediately follow its header, with the clusters covering more chars first. The node's children follow its clusters in the same order as their indices, each followed by its own descendants (i.e. depth first).
+///
+/// # Example
+///
+/// ```
+/// use fastrie::*;
+///
+/// let mut builder = FastrieBuilderNode::new(IndexWidth(2));
+/// // The root has one cluster `a..=c` with two children, and `a` has a single child.
+/// builder.add(b"ab", ());
+/// builder.add(b"c", ());
+/// let build = builder.prebuild().unwrap();
+///
+/// let layout = Layout::new(build.index_width);
+/// assert_eq!(layout.node_header_len(), 3);
+/// assert_eq!(layout.cluster_header_len(), 3);
+/// // Every child is within 255 bytes of its parent, so child indices are one byte.
+/// assert_eq!(layout.child_width(build.data[Layout::HEADER_LEN + layout.node_header_len() - 1]), IndexWidth(1));
+/// let root_len = layout.node_header_len() + layout.cluster_len(b'a', b'c', 2, IndexWidth(1));
+/// let a_len = layout.node_header_len() + layout.single_child_len(IndexWidth(1));
+/// let leaf_len = layout.node_header_len();
+/// assert_eq!(build.data[0], 0xF2);
+/// assert_eq!(build.data.len(), Layout::HEADER_LEN + root_len + a_len + leaf_len + leaf_len);
+/// // The first child of the root immediately follows the root.
+/// let first_child_slot = Layout::HEADER_LEN + layout.node_header_len() + layout.cluster_header_len() + layout.cluster_bitmap_len(b'a', b'c');
+/// assert_eq!(build.data[first_child_slot] as usize, root_len);
+/// ```
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub struct Layout {
+    pub index_width: IndexWidth,
+}
+
+impl Layout {
+    /// Version of the layout described here. This is incremented whenever the layout changes incompatibly. It's stored in the header of [`FastrieBuild::pack`](crate::FastrieBuild::pack), and loading a packed buffer with another version fails with [`FastrieError::UnsupportedVersion`](crate::FastrieError::UnsupportedVersion).
+    pub const VERSION: u32 = 7;
+
+    /// Length of the header byte at the start of built data.
+    pub const HEADER_LEN: usize = 1;
+
+    /// Bit of a node's children-present byte that's set if the node has a value. Tools that clear or set a node's value index must update it too.
+    pub const HAS_VALUE: u8 = 1 << 3;
+
+    pub const fn new(index_width: IndexWidth) -> Layout {
+        Layout { index_width }
+    }
+
+    /// Length of a node's value index and children-present byte.
+    pub const fn node_header_len(self) -> usize {
+        self.index_width.0 + 1
+    }
+
+    /// Width of the child indices of a node, given its children-present byte. Only meaningful for nodes with clusters or a single child.
+    pub const fn child_width(self, children_present: u8) -> IndexWidth {
+        IndexWidth::of_children_present(children_present)
+    }
+
+    /// Length of the char and child index following the header of a node with a single child and child width `child_width`.
+    pub const fn single_child_len(self, child_width: IndexWidth) -> usize {
+        1 + child_width.0
+    }
+
+    pub(crate) fn symbol_single_child_len<K: Symbol>(self, child_width: IndexWidth) -> usize {
+        K::WIDTH + child_width.0
+    }
+
+    /// Length of the path of `len` chars following the header of a node with a path.
+    pub const fn path_len(self, len: usize) -> usize {
+        1 + len
+    }
+
+    pub(crate) fn symbol_path_len<K: Symbol>(self, len: usize) -> usize {
+        1 + len * K::WIDTH
+    }
+
+    /// Length of a cluster's next cluster distance and min and max chars.
+    pub const fn cluster_header_len(self) -> usize {
+        1 + 2
+    }
+
+    pub(crate) fn symbol_cluster_header_len<K: Symbol>(self) -> usize {
+        1 + 2 * K::WIDTH
+    }
+
+    /// Length of the bitmap of a cluster covering `min..=max`.
+    pub fn cluster_bitmap_len(self, min: u8, max: u8) -> usize {
+        cluster_bitmap_len(min, max)
+    }
+
+    /// Total length of a cluster covering `min..=max` with `child_count` present children, of a node with child width `child_width`.
+    pub fn cluster_len(self, min: u8, max: u8, child_count: usize, child_width: IndexWidth) -> usize {
+        self.symbol_cluster_len(min, max, child_count, child_width)
+    }
+
+    pub(crate) fn symbol_cluster_len<K: Symbol>(self, min: K, max: K, child_count: usize, child_width: IndexWidth) -> usize {
+        self.symbol_cluster_header_len::<K>() + cluster_bitmap_len(min, max) + child_count * child_width.0
+    }
+}
@@ -1,20 +1,218 @@
+use std::cmp::Reverse;
+use std::collections::BTreeMap;
+#[cfg(feature = "track-keys")]
+use std::collections::BTreeSet;
 use std::collections::HashMap;
+use std::error::Error;
+use std::fmt;
+use std::hash::Hash;
+use std::io;
+use std::marker::PhantomData;
+use std::num::NonZeroUsize;
+use std::ops::Range;
 
-pub struct FastrieBuilderNode<V> {
+pub use chunked::{ByteSource, ChunkedFastrie, Chunks};
+pub use fixed::FixedFastrie;
+pub use fixed_len::{FixedLenBuild, FixedLenFastrie};
+pub use growable::GrowableFastrie;
+pub use layout::Layout;
+pub use owned::OwnedFastrie;
+pub use packed::{from_prebuilt_packed, PackedValues, PACKED_MAGIC};
+pub use prefix_map::PrefixMap;
+pub use stream::WriteError;
+pub use symbol::Symbol;
+
+use symbol::{push_symbol, read_symbol};
+use walk::Walker;
+
+#[cfg(feature = "arena")]
+mod arena;
+mod chunked;
+mod compact;
+mod concat;
+mod duplicates;
+mod fixed;
+mod fixed_len;
+mod growable;
+mod layout;
+mod owned;
+mod packed;
+mod prefix_map;
+mod records;
+mod segments;
+mod stream;
+mod symbol;
+mod text;
+mod walk;
+
+/// Children of a builder node by char. With the `arena` feature, the maps of every node under a root builder allocate from its arena.
+#[cfg(not(feature = "arena"))]
+type ChildMap<V, K> = HashMap<K, FastrieBuilderNode<V, K>>;
+#[cfg(feature = "arena")]
+type ChildMap<V, K> = hashbrown::HashMap<K, FastrieBuilderNode<V, K>, std::collections::hash_map::RandomState, arena::Arena>;
+
+pub struct FastrieBuilderNode<V, K = u8> {
     built: bool,
-    children: HashMap<u8, FastrieBuilderNode<V>>,
+    children: ChildMap<V, K>,
     index_width: IndexWidth,
+    // Only used on the root.
+    max_cluster_len: usize,
+    // Only used on the root.
+    merge_clusters: bool,
     value: Option<V>,
+    // Index of `value` in the built values. Only set while building.
+    value_idx: Option<StoredValueIdx>,
+    // Keys added through this node, relative to it.
+    #[cfg(feature = "track-keys")]
+    inserted_keys: BTreeSet<Vec<K>>,
+}
+
+type ClusterWeight<'w, K> = &'w dyn Fn(&[Option<K>]) -> u64;
+
+/// How children are grouped into clusters and ordered when building. Taken from the root builder.
+#[derive(Clone, Copy)]
+struct ClusterOptions<'w, K> {
+    max_cluster_len: usize,
+    merge_clusters: bool,
+    // Clusters are ordered by descending weight if set, and largest first otherwise.
+    cluster_weight: Option<ClusterWeight<'w, K>>,
 }
 
 const RESERVED_BYTE: u8 = 0xFF;
-const MAX_CLUSTER_GAP_LEN: i16 = 3;
+const MAX_CLUSTER_GAP_LEN: usize = 3;
+
+// Flags in a node's children-present byte. At most one of the flags for children is set.
+/// The node's children are in a chain of clusters following the node header.
+const NODE_HAS_CLUSTERS: u8 = 1 << 0;
+/// The node has exactly one child, stored compactly as its char and index following the node header instead of as a cluster.
+const NODE_HAS_SINGLE_CHILD: u8 = 1 << 1;
+/// The node is followed by a path of two or more chars through nodes without values that each have exactly one child, stored inline as its length and chars, immediately followed by the node at the end of the path.
+const NODE_HAS_PATH: u8 = 1 << 2;
+/// Bits of the children-present byte that say whether and how the node has children.
+const NODE_CHILDREN_MASK: u8 = NODE_HAS_CLUSTERS | NODE_HAS_SINGLE_CHILD | NODE_HAS_PATH;
+/// The node has a value, so its value index is non-zero. Most nodes don't, so walks only read the value index if this is set.
+const NODE_HAS_VALUE: u8 = Layout::HAS_VALUE;
+/// Bits of the children-present byte that hold one less than the width of the node's child indices, for nodes with a single child or clusters.
+const NODE_CHILD_WIDTH_SHIFT: u32 = 4;
+const NODE_CHILD_WIDTH_MASK: u8 = 0x7 << NODE_CHILD_WIDTH_SHIFT;
+/// Maximum amount of chars in a path, as its length is stored in one byte. Longer chains are split into consecutive paths.
+const MAX_PATH_LEN: usize = 255;
+/// Maximum length in bytes of a cluster, as the distance from a cluster to the next one of the same node, which immediately follows it, is stored in one byte. Clusters that would be longer are split.
+const MAX_CLUSTER_BYTES: usize = 255;
+
+/// High bits of the header byte at the start of built data. The low bits are the index width the data was built with.
+const DATA_HEADER_MAGIC: u8 = 0xF0;
+/// Position of the root node, which follows the header byte.
+const ROOT_POS: usize = Layout::HEADER_LEN;
+
+/// Length of the bitmap of present children in a cluster ranging over `min..=max`, with one bit per char. Singleton clusters have no bitmap, as their only char is always present.
+fn cluster_bitmap_len<K: Symbol>(min: K, max: K) -> usize {
+    if min == max {
+        0
+    } else {
+        (max.to_usize() - min.to_usize()) / 8 + 1
+    }
+}
+
+/// Pushes the distance to the next cluster, min and max chars, and bitmap of `cluster`, which is the last cluster of its node if `is_last`. Its child indices, which are `child_width` bytes, must be pushed after.
+fn push_cluster_header<K: Symbol>(layout: Layout, child_width: IndexWidth, data: &mut Vec<u8>, cluster: &[Option<K>], is_last: bool) {
+    let min = cluster.first().unwrap().unwrap();
+    let max = cluster.last().unwrap().unwrap();
+    // Clusters of a node are contiguous, so the next one starts right after this one.
+    data.push(if is_last { 0 } else { layout.symbol_cluster_len(min, max, cluster.iter().flatten().count(), child_width) as u8 });
+    push_symbol(data, min);
+    push_symbol(data, max);
+    // Gaps are represented by unset bits in the bitmap rather than zero index slots.
+    let bitmap_pos = data.len();
+    data.resize(bitmap_pos + cluster_bitmap_len(min, max), 0);
+    for (bit, c) in cluster.iter().enumerate() {
+        if c.is_some() && min != max {
+            data[bitmap_pos + bit / 8] |= 1 << (bit % 8);
+        };
+    };
+}
 
-/// How many bytes to store and represent indices in the built data. Must be between 1 and 8 inclusive. Indices will be encoded in little endian format.
+/// Merges each cluster into the preceding one, in char order, when the merged cluster would be smaller than the two separately, cover at most `max_cluster_len` chars, and be at most `MAX_CLUSTER_BYTES` long.
+fn merge_adjacent_clusters<K: Symbol>(layout: Layout, child_width: IndexWidth, max_cluster_len: usize, clusters: Vec<Vec<Option<K>>>) -> Vec<Vec<Option<K>>> {
+    let bounds = |cluster: &[Option<K>]| (cluster.first().unwrap().unwrap(), cluster.last().unwrap().unwrap(), cluster.iter().flatten().count());
+    let mut merged: Vec<Vec<Option<K>>> = Vec::with_capacity(clusters.len());
+    for cluster in clusters {
+        if let Some(last) = merged.last_mut() {
+            let (last_min, last_max, last_count) = bounds(last);
+            let (min, max, count) = bounds(&cluster);
+            let gap_len = min.to_usize() - last_max.to_usize() - 1;
+            let separate_len = layout.symbol_cluster_len(last_min, last_max, last_count, child_width) + layout.symbol_cluster_len(min, max, count, child_width);
+            let merged_len = layout.symbol_cluster_len(last_min, max, last_count + count, child_width);
+            if last.len() + gap_len + cluster.len() <= max_cluster_len && merged_len < separate_len && merged_len <= MAX_CLUSTER_BYTES {
+                last.resize(last.len() + gap_len, None);
+                last.extend(cluster);
+                continue;
+            };
+        };
+        merged.push(cluster);
+    };
+    merged
+}
+
+/// A value index as stored in a node header, which is one plus the zero-based index into the values, as zero means the node has no value. All conversions between the two go through this type.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+struct StoredValueIdx(NonZeroUsize);
+
+impl StoredValueIdx {
+    fn new(value_idx: usize) -> StoredValueIdx {
+        StoredValueIdx(NonZeroUsize::new(value_idx + 1).unwrap())
+    }
+
+    /// The zero-based index into the values.
+    fn value_idx(self) -> usize {
+        self.0.get() - 1
+    }
+}
+
+/// How many bytes to store and represent value indices in the built data. Must be between 1 and 8 inclusive. Indices will be encoded in little endian format. Child indices have their own width per node, which is at most 8 bytes; see [`Layout`].
 #[derive(Clone, Copy, PartialEq, Eq, Debug)]
 pub struct IndexWidth(pub usize);
 
 impl IndexWidth {
+  /// The byte at the start of data built with this index width.
+  const fn header_byte(self) -> u8 {
+      DATA_HEADER_MAGIC | self.0 as u8
+  }
+
+  /// Checks that `data` starts with the header byte of data built with this index width.
+  const fn check_header(self, data: &[u8]) -> Result<(), FastrieError> {
+      if data.is_empty() {
+          return Err(FastrieError::TruncatedData { pos: 0 });
+      };
+      if data[0] & DATA_HEADER_MAGIC != DATA_HEADER_MAGIC {
+          return Err(FastrieError::BadMagic);
+      };
+      let built = (data[0] & !DATA_HEADER_MAGIC) as usize;
+      if built != self.0 {
+          return Err(FastrieError::IndexWidthMismatch { given: self, built: IndexWidth(built) });
+      };
+      Ok(())
+  }
+
+  fn can_represent(self, idx: usize) -> bool {
+      self.0 >= std::mem::size_of::<usize>() || idx >> (8 * self.0) == 0
+  }
+
+  /// The smallest index width that can represent `idx`.
+  fn smallest_for(idx: usize) -> IndexWidth {
+      (1..=8).map(IndexWidth).find(|width| width.can_represent(idx)).unwrap()
+  }
+
+  /// The bits of a children-present byte declaring this as the width of the node's child indices.
+  const fn children_present_bits(self) -> u8 {
+      ((self.0 - 1) as u8) << NODE_CHILD_WIDTH_SHIFT
+  }
+
+  /// The width of child indices declared by a children-present byte.
+  const fn of_children_present(children_present: u8) -> IndexWidth {
+      IndexWidth(((children_present & NODE_CHILD_WIDTH_MASK) >> NODE_CHILD_WIDTH_SHIFT) as usize + 1)
+  }
+
   fn reserve_idx(self, vec: &mut Vec<u8>) -> usize {
       let pos = vec.len();
       for _ in 0..self.0 {
@@ -23,18 +221,22 @@ impl IndexWidth {
       pos
   }
 
-  fn write_idx(self, vec: &mut Vec<u8>, pos: usize, mut idx: usize) -> () {
+  fn write_idx(self, vec: &mut [u8], pos: usize, mut idx: usize) {
     for i in 0..self.0 {
       vec[pos + i] = idx as u8;
       idx >>= 8;
     };
   }
 
-  fn push_idx(self, vec: &mut Vec<u8>, idx: usize) -> () {
+  fn push_idx(self, vec: &mut Vec<u8>, idx: usize) {
       let pos = self.reserve_idx(vec);
       self.write_idx(vec, pos, idx);
   }
 
+  fn push_value_idx(self, vec: &mut Vec<u8>, value_idx: Option<StoredValueIdx>) {
+      self.push_idx(vec, value_idx.map_or(0, |idx| idx.0.get()));
+  }
+
   fn read_idx(self, data: &[u8], pos: usize) -> usize {
       let mut idx = 0usize;
       for i in 0..self.0 {
@@ -42,256 +244,3248 @@ impl IndexWidth {
       }
       idx
   }
+
+  /// Like `read_idx`, but returns `None` instead of panicking if the index would extend past the end of `data`.
+  fn read_idx_checked(self, data: &[u8], pos: usize) -> Option<usize> {
+      if pos.checked_add(self.0)? > data.len() {
+          return None;
+      };
+      Some(self.read_idx(data, pos))
+  }
 }
 
-pub struct FastrieBuild<V> {
-    pub data: Vec<u8>,
-    pub index_width: IndexWidth,
-    pub values: Vec<V>,
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum AddError {
+    /// The key is longer than the maximum key length allowed.
+    TooLong,
 }
 
-impl<V> FastrieBuilderNode<V> {
-    pub fn new(index_width: IndexWidth) -> FastrieBuilderNode<V> {
-        FastrieBuilderNode {
-          built: false,
-          children: HashMap::new(),
-          index_width,
-          value: None,
+impl fmt::Display for AddError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            AddError::TooLong => write!(f, "key is longer than the maximum key length"),
         }
     }
+}
 
-    pub fn add(&mut self, pattern: &[u8], value: V) -> () {
-        let mut current: &mut FastrieBuilderNode<V> = self;
-        for c in pattern {
-            if !current.children.contains_key(c) {
-                current.children.insert(*c, FastrieBuilderNode::new(current.index_width));
-            };
-            current = current.children.get_mut(c).unwrap();
+impl Error for AddError {}
+
+/// The value of a key in a builder, which may not exist yet. Created by [`FastrieBuilderNode::entry`].
+pub struct Entry<'b, 'p, V, K = u8> {
+    root: &'b mut FastrieBuilderNode<V, K>,
+    pattern: &'p [K],
+}
+
+impl<'b, V, K: Symbol> Entry<'b, '_, V, K> {
+    /// Calls `f` with the value if the key has one.
+    pub fn and_modify(self, f: impl FnOnce(&mut V)) -> Self {
+        if let Some(value) = self.root.existing_node_mut(self.pattern).and_then(|node| node.value.as_mut()) {
+            f(value);
         };
-        current.value = Some(value);
+        self
     }
 
-    fn _build(&mut self, data: &mut Vec<u8>, values: &mut Vec<V>) -> () {
-        assert!(!self.built);
-        self.built = true;
+    /// Returns the value, inserting `default` if the key doesn't have one.
+    pub fn or_insert(self, default: V) -> &'b mut V {
+        self.or_insert_with(|| default)
+    }
 
-        let value_idx: usize = if self.value.is_some() {
-            // Index 0 is reserved.
-            let idx = values.len() + 1;
-            values.push(self.value.take().unwrap());
-            idx
-        } else { 0 };
-        self.index_width.push_idx(data, value_idx);
+    /// Returns the value, inserting the result of `default` if the key doesn't have one.
+    pub fn or_insert_with(self, default: impl FnOnce() -> V) -> &'b mut V {
+        self.root.node_mut(self.pattern).value.get_or_insert_with(default)
+    }
 
-        let mut child_chars = self.children.keys().map(|k| *k).collect::<Vec<u8>>();
-        child_chars.sort();
+    /// Returns the value, inserting `V::default()` if the key doesn't have one.
+    pub fn or_default(self) -> &'b mut V where V: Default {
+        self.or_insert_with(V::default)
+    }
+}
 
-        let mut child_char_clusters: Vec<Vec<Option<u8>>> = vec![];
-        // Use i16 for:
-        // - safe initial value that's guaranteed to cause new cluster creation;
-        // - safe adding of `last_char + MAX_CLUSTER_GAP_LEN` without overflow; and
-        // - safe calculation of `p - 1`.
-        let mut last_char: i16 = std::i16::MIN;
-        for c in child_chars {
-            let p = c as i16;
-            // Allow a maximum gap length of MAX_CLUSTER_GAP_LEN between any two children in a cluster.
-            // Create a new cluster if it's the first char, or previous char in the current cluster is more than 3 character positions away.
-            if last_char + MAX_CLUSTER_GAP_LEN < p {
-                child_char_clusters.push(Vec::new());
-            } else {
-                // Fill any gaps with None values.
-                for _ in last_char..p - 1 {
-                    child_char_clusters.last_mut().unwrap().push(None);
-                };
-            };
-            child_char_clusters.last_mut().unwrap().push(Some(c));
-            last_char = p;
-        };
-        // Check largest first for faster performance on average.
-        child_char_clusters.sort_by(|a, b| b.len().cmp(&a.len()));
-
-        let mut replace_with_child_indices: HashMap<u8, usize> = HashMap::new();
-
-        data.push(!self.children.is_empty() as u8);
-        let mut last_cluster_next_cluster_dist_pos: Option<usize> = None;
-        for cluster in &child_char_clusters {
-            let cluster_pos = data.len();
-            if let Some(out_pos) = last_cluster_next_cluster_dist_pos {
-                self.index_width.write_idx(data, out_pos, cluster_pos);
-            };
-            last_cluster_next_cluster_dist_pos = Some(self.index_width.reserve_idx(data));
-            let min = cluster.first().unwrap().unwrap();
-            let max = cluster.last().unwrap().unwrap();
-            data.push(min);
-            data.push(max);
-            for c in cluster {
-                match c {
-                    Some(c) => {
-                        debug_assert!(!replace_with_child_indices.contains_key(c));
-                        replace_with_child_indices.insert(*c, self.index_width.reserve_idx(data));
-                    }
-                    None => { self.index_width.push_idx(data, 0); }
-                };
-            };
-        };
-        if let Some(out_pos) = last_cluster_next_cluster_dist_pos {
-            self.index_width.write_idx(data, out_pos, 0);
-        };
+/// Positions in built data of the nodes of keys, as returned by [`FastrieBuilderNode::prebuild_with_offsets`].
+pub type KeyOffsets<K = u8> = HashMap<Vec<K>, usize>;
 
-        for cluster in &child_char_clusters {
-            for c in cluster {
-                if let Some(c) = c {
-                    self.index_width.write_idx(data, *replace_with_child_indices.get(c).unwrap(), data.len());
-                    let child_node = self.children.get_mut(c).unwrap();
-                    child_node._build(data, values);
-                };
-            };
-        };
+/// Returned by [`FastrieBuilderNode::prebuild`] and the other build methods if the builder has already been built, as building consumes its values.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub struct AlreadyBuilt;
+
+impl fmt::Display for AlreadyBuilt {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "builder has already been built")
     }
+}
 
-    pub fn prebuild(&mut self) -> FastrieBuild<V> {
-        let mut data: Vec<u8> = Vec::new();
-        let mut values: Vec<V> = Vec::new();
-        self._build(&mut data, &mut values);
-        FastrieBuild {
-          data,
-          index_width: self.index_width,
-          values,
+impl Error for AlreadyBuilt {}
+
+/// Errors from loading or verifying built data.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum FastrieError {
+    /// The data ended in the middle of the node or cluster at `pos`.
+    TruncatedData { pos: usize },
+    /// The children-present byte of the node at `node_pos` doesn't match whether a cluster chain follows it.
+    PresenceMismatch { node_pos: usize },
+    /// The data at `pos` isn't laid out like a build, e.g. a node is at `pos` but a node was expected elsewhere.
+    MalformedData { pos: usize },
+    /// The buffer doesn't start with the expected magic bytes.
+    BadMagic,
+    /// The index width isn't between 1 and 8 inclusive.
+    InvalidIndexWidth(usize),
+    /// The index width given when loading the data isn't the one the data was built with.
+    IndexWidthMismatch { given: IndexWidth, built: IndexWidth },
+    /// The data refers to the value at `idx`, but there are only `len` values, e.g. because the values don't belong to the data.
+    ValueIndexOutOfRange { idx: usize, len: usize },
+    /// The value at `value_idx` isn't referred to by any node, so it's unreachable by queries.
+    OrphanedValue { value_idx: usize },
+    /// The content hash in the header of a packed buffer doesn't match its data and values, so they've been corrupted.
+    ContentHashMismatch,
+    /// The buffer was written with a version of the layout other than [`Layout::VERSION`].
+    UnsupportedVersion(u32),
+    /// The builder has already been built, as by [`AlreadyBuilt`].
+    AlreadyBuilt,
+}
+
+impl fmt::Display for FastrieError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            FastrieError::TruncatedData { pos } => write!(f, "data is truncated in the node or cluster at {}", pos),
+            FastrieError::PresenceMismatch { node_pos } => write!(f, "children-present byte of the node at {} doesn't match its children", node_pos),
+            FastrieError::MalformedData { pos } => write!(f, "data is malformed at {}", pos),
+            FastrieError::BadMagic => write!(f, "buffer doesn't start with the expected magic bytes"),
+            FastrieError::InvalidIndexWidth(width) => write!(f, "index width {} isn't between 1 and 8", width),
+            FastrieError::IndexWidthMismatch { given, built } => write!(f, "index width {} was given, but the data was built with {}", given.0, built.0),
+            FastrieError::ValueIndexOutOfRange { idx, len } => write!(f, "value index {} is out of range of the {} values", idx, len),
+            FastrieError::OrphanedValue { value_idx } => write!(f, "value at index {} isn't referred to by any node", value_idx),
+            FastrieError::ContentHashMismatch => write!(f, "content hash doesn't match the data and values"),
+            FastrieError::UnsupportedVersion(version) => write!(f, "layout version {} isn't supported", version),
+            FastrieError::AlreadyBuilt => write!(f, "builder has already been built"),
         }
     }
 }
 
-pub struct Fastrie<'v, 'd, V> {
-    data: &'d [u8],
-    index_width: IndexWidth,
-    // If None, keys are used as a set.
-    values: Option<&'v [V]>,
+impl Error for FastrieError {}
+
+impl From<AlreadyBuilt> for FastrieError {
+    fn from(_: AlreadyBuilt) -> FastrieError {
+        FastrieError::AlreadyBuilt
+    }
 }
 
-pub struct FastrieMatch<'v, V> {
-    /// Inclusive.
-    pub end: usize,
-    pub value: &'v V,
+pub struct FastrieBuild<V> {
+    pub data: Vec<u8>,
+    pub index_width: IndexWidth,
+    pub values: Vec<V>,
 }
 
+impl<V> FastrieBuild<V> {
+    /// Returns the amount of values, which is the amount of keys unless built by [`FastrieBuilderNode::prebuild_dedup`], as each key has its own value.
+    pub fn len(&self) -> usize {
+        self.values.len()
+    }
 
-/// # Example
-///
-/// ```
-/// use fastrie::*;
-///
-/// let mut builder = FastrieBuilderNode::new(IndexWidth(2));
-/// builder.add(b"hell", 1);
-/// builder.add(b"hello", 2);
-/// builder.add(b"world", 4);
-/// let build = builder.prebuild();
-///
-/// // `build.data` can be written as bytes to a file, or embedded directly into code as a literal byte array/slice.
-///
-/// let trie = from_prebuilt_without_values(build.index_width, &build.data);
-/// assert!(trie.contains_key(b"hell"));
-/// assert!(trie.contains_key(b"hello"));
-/// assert!(trie.contains_key(b"world"));
-/// assert!(!trie.contains_key(b"worl"));
-/// assert!(!trie.contains_key(b"worlds"));
-/// ```
-pub const fn from_prebuilt_without_values<'d>(index_width: IndexWidth, data: &'d [u8]) -> Fastrie<'_, 'd, ()> {
-  Fastrie {
-    data,
-    index_width,
-    values: None,
-  }
+    pub fn is_empty(&self) -> bool {
+        self.values.is_empty()
+    }
+
+    /// Drops the values, keeping the data as is, for consumers that only check membership. Nodes keep their value indices, which mark which nodes are keys, so the data works with [`from_prebuilt_without_values`]. The values are replaced with as many `()`, which take no memory, so value indices remain valid and checks like [`FastrieBuild::verify_values`] still pass.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use fastrie::*;
+    ///
+    /// let mut builder = FastrieBuilderNode::new(IndexWidth(2));
+    /// builder.add(b"hell", String::from("place"));
+    /// builder.add(b"hello", String::from("greeting"));
+    /// let build = builder.prebuild().unwrap().into_set();
+    /// assert_eq!(build.len(), 2);
+    ///
+    /// let trie = from_prebuilt_without_values(build.index_width, &build.data);
+    /// assert!(trie.contains_key(b"hell"));
+    /// assert!(trie.contains_key(b"hello"));
+    /// assert!(!trie.contains_key(b"hel"));
+    /// ```
+    pub fn into_set(self) -> FastrieBuild<()> {
+        FastrieBuild {
+            values: vec![(); self.values.len()],
+            data: self.data,
+            index_width: self.index_width,
+        }
+    }
+
+    /// Returns whether this build and `other` return the same match length and value from [`Fastrie::longest_matching_prefix`] for every one of `inputs`, e.g. to check that changing build options doesn't change query results over a corpus. Value indices aren't compared, so builds with differently ordered or deduplicated values can agree. Returns an error instead of panicking if either isn't a valid build of byte keys, e.g. if its data was changed or its values truncated; see [`Fastrie::try_longest_matching_prefix`].
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use fastrie::*;
+    ///
+    /// let build_with = |merge_clusters| {
+    ///     let mut builder = FastrieBuilderNode::new(IndexWidth(2));
+    ///     for (i, key) in ["a", "e", "i", "io"].iter().enumerate() {
+    ///         builder.add(key.as_bytes(), i);
+    ///     };
+    ///     builder.set_merge_clusters(merge_clusters);
+    ///     builder.prebuild().unwrap()
+    /// };
+    /// let separate = build_with(false);
+    /// let merged = build_with(true);
+    /// assert_ne!(separate.data, merged.data);
+    /// let inputs: &[&[u8]] = &[b"a", b"b", b"ion", b""];
+    /// assert_eq!(merged.queries_agree_with(&separate, inputs), Ok(true));
+    ///
+    /// let mut builder = FastrieBuilderNode::new(IndexWidth(2));
+    /// builder.add(b"i", 2);
+    /// assert_eq!(builder.prebuild().unwrap().queries_agree_with(&separate, inputs), Ok(false));
+    ///
+    /// // Values from a build with fewer keys.
+    /// let stale = FastrieBuild { index_width: separate.index_width, values: separate.values[..1].to_vec(), data: separate.data.clone() };
+    /// assert!(merged.queries_agree_with(&stale, inputs).is_err());
+    /// ```
+    pub fn queries_agree_with(&self, other: &FastrieBuild<V>, inputs: &[&[u8]]) -> Result<bool, FastrieError> where V: PartialEq {
+        let trie = Fastrie::from_prebuilt(self.index_width, &self.values, &self.data)?;
+        let other = Fastrie::from_prebuilt(other.index_width, &other.values, &other.data)?;
+        for input in inputs {
+            let mat = trie.try_longest_matching_prefix(input)?.map(|m| (m.len, m.value));
+            if mat != other.try_longest_matching_prefix(input)?.map(|m| (m.len, m.value)) {
+                return Ok(false);
+            };
+        };
+        Ok(true)
+    }
+}
+
+impl<V, K> Drop for FastrieBuilderNode<V, K> {
+    fn drop(&mut self) {
+        // Take the descendants out before they're dropped, so dropping a long key's chain of nodes doesn't recurse once per char.
+        let mut stack = self.children.drain().map(|(_, child)| child).collect::<Vec<_>>();
+        while let Some(mut node) = stack.pop() {
+            stack.extend(node.children.drain().map(|(_, child)| child));
+        };
+    }
 }
 
-impl<V> Fastrie<'_, '_, V> {
+impl<V> FastrieBuilderNode<V> {
+    pub fn new(index_width: IndexWidth) -> FastrieBuilderNode<V> {
+        FastrieBuilderNode::new_with_symbols(index_width)
+    }
+
+    /// Builds a trie of every entry of `map`, without a builder.
+    ///
     /// # Example
     ///
     /// ```
+    /// use std::collections::HashMap;
     /// use fastrie::*;
     ///
-    /// let mut builder = FastrieBuilderNode::new(IndexWidth(3));
-    /// builder.add(b"hell", 1);
-    /// builder.add(b"hello", 2);
-    /// builder.add(b"world", 4);
-    /// let build = builder.prebuild();
+    /// let mut map = HashMap::new();
+    /// map.insert("hell", 1);
+    /// map.insert("hello", 2);
+    /// let build = FastrieBuilderNode::from_map(map, IndexWidth(2));
     ///
-    /// // `build.data` can be written as bytes to a file, or embedded directly into code as a literal byte array/slice.
+    /// let trie = Fastrie::from_prebuilt(build.index_width, &build.values, &build.data).unwrap();
+    /// assert_eq!(trie.longest_matching_prefix(b"hello!").unwrap().value, &2);
+    /// ```
+    pub fn from_map<K: AsRef<[u8]>>(map: HashMap<K, V>, index_width: IndexWidth) -> FastrieBuild<V> {
+        Self::from_entries(map, index_width)
+    }
+
+    /// Like [`FastrieBuilderNode::from_map`], but for a `BTreeMap`.
+    pub fn from_btree_map<K: AsRef<[u8]>>(map: BTreeMap<K, V>, index_width: IndexWidth) -> FastrieBuild<V> {
+        Self::from_entries(map, index_width)
+    }
+
+    /// Builds a trie of every `(key, value)` of `entries`, without a builder. Keys can be borrowed, e.g. `&[u8]` slices of a larger buffer, and each is only used while it's added, so a large dataset can be built from a lazy iterator without collecting copies of its keys first. Peak memory is then the source of the keys, the builder's nodes, and the built data, without a copy of every key as when collecting keys into a `Vec<Vec<u8>>` to call [`FastrieBuilderNode::add`] with. Note that the builder's nodes, one per distinct key prefix, usually take far more memory than the keys themselves. A later key replaces the value of an earlier equal one, as with `add`.
+    ///
+    /// # Example
     ///
-    /// let trie = Fastrie::from_prebuilt(build.index_width, &build.values, &build.data);
-    /// assert!(trie.contains_key(b"hello"));
-    /// let query = b"hello world!";
-    /// let mat = trie.longest_matching_prefix(query).unwrap();
-    /// assert_eq!(mat.end, 4);
-    /// assert_eq!(&query[..=mat.end], b"hello");
-    /// assert_eq!(mat.value, &2);
-    /// let query = b"hell's kitchen";
-    /// let mat = trie.longest_matching_prefix(query).unwrap();
-    /// assert_eq!(mat.end, 3);
-    /// assert_eq!(&query[..=mat.end], b"hell");
-    /// assert_eq!(mat.value, &1);
     /// ```
-    pub const fn from_prebuilt<'v, 'd>(index_width: IndexWidth, values: &'v [V], data: &'d [u8]) -> Fastrie<'v, 'd, V> {
-        Fastrie {
-          data,
+    /// use fastrie::*;
+    ///
+    /// let source = "hell\nhello\nworld";
+    /// let build = FastrieBuilderNode::from_entries(source.lines().enumerate().map(|(i, line)| (line.as_bytes(), i)), IndexWidth(2));
+    ///
+    /// let trie = Fastrie::from_prebuilt(build.index_width, &build.values, &build.data).unwrap();
+    /// assert_eq!(trie.longest_matching_prefix(b"hello!").unwrap().value, &1);
+    /// assert_eq!(trie.get(b"world"), Some(&2));
+    /// ```
+    pub fn from_entries<K: AsRef<[u8]>>(entries: impl IntoIterator<Item = (K, V)>, index_width: IndexWidth) -> FastrieBuild<V> {
+        let mut builder = FastrieBuilderNode::new(index_width);
+        builder.add_many(entries);
+        builder.prebuild().unwrap()
+    }
+}
+
+impl<V, K: Symbol> FastrieBuilderNode<V, K> {
+    /// Like [`FastrieBuilderNode::new`], but for keys of `K` symbols instead of bytes. See [`Symbol`].
+    pub fn new_with_symbols(index_width: IndexWidth) -> FastrieBuilderNode<V, K> {
+        FastrieBuilderNode::with_children(index_width, ChildMap::default())
+    }
+
+    fn with_children(index_width: IndexWidth, children: ChildMap<V, K>) -> FastrieBuilderNode<V, K> {
+        FastrieBuilderNode {
+          built: false,
+          children,
           index_width,
-          values: Some(values),
+          max_cluster_len: usize::MAX,
+          merge_clusters: false,
+          value: None,
+          value_idx: None,
+          #[cfg(feature = "track-keys")]
+          inserted_keys: BTreeSet::new(),
         }
     }
 
-    pub fn memory_size(&self) -> usize {
-        self.data.len()
+    /// Returns an empty node to be added as a child of this one, sharing its arena with the `arena` feature.
+    fn new_child(&self) -> FastrieBuilderNode<V, K> {
+        #[cfg(not(feature = "arena"))]
+        let children = ChildMap::default();
+        #[cfg(feature = "arena")]
+        let children = ChildMap::with_hasher_in(Default::default(), self.children.allocator().clone());
+        FastrieBuilderNode::with_children(self.index_width, children)
     }
 
-    fn _longest_matching_prefix(&self, text: &[u8]) -> Option<(usize, usize)> {
-      let mut node_pos: usize = 0;
-      let mut match_opt: Option<(usize, usize)> = None;
-      'outer: for (i, &c) in text.iter().enumerate() {
-          let idx_bytes = self.index_width.0;
-          if self.data[node_pos + idx_bytes] == 0 {
-              // This node has no children.
-              break;
-          };
+    /// Returns the node at `pattern`, creating it and any missing nodes along the way.
+    fn node_mut(&mut self, pattern: &[K]) -> &mut FastrieBuilderNode<V, K> {
+        #[cfg(feature = "track-keys")]
+        self.inserted_keys.insert(pattern.to_vec());
+        let mut current: &mut FastrieBuilderNode<V, K> = self;
+        for c in pattern {
+            if !current.children.contains_key(c) {
+                current.children.insert(*c, current.new_child());
+            };
+            current = current.children.get_mut(c).unwrap();
+        };
+        current
+    }
 
-          let mut cluster_pos: usize = node_pos + idx_bytes + 1;
-          loop {
-              let next_cluster_pos = self.index_width.read_idx(&self.data, cluster_pos);
-              let cluster_min: u8 = self.data[cluster_pos + idx_bytes];
-              let cluster_max: u8 = self.data[cluster_pos + idx_bytes + 1];
-              if c >= cluster_min && c <= cluster_max {
-                  // Character is in this cluster, but it might point to a gap.
-                  node_pos = self.index_width.read_idx(&self.data, cluster_pos + idx_bytes + 2 + ((c - cluster_min) as usize) * idx_bytes);
-                  if node_pos == 0 {
-                      // Character is not a child, as child node index is zero which means it's a gap.
-                      break 'outer;
-                  } else {
-                      break;
-                  };
-              };
-              if next_cluster_pos == 0 {
-                  // Next cluster index is zero, which means this is last cluster.
-                  break 'outer;
-              };
-              cluster_pos = next_cluster_pos;
-          };
+    /// Returns the node at `pattern` if it exists, without creating any nodes.
+    fn existing_node_mut(&mut self, pattern: &[K]) -> Option<&mut FastrieBuilderNode<V, K>> {
+        let mut current: &mut FastrieBuilderNode<V, K> = self;
+        for c in pattern {
+            current = current.children.get_mut(c)?;
+        };
+        Some(current)
+    }
 
-          // Get value of child node.
-          let node_value_idx: usize = self.index_width.read_idx(&self.data, node_pos);
-          if node_value_idx != 0 {
-              match_opt = Some((i, node_value_idx - 1));
-          };
-      };
+    pub fn add(&mut self, pattern: &[K], value: V) {
+        self.node_mut(pattern).value = Some(value);
+    }
+
+    /// Adds every `(pattern, value)` of `entries`, like calling [`FastrieBuilderNode::add`] for each. This node's children are first reserved for the amount of entries, up to one child per distinct symbol (e.g. 256 for bytes, 65536 for `u16`), so a wide root doesn't rehash as its children are added.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use fastrie::*;
+    ///
+    /// let mut builder = FastrieBuilderNode::new(IndexWidth(2));
+    /// builder.add_many((b'a'..=b'z').map(|c| (vec![c], c)));
+    /// let build = builder.prebuild().unwrap();
+    ///
+    /// let trie = Fastrie::from_prebuilt(build.index_width, &build.values, &build.data).unwrap();
+    /// assert_eq!(trie.keys().count(), 26);
+    /// assert_eq!(trie.get(b"q"), Some(&b'q'));
+    /// ```
+    pub fn add_many<P: AsRef<[K]>>(&mut self, entries: impl IntoIterator<Item = (P, V)>) {
+        let entries = entries.into_iter();
+        // A node has at most one child per distinct symbol, so reserving more would be wasted however many entries there are.
+        let max_children = 1usize.checked_shl(8 * K::WIDTH as u32).unwrap_or(usize::MAX);
+        self.reserve_children(entries.size_hint().0.min(max_children));
+        for (pattern, value) in entries {
+            self.add(pattern.as_ref(), value);
+        };
+    }
 
-      match_opt
+    /// Reserves room for at least `additional` more children of this node, so adding them doesn't rehash its map of children. This is only worth it for nodes known to have many children, such as a root over every letter.
+    pub fn reserve_children(&mut self, additional: usize) {
+        self.children.reserve(additional);
     }
 
-    pub fn contains_key(&self, key: &[u8]) -> bool {
-      self._longest_matching_prefix(key).filter(|(i, _)| *i == key.len() - 1).is_some()
+    /// Returns the entry for the key `pattern`, to insert or modify its value in place like [`std::collections::HashMap::entry`]. Nodes are only created once a value is inserted, so modifying a missing key doesn't change the builder.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use fastrie::*;
+    ///
+    /// let mut builder = FastrieBuilderNode::new(IndexWidth(2));
+    /// for word in "the cat and the hat and the bat".split(' ') {
+    ///     builder.entry(word.as_bytes()).and_modify(|count| *count += 1).or_insert(1);
+    /// };
+    /// let build = builder.prebuild().unwrap();
+    ///
+    /// let trie = Fastrie::from_prebuilt(build.index_width, &build.values, &build.data).unwrap();
+    /// assert_eq!(trie.get(b"the"), Some(&3));
+    /// assert_eq!(trie.get(b"and"), Some(&2));
+    /// assert_eq!(trie.get(b"cat"), Some(&1));
+    /// ```
+    pub fn entry<'p>(&mut self, pattern: &'p [K]) -> Entry<'_, 'p, V, K> {
+        Entry { root: self, pattern }
     }
 
-    pub fn longest_matching_prefix(&self, text: &[u8]) -> Option<FastrieMatch<V>> {
-      self._longest_matching_prefix(text).map(|(end, value_idx)| FastrieMatch {
-          end,
-          value: &self.values.unwrap()[value_idx],
-      })
+    /// Like [`FastrieBuilderNode::add`], but rejects keys longer than `max_key_len` without modifying the builder. Useful to guard against pathologically long keys from untrusted input.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use fastrie::*;
+    ///
+    /// let mut builder = FastrieBuilderNode::new(IndexWidth(2));
+    /// assert_eq!(builder.add_checked(b"hello", 1, 5), Ok(()));
+    /// assert_eq!(builder.add_checked(b"hello world", 2, 5), Err(AddError::TooLong));
+    /// let build = builder.prebuild().unwrap();
+    ///
+    /// let trie = Fastrie::from_prebuilt(build.index_width, &build.values, &build.data).unwrap();
+    /// assert_eq!(trie.keys().collect::<Vec<Vec<u8>>>(), vec![b"hello".to_vec()]);
+    /// assert_eq!(build.values, vec![1]);
+    /// ```
+    pub fn add_checked(&mut self, pattern: &[K], value: V, max_key_len: usize) -> Result<(), AddError> {
+        if pattern.len() > max_key_len {
+            return Err(AddError::TooLong);
+        };
+        self.add(pattern, value);
+        Ok(())
+    }
+
+    /// Removes the subtree at `prefix` from this builder and returns it as a new root builder, where keys are relative to `prefix`. Nodes along `prefix` that are left without a value or children are pruned. Returns `None` if no key starts with `prefix`.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use fastrie::*;
+    ///
+    /// let mut builder = FastrieBuilderNode::new(IndexWidth(2));
+    /// builder.add(b"hell", 1);
+    /// builder.add(b"hello", 2);
+    /// builder.add(b"help", 3);
+    /// builder.add(b"world", 4);
+    ///
+    /// let mut split = builder.split_off(b"hel").unwrap();
+    /// assert!(builder.split_off(b"hel").is_none());
+    ///
+    /// let split_build = split.prebuild().unwrap();
+    /// let split_trie = Fastrie::from_prebuilt(split_build.index_width, &split_build.values, &split_build.data).unwrap();
+    /// assert_eq!(split_trie.longest_matching_prefix(b"lo!").unwrap().value, &2);
+    /// assert!(split_trie.contains_key(b"l"));
+    /// assert!(split_trie.contains_key(b"p"));
+    /// assert!(!split_trie.contains_key(b"world"));
+    ///
+    /// let build = builder.prebuild().unwrap();
+    /// let trie = Fastrie::from_prebuilt(build.index_width, &build.values, &build.data).unwrap();
+    /// assert_eq!(trie.keys().collect::<Vec<Vec<u8>>>(), vec![b"world".to_vec()]);
+    /// ```
+    pub fn split_off(&mut self, prefix: &[K]) -> Option<FastrieBuilderNode<V, K>> {
+        #[allow(unused_mut)]
+        let mut split = self._split_off(prefix);
+        #[cfg(feature = "track-keys")]
+        if let Some(split) = &mut split {
+            let moved = self.inserted_keys.iter().filter(|key| key.starts_with(prefix)).cloned().collect::<Vec<Vec<K>>>();
+            for key in moved {
+                self.inserted_keys.remove(&key);
+                split.inserted_keys.insert(key[prefix.len()..].to_vec());
+            };
+        };
+        split
+    }
+
+    /// Replaces the subtree at `prefix` with `sub`, whose keys are relative to `prefix`, creating the nodes along `prefix` if they don't exist. Unlike adding each key of `sub`, every key previously starting with `prefix` is removed, including `prefix` itself. Settings of `sub` like [`FastrieBuilderNode::set_max_cluster_len`] are ignored, as only those of the root are used.
+    ///
+    /// Panics if `sub` has already been built.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use fastrie::*;
+    ///
+    /// let mut builder = FastrieBuilderNode::new(IndexWidth(2));
+    /// builder.add(b"hell", 1);
+    /// builder.add(b"hello", 2);
+    /// builder.add(b"world", 4);
+    ///
+    /// let mut sub = FastrieBuilderNode::new(IndexWidth(2));
+    /// sub.add(b"p", 5);
+    /// sub.add(b"met", 6);
+    /// builder.replace_subtree(b"hel", sub);
+    /// let build = builder.prebuild().unwrap();
+    ///
+    /// let trie = Fastrie::from_prebuilt(build.index_width, &build.values, &build.data).unwrap();
+    /// assert_eq!(trie.get(b"hello"), None);
+    /// assert_eq!(trie.get(b"hell"), None);
+    /// assert_eq!(trie.get(b"help"), Some(&5));
+    /// assert_eq!(trie.get(b"helmet"), Some(&6));
+    /// assert_eq!(trie.get(b"world"), Some(&4));
+    /// ```
+    pub fn replace_subtree(&mut self, prefix: &[K], #[allow(unused_mut)] mut sub: FastrieBuilderNode<V, K>) {
+        assert!(!sub.built, "subtree has already been built");
+        let (index_width, max_cluster_len, merge_clusters) = (self.index_width, self.max_cluster_len, self.merge_clusters);
+        self.split_off(prefix);
+        if sub.value.is_some() || !sub.children.is_empty() {
+            #[cfg(feature = "track-keys")]
+            if !prefix.is_empty() {
+                // Moved instead of copied, as `sub` is consumed anyway.
+                self.inserted_keys.extend(std::mem::take(&mut sub.inserted_keys).into_iter().map(|mut key| {
+                    key.splice(0..0, prefix.iter().copied());
+                    key
+                }));
+            };
+            let mut current: &mut FastrieBuilderNode<V, K> = self;
+            for c in prefix {
+                if !current.children.contains_key(c) {
+                    current.children.insert(*c, current.new_child());
+                };
+                current = current.children.get_mut(c).unwrap();
+            };
+            *current = sub;
+        };
+        // The root may have been replaced, but keeps its settings.
+        self.index_width = index_width;
+        self.max_cluster_len = max_cluster_len;
+        self.merge_clusters = merge_clusters;
+    }
+
+    fn _split_off(&mut self, prefix: &[K]) -> Option<FastrieBuilderNode<V, K>> {
+        if prefix.is_empty() {
+            // The root keeps its settings.
+            let mut root = FastrieBuilderNode::new_with_symbols(self.index_width);
+            root.max_cluster_len = self.max_cluster_len;
+            root.merge_clusters = self.merge_clusters;
+            return Some(std::mem::replace(self, root));
+        };
+        // Nodes along `prefix` left without a value or children are pruned, so the subtree is cut from below the deepest node along it that keeps either, at depth `cut - 1`.
+        let mut cut = 1;
+        let mut node: &FastrieBuilderNode<V, K> = self;
+        for (i, c) in prefix.iter().enumerate() {
+            node = node.children.get(c)?;
+            if i + 1 < prefix.len() && (node.value.is_some() || node.children.len() != 1) {
+                cut = i + 2;
+            };
+        };
+        let mut parent: &mut FastrieBuilderNode<V, K> = self;
+        for c in &prefix[..cut - 1] {
+            parent = parent.children.get_mut(c).unwrap();
+        };
+        let mut split = parent.children.remove(&prefix[cut - 1]).unwrap();
+        for c in &prefix[cut..] {
+            let child = split.children.remove(c).unwrap();
+            split = child;
+        };
+        Some(split)
+    }
+
+    /// Limits each cluster to covering at most `max_cluster_len` chars, including gaps. Wider runs of children are split into multiple clusters, trading more cluster headers for smaller bitmaps to count bits in when looking up a child. This takes precedence over the gap rule, which lets chars up to 3 positions apart share a cluster: such a char starts a new cluster instead if the current one would become too wide. There is no limit by default. Only the limit set on the root builder is used.
+    ///
+    /// Panics if `max_cluster_len` is zero.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use fastrie::*;
+    ///
+    /// let mut builder = FastrieBuilderNode::new(IndexWidth(2));
+    /// for c in 0..=255u8 {
+    ///     builder.add(&[c], c);
+    /// };
+    /// builder.set_max_cluster_len(64);
+    /// let build = builder.prebuild().unwrap();
+    ///
+    /// let layout = Layout::new(build.index_width);
+    /// // The root has four clusters of 64 chars instead of one of 256, and its last children are too far away for one-byte child indices.
+    /// let child_width = layout.child_width(build.data[Layout::HEADER_LEN + layout.node_header_len() - 1]);
+    /// assert_eq!(child_width, IndexWidth(2));
+    /// let root_len = layout.node_header_len() + 4 * layout.cluster_len(0, 63, 64, child_width);
+    /// assert_eq!(build.data.len(), Layout::HEADER_LEN + root_len + 256 * layout.node_header_len());
+    /// let trie = Fastrie::from_prebuilt(build.index_width, &build.values, &build.data).unwrap();
+    /// assert_eq!(trie.verify(), Ok(()));
+    /// assert!((0..=255u8).all(|c| trie.get(&[c]) == Some(&c)));
+    /// ```
+    pub fn set_max_cluster_len(&mut self, max_cluster_len: usize) {
+        assert!(max_cluster_len > 0);
+        self.max_cluster_len = max_cluster_len;
+    }
+
+    /// Enables merging adjacent clusters of a node whenever the merged cluster is smaller than the two separate ones, i.e. when the header saved outweighs the bitmap bits added for the gap between them. The gap rule alone only joins chars up to 3 positions apart, so this reduces size for nodes with children spread slightly further apart, at the cost of a slower build. Merged clusters still respect [`FastrieBuilderNode::set_max_cluster_len`]. This is disabled by default. Only the setting on the root builder is used.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use fastrie::*;
+    ///
+    /// let mut builder = FastrieBuilderNode::new(IndexWidth(2));
+    /// builder.add(b"a", 1);
+    /// builder.add(b"e", 2);
+    /// builder.add(b"i", 3);
+    /// let separate = builder.prebuild().unwrap();
+    ///
+    /// let mut builder = FastrieBuilderNode::new(IndexWidth(2));
+    /// builder.add(b"a", 1);
+    /// builder.add(b"e", 2);
+    /// builder.add(b"i", 3);
+    /// builder.set_merge_clusters(true);
+    /// let merged = builder.prebuild().unwrap();
+    ///
+    /// let layout = Layout::new(merged.index_width);
+    /// // One cluster covering `a..=i` instead of three singleton clusters.
+    /// assert_eq!(merged.data.len(), Layout::HEADER_LEN + layout.node_header_len() + layout.cluster_len(b'a', b'i', 3, IndexWidth(1)) + 3 * layout.node_header_len());
+    /// assert!(merged.data.len() < separate.data.len());
+    /// let trie = Fastrie::from_prebuilt(merged.index_width, &merged.values, &merged.data).unwrap();
+    /// assert_eq!(trie.verify(), Ok(()));
+    /// assert_eq!(trie.get(b"e"), Some(&2));
+    /// assert_eq!(trie.get(b"f"), None);
+    /// ```
+    pub fn set_merge_clusters(&mut self, merge_clusters: bool) {
+        self.merge_clusters = merge_clusters;
+    }
+
+    /// Returns the amount of distinct keys added to this builder. Keys remain counted after building.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use fastrie::*;
+    ///
+    /// let mut builder = FastrieBuilderNode::new(IndexWidth(2));
+    /// assert!(builder.is_empty());
+    /// builder.add(b"hell", 1);
+    /// builder.add(b"hello", 2);
+    /// builder.add(b"world", 3);
+    /// builder.add(b"hell", 4);
+    /// assert_eq!(builder.len(), 3);
+    /// assert!(!builder.is_empty());
+    /// let build = builder.prebuild().unwrap();
+    /// assert_eq!(build.len(), 3);
+    /// assert_eq!(builder.len(), 3);
+    /// ```
+    pub fn len(&self) -> usize {
+        // See `_for_each_node_mut`.
+        let mut len = 0;
+        let mut stack = vec![self];
+        while let Some(node) = stack.pop() {
+            len += node.has_value() as usize;
+            stack.extend(node.children.values());
+        };
+        len
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Returns every key added to this builder, including keys added more than once or moved here by [`FastrieBuilderNode::split_off`], and excluding keys split off. Keys remain after building, so they can be diffed against the keys of a previous build without reading the built data. Only available with the `track-keys` feature, so that normal builds don't pay for tracking.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use std::collections::BTreeSet;
+    /// use fastrie::*;
+    ///
+    /// let mut builder = FastrieBuilderNode::new(IndexWidth(2));
+    /// builder.add(b"hello", 1);
+    /// builder.add(b"hell", 2);
+    /// builder.add(b"world", 3);
+    /// builder.add(b"hell", 4);
+    /// let mut split = builder.split_off(b"wor").unwrap();
+    /// builder.prebuild().unwrap();
+    ///
+    /// let expected = [b"hell".to_vec(), b"hello".to_vec()].iter().cloned().collect::<BTreeSet<Vec<u8>>>();
+    /// assert_eq!(builder.inserted_keys(), &expected);
+    /// assert_eq!(split.inserted_keys().iter().collect::<Vec<_>>(), vec![b"ld"]);
+    /// ```
+    #[cfg(feature = "track-keys")]
+    pub fn inserted_keys(&self) -> &BTreeSet<Vec<K>> {
+        &self.inserted_keys
+    }
+
+    /// Estimates the heap memory used by this builder, excluding any heap memory owned by values. See [`FastrieBuilderNode::heap_size_with`].
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use fastrie::*;
+    ///
+    /// let mut builder = FastrieBuilderNode::new(IndexWidth(2));
+    /// assert_eq!(builder.heap_size(), 0);
+    /// builder.add(b"hell", 1);
+    /// let size = builder.heap_size();
+    /// assert!(size > 0);
+    /// builder.add(b"world", 2);
+    /// assert!(builder.heap_size() > size);
+    /// ```
+    pub fn heap_size(&self) -> usize {
+        self.heap_size_with(&|_| 0)
+    }
+
+    /// Estimates the heap memory used by this builder, using `value_heap_size` to get the heap memory owned by each value (e.g. `String::capacity`). The node itself is not counted, as it's usually on the stack, but all descendant nodes (including their values stored inline) are.
+    ///
+    /// Each `HashMap` of children is counted as its capacity multiplied by the entry size plus one control byte per entry, which approximates the allocation without depending on `HashMap` internals.
+    pub fn heap_size_with(&self, value_heap_size: &impl Fn(&V) -> usize) -> usize {
+        let mut size = 0;
+        let mut stack = vec![self];
+        while let Some(node) = stack.pop() {
+            size += node.children.capacity() * (std::mem::size_of::<(K, FastrieBuilderNode<V, K>)>() + 1);
+            size += node.value.as_ref().map_or(0, value_heap_size);
+            stack.extend(node.children.values());
+        };
+        size
+    }
+
+    /// Walks the unbuilt trie, returning the length and value of the longest key that is a prefix of `text`, including the empty key.
+    fn _longest_matching_prefix(&self, text: &[K]) -> Option<(usize, &V)> {
+        let mut node: &FastrieBuilderNode<V, K> = self;
+        let mut match_opt: Option<(usize, &V)> = self.value.as_ref().map(|v| (0, v));
+        for (i, c) in text.iter().enumerate() {
+            match node.children.get(c) {
+                Some(child) => node = child,
+                None => break,
+            };
+            if let Some(v) = &node.value {
+                match_opt = Some((i + 1, v));
+            };
+        };
+        match_opt
+    }
+
+    fn has_value(&self) -> bool {
+        // Values are moved out when building, but the value index remains.
+        self.value.is_some() || self.value_idx.is_some()
+    }
+
+    /// Returns the chars along the chain of nodes from this node, which must have exactly one child, down to the first node that has a value or doesn't have exactly one child, up to `MAX_PATH_LEN` chars.
+    fn single_child_path(&self) -> Vec<K> {
+        let mut path = Vec::new();
+        let mut node = self;
+        loop {
+            let (&c, child) = node.children.iter().next().unwrap();
+            path.push(c);
+            node = child;
+            if path.len() == MAX_PATH_LEN || node.has_value() || node.children.len() != 1 {
+                break;
+            };
+        };
+        path
+    }
+
+    /// Groups the children's chars into clusters with child indices of `child_width` bytes, in the order they're laid out in the built data.
+    fn child_char_clusters(&self, layout: Layout, child_width: IndexWidth, options: ClusterOptions<K>) -> Vec<Vec<Option<K>>> {
+        let max_cluster_len = options.max_cluster_len;
+        let mut child_chars = self.children.keys().copied().collect::<Vec<K>>();
+        child_chars.sort();
+
+        let mut child_char_clusters: Vec<Vec<Option<K>>> = vec![];
+        let mut last_char: Option<usize> = None;
+        for c in child_chars {
+            let p = c.to_usize();
+            // Allow a maximum gap length of MAX_CLUSTER_GAP_LEN between any two children in a cluster.
+            // Create a new cluster if it's the first char, or previous char in the current cluster is more than 3 character positions away.
+            // A new cluster is also created if the current one would otherwise cover more than `max_cluster_len` chars or be longer than `MAX_CLUSTER_BYTES`, which takes precedence over joining across a gap.
+            let fits = |cluster: &Vec<Option<K>>, last: usize| {
+                let min = cluster.first().unwrap().unwrap();
+                cluster.len() + (p - last) <= max_cluster_len && layout.symbol_cluster_len(min, c, cluster.iter().flatten().count() + 1, child_width) <= MAX_CLUSTER_BYTES
+            };
+            match last_char {
+                Some(last) if p <= last + MAX_CLUSTER_GAP_LEN && fits(child_char_clusters.last().unwrap(), last) => {
+                    // Fill any gaps with None values.
+                    for _ in last + 1..p {
+                        child_char_clusters.last_mut().unwrap().push(None);
+                    };
+                }
+                _ => child_char_clusters.push(Vec::new()),
+            };
+            child_char_clusters.last_mut().unwrap().push(Some(c));
+            last_char = Some(p);
+        };
+        if options.merge_clusters {
+            child_char_clusters = merge_adjacent_clusters(layout, child_width, max_cluster_len, child_char_clusters);
+        };
+        // Check largest first for faster performance on average.
+        child_char_clusters.sort_by_key(|c| Reverse(c.len()));
+        if let Some(cluster_weight) = options.cluster_weight {
+            // Check the most frequently accessed first instead, keeping larger clusters first among equally weighted ones.
+            child_char_clusters.sort_by_cached_key(|c| Reverse(cluster_weight(c)));
+        };
+        child_char_clusters
+    }
+
+    /// Passes the values of this subtree to `add_value` in lexicographic key order, which returns the zero-based value index to refer to each by. This is independent of the order nodes are laid out by `_build`.
+    fn _assign_value_indices(&mut self, add_value: &mut impl FnMut(V) -> usize) {
+        self._for_each_node_mut(&mut |node| {
+            node.value_idx = node.value.take().map(|value| StoredValueIdx::new(add_value(value)));
+        });
+    }
+
+    /// Calls `f` with every node of this subtree in lexicographic order of their keys, which is the order values are given indices in.
+    ///
+    /// Nodes are visited with an explicit stack, as recursing once per char of a long key could overflow the stack. Every other traversal of the builder does the same.
+    fn _for_each_node_mut(&mut self, f: &mut impl FnMut(&mut Self)) {
+        let mut stack = vec![self];
+        while let Some(node) = stack.pop() {
+            f(node);
+            let mut children = node.children.iter_mut().collect::<Vec<_>>();
+            // Popped in ascending order of char.
+            children.sort_by(|(a, _), (b, _)| b.cmp(a));
+            stack.extend(children.into_iter().map(|(_, child)| child));
+        };
+    }
+
+    /// Writes this subtree to `data`. Value indices must have been assigned by `_assign_value_indices`.
+    fn _build(&mut self, index_width: IndexWidth, options: ClusterOptions<K>, data: &mut Vec<u8>) {
+        assert!(!self.built);
+        // Writing to a `Vec` never fails.
+        self._write_records(index_width, IndexWidth(1), options, data).unwrap();
+        self._set_built(true);
+    }
+
+    /// Sets whether this subtree has been built. Clearing it allows the subtree to be built again by `_build`, reusing the value indices already assigned.
+    fn _set_built(&mut self, built: bool) {
+        let mut stack = vec![self];
+        while let Some(node) = stack.pop() {
+            node.built = built;
+            stack.extend(node.children.values_mut());
+        };
+    }
+
+    /// The options set on this builder, which are used if it's the root.
+    fn cluster_options<'w>(&self) -> ClusterOptions<'w, K> {
+        ClusterOptions {
+            max_cluster_len: self.max_cluster_len,
+            merge_clusters: self.merge_clusters,
+            cluster_weight: None,
+        }
+    }
+
+    fn _prebuild(&mut self, index_width: IndexWidth, options: ClusterOptions<K>) -> FastrieBuild<V> {
+        let mut data: Vec<u8> = Vec::new();
+        let mut values: Vec<V> = Vec::new();
+        data.push(index_width.header_byte());
+        self._assign_value_indices(&mut |value| {
+            values.push(value);
+            values.len() - 1
+        });
+        self._build(index_width, options, &mut data);
+        FastrieBuild {
+          data,
+          index_width,
+          values,
+        }
+    }
+
+    /// Builds the trie, consuming the values of this builder. Returns an error if this builder has already been built.
+    ///
+    /// Values are ordered by their keys in lexicographic order, i.e. the value index of a key is its position in [`Fastrie::keys`]. This is independent of how nodes are laid out in the data and of the order keys were added, so value indices are stable across builds of the same keys and can be persisted separately from the values.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use fastrie::*;
+    ///
+    /// let mut builder = FastrieBuilderNode::new(IndexWidth(2));
+    /// for key in ["world", "hello", "hell", "a", "help"].iter() {
+    ///     builder.add(key.as_bytes(), *key);
+    /// };
+    /// let build = builder.prebuild().unwrap();
+    /// assert_eq!(build.values, vec!["a", "hell", "hello", "help", "world"]);
+    ///
+    /// let mut builder = FastrieBuilderNode::new(IndexWidth(2));
+    /// for key in ["help", "a", "hell", "world", "hello"].iter() {
+    ///     builder.add(key.as_bytes(), *key);
+    /// };
+    /// assert_eq!(builder.prebuild().unwrap().values, build.values);
+    /// ```
+    pub fn prebuild(&mut self) -> Result<FastrieBuild<V>, AlreadyBuilt> {
+        if self.built {
+            return Err(AlreadyBuilt);
+        };
+        Ok(self._prebuild(self.index_width, self.cluster_options()))
+    }
+
+    /// Returns whether this builder has been built, in which case its values have been consumed and [`FastrieBuilderNode::prebuild`] returns an error.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use fastrie::*;
+    ///
+    /// let mut builder = FastrieBuilderNode::new(IndexWidth(2));
+    /// builder.add(b"hello", 1);
+    /// assert!(!builder.is_built());
+    /// assert!(builder.prebuild().is_ok());
+    /// assert!(builder.is_built());
+    /// assert_eq!(builder.prebuild().err(), Some(AlreadyBuilt));
+    /// ```
+    pub fn is_built(&self) -> bool {
+        self.built
+    }
+
+    /// Like [`FastrieBuilderNode::prebuild`], but also returns the position in `data` of each key's node, which starts with its value index slot. The slot holds one plus the value index in `index_width` bytes, little endian, so tools can change a key's value index in place without rebuilding.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use fastrie::*;
+    ///
+    /// let mut builder = FastrieBuilderNode::new(IndexWidth(2));
+    /// builder.add(b"hell", 1);
+    /// builder.add(b"hello", 2);
+    /// let (mut build, offsets) = builder.prebuild_with_offsets().unwrap();
+    /// assert_eq!(offsets.len(), 2);
+    ///
+    /// // Point "hell" at the value of "hello", whose value index is 1.
+    /// let slot = offsets[&b"hell".to_vec()];
+    /// build.data[slot..slot + 2].copy_from_slice(&(1u16 + 1).to_le_bytes());
+    /// let trie = Fastrie::from_prebuilt(build.index_width, &build.values, &build.data).unwrap();
+    /// assert_eq!(trie.get(b"hell"), Some(&2));
+    /// ```
+    pub fn prebuild_with_offsets(&mut self) -> Result<(FastrieBuild<V>, KeyOffsets<K>), AlreadyBuilt> {
+        let build = self.prebuild()?;
+        let trie = Fastrie::<_, _, K>::from_prebuilt_with_symbols(build.index_width, &build.values, &build.data).unwrap();
+        let offsets = trie.keys().map(|key| {
+            let node_pos = trie.walk_to(&key).unwrap().node_pos;
+            (key, node_pos)
+        }).collect();
+        Ok((build, offsets))
+    }
+
+    /// Like [`FastrieBuilderNode::prebuild`], but stores each distinct value once, so keys with equal values share one entry in `values`. Queries are unaffected. Values are ordered by the lexicographically first key having them. Returns an error if this builder has already been built.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use fastrie::*;
+    ///
+    /// let mut builder = FastrieBuilderNode::new(IndexWidth(2));
+    /// builder.add(b"amp", "&");
+    /// builder.add(b"AMP", "&");
+    /// builder.add(b"lt", "<");
+    /// builder.add(b"LT", "<");
+    /// let build = builder.prebuild_dedup().unwrap();
+    /// assert_eq!(build.values.len(), 2);
+    ///
+    /// let trie = Fastrie::from_prebuilt(build.index_width, &build.values, &build.data).unwrap();
+    /// assert_eq!(trie.longest_matching_prefix(b"AMP;").unwrap().value, &"&");
+    /// assert_eq!(trie.longest_matching_prefix(b"lt;").unwrap().value, &"<");
+    /// ```
+    pub fn prebuild_dedup(&mut self) -> Result<FastrieBuild<V>, AlreadyBuilt> where V: Hash + Eq {
+        if self.built {
+            return Err(AlreadyBuilt);
+        };
+        let index_width = self.index_width;
+        let mut data: Vec<u8> = Vec::new();
+        let mut pool: HashMap<V, usize> = HashMap::new();
+        data.push(index_width.header_byte());
+        self._assign_value_indices(&mut |value| {
+            let next_idx = pool.len();
+            *pool.entry(value).or_insert(next_idx)
+        });
+        self._build(index_width, self.cluster_options(), &mut data);
+        let mut values = pool.into_iter().collect::<Vec<(V, usize)>>();
+        values.sort_unstable_by_key(|(_, idx)| *idx);
+        Ok(FastrieBuild {
+          data,
+          index_width,
+          values: values.into_iter().map(|(value, _)| value).collect(),
+        })
+    }
+
+    /// Builds using the smallest index width that can represent every value index, ignoring the index width this builder was created with. Child indices have their own width per node, so they don't affect it. Returns an error if this builder has already been built.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use fastrie::*;
+    ///
+    /// let mut builder = FastrieBuilderNode::new(IndexWidth(8));
+    /// builder.add(b"hell", 1);
+    /// builder.add(b"hello", 2);
+    /// let build = builder.prebuild_auto().unwrap();
+    /// assert_eq!(build.index_width, IndexWidth(1));
+    ///
+    /// let mut builder = FastrieBuilderNode::new(IndexWidth(1));
+    /// for i in 0..1000u32 {
+    ///     builder.add(&i.to_be_bytes(), i);
+    /// };
+    /// let build = builder.prebuild_auto().unwrap();
+    /// assert_eq!(build.index_width, IndexWidth(2));
+    /// let trie = Fastrie::from_prebuilt(build.index_width, &build.values, &build.data).unwrap();
+    /// assert_eq!(trie.longest_matching_prefix(&999u32.to_be_bytes()).unwrap().value, &999);
+    /// ```
+    pub fn prebuild_auto(&mut self) -> Result<FastrieBuild<V>, AlreadyBuilt> {
+        if self.built {
+            return Err(AlreadyBuilt);
+        };
+        // The largest stored value index is that of the last value.
+        let max_value_idx = self.len().checked_sub(1).map_or(0, |last| StoredValueIdx::new(last).0.get());
+        Ok(self._prebuild(IndexWidth::smallest_for(max_value_idx), self.cluster_options()))
+    }
+
+    /// Builds like [`FastrieBuilderNode::prebuild`], but orders each node's clusters by descending `cluster_weight`, which is given the characters of a cluster, including `None` gaps, and should return how often lookups are expected to go through it. Clusters are checked in order when looking up a child, so checking frequently accessed clusters first speeds up lookups. Without weights, as with `prebuild`, larger clusters are checked first, which is best if every child is equally likely; clusters of equal weight are also ordered this way. Weights only affect the layout, not the results of queries. Returns an error if this builder has already been built.
+    ///
+    /// Weights are typically derived from character frequencies in a sample of queries.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use fastrie::*;
+    ///
+    /// let mut builder = FastrieBuilderNode::new(IndexWidth(1));
+    /// builder.add(b"apple", 1);
+    /// builder.add(b"banana", 2);
+    /// builder.add(b"zebra", 3);
+    /// // Queries almost always start with 'z'.
+    /// let build = builder.prebuild_weighted(|cluster| if cluster.contains(&Some(b'z')) { 100 } else { 1 }).unwrap();
+    /// let layout = Layout::new(build.index_width);
+    /// // The first cluster of the root, which is at position 1, starts with 'z'.
+    /// let first_cluster_pos = 1 + layout.node_header_len();
+    /// assert_eq!(build.data[first_cluster_pos + 1], b'z');
+    /// let trie = Fastrie::from_prebuilt(build.index_width, &build.values, &build.data).unwrap();
+    /// assert_eq!(trie.get(b"apple"), Some(&1));
+    /// assert_eq!(trie.get(b"zebra"), Some(&3));
+    /// ```
+    pub fn prebuild_weighted(&mut self, cluster_weight: impl Fn(&[Option<K>]) -> u64) -> Result<FastrieBuild<V>, AlreadyBuilt> {
+        if self.built {
+            return Err(AlreadyBuilt);
+        };
+        let options = ClusterOptions {
+            cluster_weight: Some(&cluster_weight),
+            ..self.cluster_options()
+        };
+        Ok(self._prebuild(self.index_width, options))
+    }
+
+    /// Builds the trie once for each of `widths`, in that order, consuming the values of this builder. Each build gets a clone of the values; value indices are assigned once and are the same across all builds. Returns an error if this builder has already been built. If `widths` is empty, nothing is built and the builder is left as is, so it can still be built later.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use fastrie::*;
+    ///
+    /// let mut builder = FastrieBuilderNode::new(IndexWidth(1));
+    /// builder.add(b"hell", 1);
+    /// builder.add(b"hello", 2);
+    /// assert!(builder.prebuild_widths(&[]).unwrap().is_empty());
+    /// assert!(!builder.is_built());
+    /// let builds = builder.prebuild_widths(&[IndexWidth(4), IndexWidth(8)]).unwrap();
+    /// assert_eq!(builds.len(), 2);
+    /// for build in builds.iter() {
+    ///     let trie = Fastrie::from_prebuilt(build.index_width, &build.values, &build.data).unwrap();
+    ///     assert_eq!(trie.longest_matching_prefix(b"hello!").unwrap().value, &2);
+    /// };
+    /// assert_eq!(builds[0].index_width, IndexWidth(4));
+    /// assert_eq!(builds[1].index_width, IndexWidth(8));
+    /// assert!(builds[0].data.len() < builds[1].data.len());
+    /// assert_eq!(builder.prebuild_widths(&[IndexWidth(2)]).err(), Some(AlreadyBuilt));
+    /// ```
+    pub fn prebuild_widths(&mut self, widths: &[IndexWidth]) -> Result<Vec<FastrieBuild<V>>, AlreadyBuilt> where V: Clone {
+        if self.built {
+            return Err(AlreadyBuilt);
+        };
+        // Assigning value indices takes the values, so don't unless something will be built with them.
+        if widths.is_empty() {
+            return Ok(Vec::new());
+        };
+        let mut values: Vec<V> = Vec::new();
+        self._assign_value_indices(&mut |value| {
+            values.push(value);
+            values.len() - 1
+        });
+        let mut builds = Vec::with_capacity(widths.len());
+        for (i, &index_width) in widths.iter().enumerate() {
+            if i > 0 {
+                self._set_built(false);
+            };
+            let mut data: Vec<u8> = vec![index_width.header_byte()];
+            self._build(index_width, self.cluster_options(), &mut data);
+            builds.push(FastrieBuild {
+              data,
+              index_width,
+              values: values.clone(),
+            });
+        };
+        Ok(builds)
+    }
+}
+
+impl<T, K: Symbol> FastrieBuilderNode<Vec<T>, K> {
+    /// Appends `value` to the values of the key `pattern`, instead of overwriting them like [`FastrieBuilderNode::add`]. This allows a key to map to several values; queries return all of them as a `Vec<T>`, in the order they were added.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use fastrie::*;
+    ///
+    /// let mut builder = FastrieBuilderNode::new(IndexWidth(2));
+    /// builder.add_multi(b"st", "street");
+    /// builder.add_multi(b"st", "saint");
+    /// builder.add_multi(b"ave", "avenue");
+    /// let build = builder.prebuild().unwrap();
+    ///
+    /// let trie = Fastrie::from_prebuilt(build.index_width, &build.values, &build.data).unwrap();
+    /// assert_eq!(trie.longest_matching_prefix(b"st.").unwrap().value.as_slice(), &["street", "saint"]);
+    /// assert_eq!(trie.longest_matching_prefix(b"ave").unwrap().value.as_slice(), &["avenue"]);
+    /// ```
+    pub fn add_multi(&mut self, pattern: &[K], value: T) {
+        self.node_mut(pattern).value.get_or_insert_with(Vec::new).push(value);
+    }
+}
+
+impl<K: Symbol> FastrieBuilderNode<(), K> {
+    /// Adds the key `pattern` to a set, returning whether it was new. Adding a key twice is harmless, as [`FastrieBuilderNode::add`] overwrites `()` with `()`, but is often a sign of a bug in deduplicating the keys, which this detects.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use fastrie::*;
+    ///
+    /// let mut builder = FastrieBuilderNode::new(IndexWidth(2));
+    /// assert!(builder.add_unique(b"hell"));
+    /// assert!(builder.add_unique(b"hello"));
+    /// assert!(!builder.add_unique(b"hell"));
+    /// assert_eq!(builder.prebuild().unwrap().len(), 2);
+    /// ```
+    pub fn add_unique(&mut self, pattern: &[K]) -> bool {
+        self.node_mut(pattern).value.replace(()).is_none()
+    }
+}
+
+/// Maps zero-based value indices of a build to values. This allows values to be backed by something other than a slice, such as an interned pool or a memory-mapped region.
+///
+/// # Example
+///
+/// ```
+/// use fastrie::*;
+///
+/// struct Interned {
+///     pool: Vec<String>,
+///     // Value index to pool index.
+///     ids: Vec<usize>,
+/// }
+///
+/// impl<'v> ValueStore<'v, String> for &'v Interned {
+///     fn get(&self, idx: usize) -> Option<&'v String> {
+///         self.ids.get(idx).map(|&id| &self.pool[id])
+///     }
+/// }
+///
+/// let mut builder = FastrieBuilderNode::new(IndexWidth(2));
+/// builder.add(b"hell", 1);
+/// builder.add(b"hello", 0);
+/// let build = builder.prebuild().unwrap();
+///
+/// let interned = Interned {
+///     pool: vec!["greeting".to_string(), "place".to_string()],
+///     ids: build.values,
+/// };
+/// let trie = Fastrie::from_prebuilt_with_store(build.index_width, &interned, &build.data);
+/// assert_eq!(trie.longest_matching_prefix(b"hello").unwrap().value, "greeting");
+/// assert_eq!(trie.longest_matching_prefix(b"hell").unwrap().value, "place");
+/// ```
+pub trait ValueStore<'v, V: ?Sized> {
+    /// Returns the value at the zero-based value index `idx`, or `None` if it's out of range.
+    fn get(&self, idx: usize) -> Option<&'v V>;
+}
+
+impl<'v, V> ValueStore<'v, V> for &'v [V] {
+    fn get(&self, idx: usize) -> Option<&'v V> {
+        <[V]>::get(self, idx)
+    }
+}
+
+/// Boxed values, which allows unsized values like `str` or `[u8]`. See [`Fastrie::from_prebuilt_boxed`].
+impl<'v, V: ?Sized> ValueStore<'v, V> for &'v [Box<V>] {
+    fn get(&self, idx: usize) -> Option<&'v V> {
+        <[Box<V>]>::get(self, idx).map(|v| &**v)
+    }
+}
+
+/// Values decoded on demand by a closure from their zero-based value index. See [`Fastrie::from_prebuilt_with_decoder`].
+#[derive(Clone, Copy)]
+pub struct DecodedValues<F> {
+    decode: F,
+}
+
+impl<'v, V: ?Sized + 'v, F: Fn(usize) -> &'v V> ValueStore<'v, V> for DecodedValues<F> {
+    fn get(&self, idx: usize) -> Option<&'v V> {
+        Some((self.decode)(idx))
+    }
+}
+
+pub struct Fastrie<'v, 'd, V: ?Sized, S = &'v [V], K = u8> {
+    data: &'d [u8],
+    index_width: IndexWidth,
+    // If None, keys are used as a set.
+    values: Option<S>,
+    value_type: PhantomData<&'v V>,
+    symbol_type: PhantomData<K>,
+}
+
+/// Receives events during a walk. The `()` implementation does nothing, so the counting compiles away for normal queries.
+trait WalkCounters {
+    fn visit_node(&mut self);
+    fn examine_cluster(&mut self);
+    /// Called with the number of gaps before the char in the cluster containing it.
+    fn skip_gaps(&mut self, gaps: usize);
+}
+
+impl WalkCounters for () {
+    #[inline(always)]
+    fn visit_node(&mut self) {}
+
+    #[inline(always)]
+    fn examine_cluster(&mut self) {}
+
+    #[inline(always)]
+    fn skip_gaps(&mut self, _gaps: usize) {}
+}
+
+/// A position in a walk, which is at a node, or part way along the path of a node before the char at `path_offset`.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+struct TriePos {
+    node_pos: usize,
+    path_offset: usize,
+}
+
+impl TriePos {
+    const ROOT: TriePos = TriePos::node(ROOT_POS);
+
+    const fn node(node_pos: usize) -> TriePos {
+        TriePos { node_pos, path_offset: 0 }
+    }
+}
+
+/// Counters collected during a single query by [`Fastrie::longest_matching_prefix_profiled`].
+#[cfg(feature = "profiling")]
+#[derive(Clone, Copy, PartialEq, Eq, Debug, Default)]
+pub struct QueryStats {
+    /// Clusters whose range was compared against a query char, across all nodes. A single child or a path counts as one cluster.
+    pub clusters_examined: usize,
+    /// Nodes descended into, including the root.
+    pub nodes_visited: usize,
+}
+
+#[cfg(feature = "profiling")]
+impl WalkCounters for QueryStats {
+    fn visit_node(&mut self) {
+        self.nodes_visited += 1;
+    }
+
+    fn examine_cluster(&mut self) {
+        self.clusters_examined += 1;
+    }
+
+    fn skip_gaps(&mut self, _gaps: usize) {}
+}
+
+/// Cost of looking up a key, returned by [`Fastrie::path_cost`].
+#[derive(Clone, Copy, PartialEq, Eq, Debug, Default)]
+pub struct PathCost {
+    /// Clusters whose range was compared against a char of the key, across all nodes. A single child or a path counts as one cluster.
+    pub clusters_examined: usize,
+    /// Gaps in the clusters containing the chars of the key that precede those chars, i.e. bitmap bits counted past to find each child that aren't children.
+    pub gaps_skipped: usize,
+    /// Nodes descended into, including the root.
+    pub nodes_visited: usize,
+}
+
+impl WalkCounters for PathCost {
+    fn visit_node(&mut self) {
+        self.nodes_visited += 1;
+    }
+
+    fn examine_cluster(&mut self) {
+        self.clusters_examined += 1;
+    }
+
+    fn skip_gaps(&mut self, gaps: usize) {
+        self.gaps_skipped += gaps;
+    }
+}
+
+/// Result of [`Fastrie::classify`].
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum KeyClass {
+    /// The key exists in the trie.
+    Exact,
+    /// The key doesn't exist, but is a prefix of one or more keys.
+    Prefix,
+    /// The key doesn't exist and isn't a prefix of any key.
+    None,
+}
+
+pub struct FastrieMatch<'v, V: ?Sized> {
+    /// Inclusive. This is zero unless the query started from an offset in the text.
+    pub start: usize,
+    /// Inclusive, so it's the index of the last matched symbol only if `len` is nonzero. If the empty key matched, no symbol was matched and this is equal to `start`, so it mustn't be used to slice the text; [`FastrieMatch::range`] and [`FastrieMatch::matched`] are correct for both.
+    pub end: usize,
+    /// Amount of symbols matched. This is zero if the matched key is the empty key, which [`Fastrie::longest_matching_prefix`] returns if no other key matches.
+    pub len: usize,
+    pub value: &'v V,
+    /// Zero-based index of `value` in the values of the [`FastrieBuild`], for looking up data stored in parallel to them. This is `usize::MAX` for matches from the overlay of a [`GrowableFastrie`], as those values aren't in the base's values.
+    pub value_index: usize,
+}
+
+impl<'v, V: ?Sized> FastrieMatch<'v, V> {
+    /// Creates a match of the `len` symbols from `start`, setting `end` to `start` if `len` is zero.
+    pub(crate) fn new(start: usize, len: usize, value: &'v V, value_index: usize) -> FastrieMatch<'v, V> {
+        FastrieMatch {
+            start,
+            end: start + len.saturating_sub(1),
+            len,
+            value,
+            value_index,
+        }
+    }
+
+    /// The range of the matched bytes in the query, i.e. `start..start + len`. Unlike `end`, this is exclusive, so it's empty if the empty key matched.
+    pub fn range(&self) -> Range<usize> {
+        self.start..self.start + self.len
+    }
+
+    /// Returns the symbols in `text` that were matched, i.e. `&text[self.range()]`. `text` should be the query that produced this match.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use fastrie::*;
+    ///
+    /// let mut builder = FastrieBuilderNode::new(IndexWidth(2));
+    /// builder.add(b"hell", 1);
+    /// let build = builder.prebuild().unwrap();
+    ///
+    /// let trie = Fastrie::from_prebuilt(build.index_width, &build.values, &build.data).unwrap();
+    /// let query = b"hello";
+    /// let mat = trie.longest_matching_prefix(query).unwrap();
+    /// assert_eq!(mat.range(), 0..4);
+    /// assert_eq!(mat.matched(query), b"hell");
+    /// ```
+    pub fn matched<'a, K>(&self, text: &'a [K]) -> &'a [K] {
+        &text[self.range()]
+    }
+}
+
+/// Result of [`Fastrie::match_detail`].
+pub struct MatchDetail<'v, V: ?Sized> {
+    /// The longest key that is a prefix of the text, as by [`Fastrie::longest_matching_prefix`].
+    pub longest: Option<FastrieMatch<'v, V>>,
+    /// The value of the whole text if it's a key, as by [`Fastrie::get`].
+    pub exact: Option<&'v V>,
+}
+
+/// A node of built data, decoded by [`Fastrie::node_at`].
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub struct NodeView<K = u8> {
+    /// The zero-based value index of the node, or `None` if it has no value.
+    pub value_idx: Option<usize>,
+    /// The node's children-present byte, which says whether and how its children are stored. See [`Layout`].
+    pub children_present: u8,
+    /// The chars of the node's path and the position of the node at its end, if the node is followed by a path. The nodes along a path aren't stored, so they have no position.
+    pub path: Option<(Vec<K>, usize)>,
+    /// The char and position of each child of a node with a single child or clusters, sorted by char. Empty if the node has a path.
+    pub children: Vec<(K, usize)>,
+}
+
+/// Iterator over the keys of a [`Fastrie`], in lexicographic byte order. Created by [`Fastrie::keys`].
+pub struct FastrieKeys<'d, K = u8> {
+    trie: Fastrie<'d, 'd, (), &'d [()], K>,
+    // Nodes yet to be visited, with the key leading to them. The top of the stack is the lexicographically smallest.
+    stack: Vec<(Vec<K>, TriePos)>,
+}
+
+impl<K: Symbol> FastrieKeys<'_, K> {
+    /// Returns the next key and its zero-based value index.
+    fn next_entry(&mut self) -> Option<(Vec<K>, usize)> {
+        while let Some((key, pos)) = self.stack.pop() {
+            // Push in reverse so that the smallest child is visited first, regardless of how clusters are laid out in `data`.
+            for (c, child_pos) in self.trie.children(pos).into_iter().rev() {
+                let mut child_key = key.clone();
+                child_key.push(c);
+                self.stack.push((child_key, child_pos));
+            };
+            if let Some(value_idx) = self.trie.pos_value_idx(pos) {
+                return Some((key, value_idx));
+            };
+        };
+        None
+    }
+}
+
+impl<K: Symbol> Iterator for FastrieKeys<'_, K> {
+    type Item = Vec<K>;
+
+    fn next(&mut self) -> Option<Vec<K>> {
+        self.next_entry().map(|(key, _)| key)
+    }
+}
+
+/// Iterator over the entries of a [`Fastrie`], in lexicographic byte order of keys. Created by [`Fastrie::iter`].
+pub struct FastrieIter<'t, 'v, 'd, V: ?Sized, S, K = u8> {
+    keys: FastrieKeys<'d, K>,
+    values: &'t S,
+    value_type: PhantomData<&'v V>,
+}
+
+impl<'v, V: ?Sized, S: ValueStore<'v, V>, K: Symbol> Iterator for FastrieIter<'_, 'v, '_, V, S, K> {
+    type Item = (Vec<K>, &'v V);
+
+    fn next(&mut self) -> Option<(Vec<K>, &'v V)> {
+        self.keys.next_entry().map(|(key, value_idx)| (key, self.values.get(value_idx).unwrap()))
+    }
+}
+
+/// State of a longest prefix match walk over text arriving in fragments, created with [`FastriePosition::default`] and advanced by [`Fastrie::advance`]. It's a plain value, so it can be stored between reads or held across awaits.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub struct FastriePosition {
+    // None if the walk has fallen off the trie.
+    trie_pos: Option<TriePos>,
+    // Amount of text walked so far.
+    consumed: usize,
+    // Length and value index of the longest nonempty match so far.
+    best: Option<(usize, usize)>,
+}
+
+impl Default for FastriePosition {
+    /// The position at the root, before any text.
+    fn default() -> FastriePosition {
+        FastriePosition {
+            trie_pos: Some(TriePos::ROOT),
+            consumed: 0,
+            best: None,
+        }
+    }
+}
+
+/// Iterator over non-overlapping leftmost-longest matches in a text. Created by [`Fastrie::scan`].
+pub struct FastrieScan<'t, 'v, 'd, V: ?Sized, S, K = u8> {
+    trie: &'t Fastrie<'v, 'd, V, S, K>,
+    text: &'t [K],
+    pos: usize,
+}
+
+impl<'t, 'v, V: ?Sized, S: ValueStore<'v, V>, K: Symbol> Iterator for FastrieScan<'t, 'v, '_, V, S, K> {
+    type Item = FastrieMatch<'t, V>;
+
+    fn next(&mut self) -> Option<FastrieMatch<'t, V>> {
+        while self.pos < self.text.len() {
+            match self.trie.longest_matching_prefix_at(self.text, self.pos) {
+                // The empty key would match at every position, so it's never reported.
+                Some(mat) if mat.len > 0 => {
+                    self.pos = mat.end + 1;
+                    return Some(mat);
+                }
+                _ => self.pos += 1,
+            };
+        };
+        None
+    }
+}
+
+/// Result of [`FastrieCursor::step`].
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum StepResult<'v, V: ?Sized> {
+    /// No key continues with the char, so the cursor didn't move.
+    NoChild,
+    /// The cursor moved to the child for the char. `terminal_value` is the value of the key ending there, if any and if it's available; for tries without values, e.g. from [`Fastrie::as_set`], it's always `None`, and [`FastrieCursor::value_index`] says whether a key ends there.
+    Descended { terminal_value: Option<&'v V> },
+}
+
+/// A position in the trie that can be moved one char at a time. This is the low-level API that the other queries are built on; it allows custom queries such as shortest or streaming matches without a method for each. Created by [`Fastrie::cursor`], at the root.
+///
+/// # Example
+///
+/// ```
+/// use fastrie::*;
+///
+/// let mut builder = FastrieBuilderNode::new(IndexWidth(2));
+/// builder.add(b"he", 1);
+/// builder.add(b"hello", 2);
+/// let build = builder.prebuild().unwrap();
+///
+/// let trie = Fastrie::from_prebuilt(build.index_width, &build.values, &build.data).unwrap();
+/// // Shortest match.
+/// let mut cursor = trie.cursor();
+/// let shortest = b"hello".iter().enumerate().find_map(|(i, &c)| match cursor.step(c) {
+///     StepResult::Descended { terminal_value: Some(value) } => Some((i, *value)),
+///     _ => None,
+/// });
+/// assert_eq!(shortest, Some((1, 1)));
+///
+/// let mut cursor = trie.cursor();
+/// assert_eq!(cursor.step(b'h'), StepResult::Descended { terminal_value: None });
+/// assert_eq!(cursor.step(b'x'), StepResult::NoChild);
+/// assert_eq!(cursor.step(b'e'), StepResult::Descended { terminal_value: Some(&1) });
+/// assert_eq!(cursor.value_index(), Some(0));
+/// assert!(cursor.has_children());
+/// ```
+pub struct FastrieCursor<'t, 'v, 'd, V: ?Sized, S, K = u8> {
+    trie: &'t Fastrie<'v, 'd, V, S, K>,
+    // The node at the cursor, or that has the path the cursor is on.
+    node_pos: usize,
+    // If the node has a path, the position of its chars and its length, and how many of them have been walked. Otherwise, `path_len` is zero.
+    path_chars_pos: usize,
+    path_len: usize,
+    path_offset: usize,
+}
+
+impl<'v, V: ?Sized, S: ValueStore<'v, V>, K: Symbol> FastrieCursor<'_, 'v, '_, V, S, K> {
+    /// Moves to the child for `c`, if it exists.
+    #[inline]
+    pub fn step(&mut self, c: K) -> StepResult<'v, V> {
+        if self.path_len == 0 {
+            // The children present byte says both whether and how the node has children, so it's read once.
+            let children_present = self.trie.data[self.node_pos + self.trie.index_width.0];
+            if children_present & NODE_CHILDREN_MASK == 0 {
+                return StepResult::NoChild;
+            };
+            if children_present & NODE_HAS_PATH == 0 {
+                return match self.trie.walker().child_of(self.node_pos, children_present, c, &mut ()) {
+                    Some(child_pos) => self.descend_to_node(child_pos),
+                    None => StepResult::NoChild,
+                };
+            };
+            let (chars_pos, len) = self.trie.path(self.node_pos);
+            self.path_chars_pos = chars_pos;
+            self.path_len = len;
+            self.path_offset = 0;
+        };
+        if read_symbol::<K>(self.trie.data, self.path_chars_pos + self.path_offset * K::WIDTH) != c {
+            if self.path_offset == 0 {
+                // Still at the node.
+                self.path_len = 0;
+            };
+            return StepResult::NoChild;
+        };
+        self.path_offset += 1;
+        if self.path_offset < self.path_len {
+            // Nodes along a path never have values.
+            return StepResult::Descended { terminal_value: None };
+        };
+        // The node at the end of the path immediately follows it.
+        self.descend_to_node(self.path_chars_pos + self.path_len * K::WIDTH)
+    }
+
+    #[inline(always)]
+    fn descend_to_node(&mut self, node_pos: usize) -> StepResult<'v, V> {
+        self.node_pos = node_pos;
+        self.path_len = 0;
+        StepResult::Descended { terminal_value: self.trie.node_value_idx(node_pos).and_then(|value_idx| self.trie.get_value(value_idx)) }
+    }
+
+    /// Returns the zero-based value index of the key ending at the cursor, if any.
+    #[inline]
+    pub fn value_index(&self) -> Option<usize> {
+        if self.path_len != 0 {
+            return None;
+        };
+        self.trie.node_value_idx(self.node_pos)
+    }
+
+    /// Returns whether any key continues past the cursor. If not, every further step returns [`StepResult::NoChild`].
+    #[inline]
+    pub fn has_children(&self) -> bool {
+        self.path_len != 0 || self.trie.has_children(self.node_pos)
+    }
+}
+
+/// Creates a trie without values, i.e. a set. Like [`Fastrie::from_prebuilt_unchecked`], the index width isn't checked against the header of `data`; use [`Fastrie::verify`] to check it.
+///
+/// # Example
+///
+/// ```
+/// use fastrie::*;
+///
+/// let mut builder = FastrieBuilderNode::new(IndexWidth(2));
+/// builder.add(b"hell", 1);
+/// builder.add(b"hello", 2);
+/// builder.add(b"world", 4);
+/// let build = builder.prebuild().unwrap();
+///
+/// // `build.data` can be written as bytes to a file, or embedded directly into code as a literal byte array/slice.
+///
+/// let trie = from_prebuilt_without_values(build.index_width, &build.data);
+/// assert!(trie.contains_key(b"hell"));
+/// assert!(trie.contains_key(b"hello"));
+/// assert!(trie.contains_key(b"world"));
+/// assert!(!trie.contains_key(b"worl"));
+/// assert!(!trie.contains_key(b"worlds"));
+/// ```
+pub const fn from_prebuilt_without_values<'d>(index_width: IndexWidth, data: &'d [u8]) -> Fastrie<'d, 'd, ()> {
+  Fastrie {
+    data,
+    index_width,
+    values: None,
+    value_type: PhantomData,
+    symbol_type: PhantomData,
+  }
+}
+
+impl<'v, 'd, V> Fastrie<'v, 'd, V> {
+    /// # Example
+    ///
+    /// ```
+    /// use fastrie::*;
+    ///
+    /// let mut builder = FastrieBuilderNode::new(IndexWidth(3));
+    /// builder.add(b"hell", 1);
+    /// builder.add(b"hello", 2);
+    /// builder.add(b"world", 4);
+    /// let build = builder.prebuild().unwrap();
+    ///
+    /// // `build.data` can be written as bytes to a file, or embedded directly into code as a literal byte array/slice.
+    ///
+    /// let trie = Fastrie::from_prebuilt(build.index_width, &build.values, &build.data).unwrap();
+    /// assert!(trie.contains_key(b"hello"));
+    /// let query = b"hello world!";
+    /// let mat = trie.longest_matching_prefix(query).unwrap();
+    /// assert_eq!(mat.end, 4);
+    /// assert_eq!(&query[..=mat.end], b"hello");
+    /// assert_eq!(mat.value, &2);
+    /// let query = b"hell's kitchen";
+    /// let mat = trie.longest_matching_prefix(query).unwrap();
+    /// assert_eq!(mat.end, 3);
+    /// assert_eq!(&query[..=mat.end], b"hell");
+    /// assert_eq!(mat.value, &1);
+    ///
+    /// // The data records the index width it was built with, so a different one is rejected.
+    /// let err = Fastrie::from_prebuilt(IndexWidth(2), &build.values, &build.data).err();
+    /// assert_eq!(err, Some(FastrieError::IndexWidthMismatch { given: IndexWidth(2), built: IndexWidth(3) }));
+    /// ```
+    pub const fn from_prebuilt(index_width: IndexWidth, values: &'v [V], data: &'d [u8]) -> Result<Fastrie<'v, 'd, V>, FastrieError> {
+        Fastrie::from_prebuilt_with_symbols(index_width, values, data)
+    }
+
+    /// Like [`Fastrie::from_prebuilt`], but doesn't check the index width against the header of `data`. This avoids the check and the `Result` for data embedded as a literal, where the index width is known to be correct.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use fastrie::*;
+    ///
+    /// // The data built from the key "a" with `IndexWidth(1)`.
+    /// static DATA: &[u8] = &[0xF1, 0, 2, b'a', 4, 1, 8];
+    /// static VALUES: &[&str] = &["a"];
+    /// static TRIE: Fastrie<&str> = Fastrie::from_prebuilt_unchecked(IndexWidth(1), VALUES, DATA);
+    ///
+    /// assert_eq!(TRIE.longest_matching_prefix(b"ab").unwrap().value, &"a");
+    /// ```
+    pub const fn from_prebuilt_unchecked(index_width: IndexWidth, values: &'v [V], data: &'d [u8]) -> Fastrie<'v, 'd, V> {
+        Fastrie {
+          data,
+          index_width,
+          values: Some(values),
+          value_type: PhantomData,
+          symbol_type: PhantomData,
+        }
+    }
+}
+
+impl<'v, 'd, V, K: Symbol> Fastrie<'v, 'd, V, &'v [V], K> {
+    /// Like [`Fastrie::from_prebuilt`], but for data built from keys of `K` symbols instead of bytes. See [`Symbol`].
+    pub const fn from_prebuilt_with_symbols(index_width: IndexWidth, values: &'v [V], data: &'d [u8]) -> Result<Fastrie<'v, 'd, V, &'v [V], K>, FastrieError> {
+        if let Err(err) = index_width.check_header(data) {
+            return Err(err);
+        };
+        Ok(Fastrie {
+          data,
+          index_width,
+          values: Some(values),
+          value_type: PhantomData,
+          symbol_type: PhantomData,
+        })
+    }
+}
+
+impl<'v, 'd, V: ?Sized, S: ValueStore<'v, V>> Fastrie<'v, 'd, V, S> {
+    /// Like [`Fastrie::from_prebuilt`], but resolves values through a custom [`ValueStore`] instead of a slice.
+    pub const fn from_prebuilt_with_store(index_width: IndexWidth, values: S, data: &'d [u8]) -> Fastrie<'v, 'd, V, S> {
+        Fastrie {
+          data,
+          index_width,
+          values: Some(values),
+          value_type: PhantomData,
+          symbol_type: PhantomData,
+        }
+    }
+
+    /// Like [`Fastrie::longest_matching_prefix`], but pulls the text from `r` a byte at a time, stopping as soon as no key can continue, and also returns how many bytes were read. Bytes read past the end of the match, i.e. `bytes_read - (end + 1)` or all of them if there's no match, can be put back by the caller. Wrap `r` in [`std::io::Read::take`] to limit how much is read, or a [`std::io::BufReader`] if reads are expensive.
+    ///
+    /// The walk can only tell that a byte has no child by reading it, so that byte is included in the count. No byte is read after reaching a node without children, or at the end of `r`.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use std::io::{Cursor, Read};
+    /// use fastrie::*;
+    ///
+    /// let mut builder = FastrieBuilderNode::new(IndexWidth(2));
+    /// builder.add(b"GET", 1);
+    /// builder.add(b"GET /", 2);
+    /// builder.add(b"POST", 3);
+    /// let build = builder.prebuild().unwrap();
+    ///
+    /// let trie = Fastrie::from_prebuilt(build.index_width, &build.values, &build.data).unwrap();
+    /// // ' ' follows "GET", but 'x' doesn't follow "GET ", and is read to find that out.
+    /// let (mat, bytes_read) = trie.longest_matching_prefix_read(Cursor::new(b"GET x")).unwrap();
+    /// assert_eq!(mat.unwrap().end, 2);
+    /// assert_eq!(bytes_read, 5);
+    /// // "POST" has no children, so nothing after it is read.
+    /// let mut r = Cursor::new(b"POST /");
+    /// let (mat, bytes_read) = trie.longest_matching_prefix_read(&mut r).unwrap();
+    /// assert_eq!(mat.unwrap().value, &3);
+    /// assert_eq!(bytes_read, 4);
+    /// assert_eq!(r.position(), 4);
+    /// // Limited to four bytes, "GET /" can't match.
+    /// let (mat, bytes_read) = trie.longest_matching_prefix_read(Cursor::new(b"GET /").take(4)).unwrap();
+    /// assert_eq!(mat.unwrap().value, &1);
+    /// assert_eq!(bytes_read, 4);
+    /// ```
+    pub fn longest_matching_prefix_read<R: io::Read>(&self, mut r: R) -> io::Result<(Option<FastrieMatch<'v, V>>, usize)> {
+        let mut cursor = self.cursor();
+        let mut mat = self.root_match();
+        let mut bytes_read = 0;
+        let mut byte = [0u8; 1];
+        while cursor.has_children() {
+            match r.read(&mut byte) {
+                Ok(0) => break,
+                Ok(_) => {}
+                Err(e) if e.kind() == io::ErrorKind::Interrupted => continue,
+                Err(e) => return Err(e),
+            };
+            bytes_read += 1;
+            match cursor.step(byte[0]) {
+                StepResult::NoChild => break,
+                StepResult::Descended { terminal_value: Some(value) } => mat = Some(FastrieMatch {
+                    start: 0,
+                    end: bytes_read - 1,
+                    len: bytes_read,
+                    value,
+                    value_index: cursor.value_index().unwrap(),
+                }),
+                StepResult::Descended { terminal_value: None } => {}
+            };
+        };
+        Ok((mat, bytes_read))
+    }
+}
+
+impl<'v, 'd, V: ?Sized> Fastrie<'v, 'd, V, &'v [Box<V>]> {
+    /// Like [`Fastrie::from_prebuilt`], but for values built as `Box<V>`, which are returned as `&V`. This allows unsized values like `str` or `[u8]` without wrapping them in `String` or `Vec<u8>`.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use fastrie::*;
+    ///
+    /// let mut builder = FastrieBuilderNode::new(IndexWidth(2));
+    /// builder.add(b"hell", Box::<str>::from("place"));
+    /// builder.add(b"hello", Box::<str>::from("greeting"));
+    /// let build = builder.prebuild().unwrap();
+    ///
+    /// let trie: Fastrie<str, _> = Fastrie::from_prebuilt_boxed(build.index_width, &build.values, &build.data).unwrap();
+    /// let value: &str = trie.longest_matching_prefix(b"hello!").unwrap().value;
+    /// assert_eq!(value, "greeting");
+    /// assert_eq!(trie.get(b"hell"), Some("place"));
+    /// ```
+    pub const fn from_prebuilt_boxed(index_width: IndexWidth, values: &'v [Box<V>], data: &'d [u8]) -> Result<Fastrie<'v, 'd, V, &'v [Box<V>]>, FastrieError> {
+        if let Err(err) = index_width.check_header(data) {
+            return Err(err);
+        };
+        Ok(Fastrie::from_prebuilt_with_store(index_width, values, data))
+    }
+}
+
+impl<'v, 'd, V: ?Sized + 'v, F: Fn(usize) -> &'v V> Fastrie<'v, 'd, V, DecodedValues<F>> {
+    /// Like [`Fastrie::from_prebuilt`], but resolves each value by calling `decode` with its zero-based value index when it's returned, so values can be sliced lazily from a region that's separate from the data, such as a memory-mapped value section, without copying them into a `&[V]` first. `decode` is only called with value indices of the build.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use fastrie::*;
+    ///
+    /// let mut builder = FastrieBuilderNode::new(IndexWidth(2));
+    /// builder.add(b"hell", 0);
+    /// builder.add(b"hello", 1);
+    /// let build = builder.prebuild().unwrap();
+    ///
+    /// // Value region of length-prefixed strings, and where each value index starts in it.
+    /// let region = b"\x05place\x08greeting";
+    /// let starts = [0, 6];
+    /// let decode = |idx: usize| {
+    ///     let start = starts[build.values[idx]];
+    ///     std::str::from_utf8(&region[start + 1..start + 1 + region[start] as usize]).unwrap()
+    /// };
+    /// let trie = Fastrie::from_prebuilt_with_decoder(build.index_width, &build.data, decode).unwrap();
+    /// assert_eq!(trie.longest_matching_prefix(b"hello!").unwrap().value, "greeting");
+    /// assert_eq!(trie.get(b"hell"), Some("place"));
+    /// ```
+    pub fn from_prebuilt_with_decoder(index_width: IndexWidth, data: &'d [u8], decode: F) -> Result<Fastrie<'v, 'd, V, DecodedValues<F>>, FastrieError> {
+        index_width.check_header(data)?;
+        Ok(Fastrie::from_prebuilt_with_store(index_width, DecodedValues { decode }, data))
+    }
+}
+
+impl<'v, 'd, V: ?Sized, S: ValueStore<'v, V>, K: Symbol> Fastrie<'v, 'd, V, S, K> {
+    /// Returns an iterator over all `(key, value)` entries in the trie, in the same guaranteed lexicographic key order as [`Fastrie::keys`].
+    ///
+    /// Panics if the trie was created without values.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use fastrie::*;
+    ///
+    /// let mut builder = FastrieBuilderNode::new(IndexWidth(2));
+    /// builder.add(b"hello", 2);
+    /// builder.add(b"world", 4);
+    /// builder.add(b"hell", 1);
+    /// let build = builder.prebuild().unwrap();
+    ///
+    /// let trie = Fastrie::from_prebuilt(build.index_width, &build.values, &build.data).unwrap();
+    /// let entries = trie.iter().collect::<Vec<(Vec<u8>, &i32)>>();
+    /// assert_eq!(entries, vec![(b"hell".to_vec(), &1), (b"hello".to_vec(), &2), (b"world".to_vec(), &4)]);
+    /// ```
+    pub fn iter(&self) -> FastrieIter<'_, 'v, 'd, V, S, K> {
+        FastrieIter {
+            keys: self.keys(),
+            values: self.values.as_ref().unwrap(),
+            value_type: PhantomData,
+        }
+    }
+
+    /// Returns a cursor at the root, for walking the trie one char at a time. See [`FastrieCursor`].
+    pub fn cursor(&self) -> FastrieCursor<'_, 'v, 'd, V, S, K> {
+      FastrieCursor {
+          trie: self,
+          node_pos: ROOT_POS,
+          path_chars_pos: 0,
+          path_len: 0,
+          path_offset: 0,
+      }
+    }
+
+    /// Returns both the longest key that is a prefix of `text` and the value of `text` if it's a key, from one walk instead of calling both [`Fastrie::longest_matching_prefix`] and [`Fastrie::get`]. The text is a key exactly when the longest match covers all of it.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use fastrie::*;
+    ///
+    /// let mut builder = FastrieBuilderNode::new(IndexWidth(2));
+    /// builder.add(b"hell", 1);
+    /// builder.add(b"hello", 2);
+    /// let build = builder.prebuild().unwrap();
+    ///
+    /// let trie = Fastrie::from_prebuilt(build.index_width, &build.values, &build.data).unwrap();
+    /// let detail = trie.match_detail(b"hello");
+    /// assert_eq!(detail.longest.unwrap().end, 4);
+    /// assert_eq!(detail.exact, Some(&2));
+    /// let detail = trie.match_detail(b"hello!");
+    /// assert_eq!(detail.longest.unwrap().end, 4);
+    /// assert_eq!(detail.exact, None);
+    /// ```
+    pub fn match_detail(&self, text: &[K]) -> MatchDetail<'v, V> {
+        let longest = self.longest_matching_prefix(text);
+        let exact = longest.as_ref().filter(|m| m.len == text.len()).map(|m| m.value);
+        MatchDetail { longest, exact }
+    }
+
+    /// Returns the longest key that is a prefix of `text`. The empty key is a prefix of every text, so if it has a value and no longer key matches, it's returned as a match with a `len` of zero, including when `text` is empty.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use fastrie::*;
+    ///
+    /// let mut builder = FastrieBuilderNode::new(IndexWidth(2));
+    /// builder.add(b"", 0);
+    /// builder.add(b"hell", 1);
+    /// let build = builder.prebuild().unwrap();
+    ///
+    /// let trie = Fastrie::from_prebuilt(build.index_width, &build.values, &build.data).unwrap();
+    /// let mat = trie.longest_matching_prefix(b"hello").unwrap();
+    /// assert_eq!((mat.len, *mat.value), (4, 1));
+    /// let mat = trie.longest_matching_prefix(b"help").unwrap();
+    /// assert_eq!((mat.len, mat.range(), *mat.value), (0, 0..0, 0));
+    /// assert_eq!(trie.longest_matching_prefix(b"").unwrap().value, &0);
+    /// ```
+    pub fn longest_matching_prefix(&self, text: &[K]) -> Option<FastrieMatch<'v, V>> {
+      let mut cursor = self.cursor();
+      // The root's value is matched by every text, so it's the match unless a longer key matches.
+      let mut mat = self.root_match();
+      for (i, &c) in text.iter().enumerate() {
+          match cursor.step(c) {
+              StepResult::NoChild => break,
+              StepResult::Descended { terminal_value: Some(value) } => mat = Some(FastrieMatch {
+                  start: 0,
+                  end: i,
+                  len: i + 1,
+                  value,
+                  value_index: cursor.value_index().unwrap(),
+              }),
+              StepResult::Descended { terminal_value: None } => {}
+          };
+          if !cursor.has_children() {
+              // No longer key can match, so stop without looking at the rest of the text.
+              break;
+          };
+      };
+      mat
+    }
+
+    /// Like [`Fastrie::longest_matching_prefix`], but calls `f` with the inclusive end and value of the match instead of returning a [`FastrieMatch`], and returns its result. This avoids constructing the match for hot loops that only need some of it. The empty key has no inclusive end, so it's never matched; use [`Fastrie::longest_matching_prefix_len_resolve`] to include it.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use fastrie::*;
+    ///
+    /// let mut builder = FastrieBuilderNode::new(IndexWidth(2));
+    /// builder.add(b"hell", 1);
+    /// builder.add(b"hello", 2);
+    /// let build = builder.prebuild().unwrap();
+    ///
+    /// let trie = Fastrie::from_prebuilt(build.index_width, &build.values, &build.data).unwrap();
+    /// assert_eq!(trie.with_longest_matching_prefix(b"hello!", |end, value| end + *value), Some(6));
+    /// assert_eq!(trie.with_longest_matching_prefix(b"help", |end, value| end + *value), None);
+    /// ```
+    #[inline]
+    pub fn with_longest_matching_prefix<R>(&self, text: &[K], f: impl FnOnce(usize, &'v V) -> R) -> Option<R> {
+      self._longest_matching_prefix_end(text).and_then(|(end, value_idx)| Some(f(end, self.get_value(value_idx)?)))
+    }
+
+    /// Like [`Fastrie::longest_matching_prefix`], but returns the inclusive end of the match and `resolve` called with its zero-based value index, without looking at this trie's values. This allows values to be stored elsewhere, e.g. in a database, including for sets created with [`Fastrie::as_set`]. The empty key has no inclusive end, so it's never matched; use [`Fastrie::longest_matching_prefix_len_resolve`] to include it.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use fastrie::*;
+    ///
+    /// let mut builder = FastrieBuilderNode::new(IndexWidth(2));
+    /// builder.add(b"hell", ());
+    /// builder.add(b"hello", ());
+    /// let build = builder.prebuild().unwrap();
+    ///
+    /// let names = ["hell", "hello"];
+    /// let trie = Fastrie::from_prebuilt(build.index_width, &build.values, &build.data).unwrap().as_set();
+    /// assert_eq!(trie.longest_matching_prefix_resolve(b"hello!", |value_idx| names[value_idx]), Some((4, "hello")));
+    /// assert_eq!(trie.longest_matching_prefix_resolve(b"help", |value_idx| names[value_idx]), None);
+    /// ```
+    #[inline]
+    pub fn longest_matching_prefix_resolve<R>(&self, text: &[K], resolve: impl Fn(usize) -> R) -> Option<(usize, R)> {
+      self._longest_matching_prefix_end(text).map(|(end, value_idx)| (end, resolve(value_idx)))
+    }
+
+    /// Like [`Fastrie::longest_matching_prefix_resolve`], but returns the length of the match instead of its inclusive end, so the empty key is matched too, with a length of zero.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use fastrie::*;
+    ///
+    /// let mut builder = FastrieBuilderNode::new(IndexWidth(2));
+    /// builder.add(b"", ());
+    /// builder.add(b"hello", ());
+    /// let build = builder.prebuild().unwrap();
+    ///
+    /// let names = ["", "hello"];
+    /// let trie = Fastrie::from_prebuilt(build.index_width, &build.values, &build.data).unwrap().as_set();
+    /// assert_eq!(trie.longest_matching_prefix_len_resolve(b"hello!", |value_idx| names[value_idx]), Some((5, "hello")));
+    /// assert_eq!(trie.longest_matching_prefix_len_resolve(b"help", |value_idx| names[value_idx]), Some((0, "")));
+    /// assert_eq!(trie.longest_matching_prefix_resolve(b"help", |value_idx| names[value_idx]), None);
+    /// ```
+    #[inline]
+    pub fn longest_matching_prefix_len_resolve<R>(&self, text: &[K], resolve: impl Fn(usize) -> R) -> Option<(usize, R)> {
+      self._longest_matching_prefix(text).map(|(len, value_idx)| (len, resolve(value_idx)))
+    }
+
+    /// Returns the inclusive end of the longest key that is a prefix of `text`, without its value, or `None` if there is none. The empty key has no inclusive end, so it's never matched. Unlike [`Fastrie::longest_matching_prefix`], this works on tries without values, e.g. from [`from_prebuilt_without_values`], so sets can be used to segment text.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use fastrie::*;
+    ///
+    /// let mut builder = FastrieBuilderNode::new(IndexWidth(3));
+    /// builder.add(b"hell", 1);
+    /// builder.add(b"hello", 2);
+    /// builder.add(b"world", 4);
+    /// let build = builder.prebuild().unwrap();
+    ///
+    /// let trie = from_prebuilt_without_values(build.index_width, &build.data);
+    /// assert_eq!(trie.longest_matching_prefix_len(b"hello world!"), Some(4));
+    /// assert_eq!(trie.longest_matching_prefix_len(b"hell's kitchen"), Some(3));
+    /// assert_eq!(trie.longest_matching_prefix_len(b"worl"), None);
+    /// ```
+    #[inline]
+    pub fn longest_matching_prefix_len(&self, text: &[K]) -> Option<usize> {
+      self._longest_matching_prefix_end(text).map(|(end, _)| end)
+    }
+
+    /// Returns the length of the longest key that is a prefix of `text`, or `0` if there is none or only the empty key matches, so the rest of `text` is always `&text[len..]`. Like [`Fastrie::longest_matching_prefix_len`], this works on tries with or without values.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use fastrie::*;
+    ///
+    /// let mut builder = FastrieBuilderNode::new(IndexWidth(2));
+    /// builder.add(b"un", ());
+    /// builder.add(b"re", ());
+    /// let build = builder.prebuild().unwrap();
+    ///
+    /// let trie = from_prebuilt_without_values(build.index_width, &build.data);
+    /// let mut word: &[u8] = b"unrelated";
+    /// while trie.matched_prefix_len(word) > 0 {
+    ///     word = &word[trie.matched_prefix_len(word)..];
+    /// };
+    /// assert_eq!(word, b"lated");
+    /// ```
+    #[inline]
+    pub fn matched_prefix_len(&self, text: &[K]) -> usize {
+      self._longest_matching_prefix(text).map_or(0, |(len, _)| len)
+    }
+
+    /// Returns the value at the zero-based value index `value_idx`, or `None` if it's out of range or the trie has no values.
+    fn get_value(&self, value_idx: usize) -> Option<&'v V> {
+        self.values.as_ref().and_then(|values| values.get(value_idx))
+    }
+
+    /// Returns the match of the empty key, which has a length of zero, if it has an available value.
+    fn root_match(&self) -> Option<FastrieMatch<'v, V>> {
+        let value_idx = self.walker().node_value_idx(ROOT_POS)?;
+        Some(FastrieMatch::new(0, 0, self.get_value(value_idx)?, value_idx))
+    }
+
+    /// Returns the amount of values, given that `value_idx` is out of range of them. Stores only say whether an index is in range, so this searches for the first index that isn't, which is fine as it's only needed for errors.
+    fn values_len_below(&self, value_idx: usize) -> usize {
+        let (mut lo, mut hi) = (0, value_idx);
+        while lo < hi {
+            let mid = lo + (hi - lo) / 2;
+            if self.get_value(mid).is_some() {
+                lo = mid + 1;
+            } else {
+                hi = mid;
+            };
+        };
+        lo
+    }
+
+    /// Like [`Fastrie::longest_matching_prefix`], but returns an error instead of panicking if the matched key's value index is out of range of the values, which happens when the values are out of sync with the data (e.g. loaded from separate files), or if the walk reads past the end of the data, which happens when the data is truncated or corrupt.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use fastrie::*;
+    ///
+    /// let mut builder = FastrieBuilderNode::new(IndexWidth(2));
+    /// builder.add(b"hell", 1);
+    /// builder.add(b"hello", 2);
+    /// let build = builder.prebuild().unwrap();
+    ///
+    /// let trie = Fastrie::from_prebuilt(build.index_width, &build.values, &build.data).unwrap();
+    /// assert_eq!(trie.try_longest_matching_prefix(b"hello").unwrap().unwrap().value, &2);
+    /// assert!(trie.try_longest_matching_prefix(b"help").unwrap().is_none());
+    ///
+    /// // Values from a build with fewer keys.
+    /// let stale = Fastrie::from_prebuilt(build.index_width, &build.values[..1], &build.data).unwrap();
+    /// assert_eq!(stale.try_longest_matching_prefix(b"hell").unwrap().unwrap().value, &1);
+    /// assert_eq!(stale.try_longest_matching_prefix(b"hello").err(), Some(FastrieError::ValueIndexOutOfRange { idx: 1, len: 1 }));
+    ///
+    /// // Data missing its last node.
+    /// let truncated = Fastrie::from_prebuilt(build.index_width, &build.values, &build.data[..build.data.len() - 1]).unwrap();
+    /// assert_eq!(truncated.try_longest_matching_prefix(b"hell").unwrap().unwrap().value, &1);
+    /// assert!(matches!(truncated.try_longest_matching_prefix(b"hello"), Err(FastrieError::TruncatedData { .. })));
+    /// ```
+    pub fn try_longest_matching_prefix(&self, text: &[K]) -> Result<Option<FastrieMatch<'v, V>>, FastrieError> {
+      match self._try_longest_matching_prefix(text)? {
+          Some((len, value_idx)) => match self.get_value(value_idx) {
+              Some(value) => Ok(Some(FastrieMatch::new(0, len, value, value_idx))),
+              None => Err(FastrieError::ValueIndexOutOfRange { idx: value_idx, len: self.values_len_below(value_idx) }),
+          },
+          None => Ok(None),
+      }
+    }
+
+    /// Like `_longest_matching_prefix`, but returns an error instead of panicking if the walk reads past the end of the data. Positions are computed with checked arithmetic, as corrupt data can hold any offset, and one that overflows is past the end of the data too.
+    fn _try_longest_matching_prefix(&self, text: &[K]) -> Result<Option<(usize, usize)>, FastrieError> {
+        let idx_bytes = self.index_width.0;
+        let mut node_pos = ROOT_POS;
+        let mut match_opt = None;
+        let mut i = 0;
+        loop {
+            let truncated = FastrieError::TruncatedData { pos: node_pos };
+            let children_present = *node_pos.checked_add(idx_bytes).and_then(|pos| self.data.get(pos)).ok_or(truncated)?;
+            if children_present & NODE_HAS_VALUE != 0 {
+                let stored_value_idx = self.index_width.read_idx_checked(self.data, node_pos).ok_or(truncated)?;
+                if stored_value_idx != 0 {
+                    match_opt = Some((i, stored_value_idx - 1));
+                };
+            };
+            if children_present & NODE_CHILDREN_MASK == 0 || i == text.len() {
+                break;
+            };
+            if children_present & NODE_HAS_PATH != 0 {
+                let len = *node_pos.checked_add(idx_bytes + 1).and_then(|pos| self.data.get(pos)).ok_or(truncated)? as usize;
+                let chars_pos = node_pos.checked_add(idx_bytes + 2).ok_or(truncated)?;
+                let end_pos = chars_pos.checked_add(len * K::WIDTH).filter(|&pos| pos <= self.data.len()).ok_or(truncated)?;
+                for j in 0..len {
+                    if i + j == text.len() || read_symbol::<K>(self.data, chars_pos + j * K::WIDTH) != text[i + j] {
+                        return Ok(match_opt);
+                    };
+                };
+                i += len;
+                node_pos = end_pos;
+            } else {
+                node_pos = match self.try_child(node_pos, children_present, text[i])? {
+                    Some(child_pos) => child_pos,
+                    None => break,
+                };
+                i += 1;
+            };
+        };
+        Ok(match_opt)
+    }
+
+    /// Like `child_in_clusters`, but returns an error instead of panicking if the node's children extend past the end of the data. Like `_try_longest_matching_prefix`, positions are computed with checked arithmetic.
+    fn try_child(&self, node_pos: usize, children_present: u8, c: K) -> Result<Option<usize>, FastrieError> {
+        let idx_bytes = self.index_width.0;
+        let child_width = IndexWidth::of_children_present(children_present);
+        let read_child_pos = |slot_pos: Option<usize>, pos: usize| slot_pos
+            .and_then(|slot_pos| child_width.read_idx_checked(self.data, slot_pos))
+            .and_then(|child_idx| node_pos.checked_add(child_idx))
+            .ok_or(FastrieError::TruncatedData { pos });
+        if children_present & NODE_HAS_SINGLE_CHILD != 0 {
+            let truncated = FastrieError::TruncatedData { pos: node_pos };
+            let c_pos = node_pos.checked_add(idx_bytes + 1).ok_or(truncated)?;
+            let slot_pos = c_pos.checked_add(K::WIDTH).filter(|&pos| pos <= self.data.len()).ok_or(truncated)?;
+            if read_symbol::<K>(self.data, c_pos) != c {
+                return Ok(None);
+            };
+            return read_child_pos(Some(slot_pos), node_pos).map(Some);
+        };
+        let mut cluster_pos = node_pos.checked_add(idx_bytes + 1).ok_or(FastrieError::TruncatedData { pos: node_pos })?;
+        loop {
+            let bitmap_pos = cluster_pos.checked_add(1 + 2 * K::WIDTH).filter(|&pos| pos <= self.data.len()).ok_or(FastrieError::TruncatedData { pos: cluster_pos })?;
+            let next_cluster_dist = self.data[cluster_pos] as usize;
+            let cluster_min: K = read_symbol(self.data, cluster_pos + 1);
+            let cluster_max: K = read_symbol(self.data, cluster_pos + 1 + K::WIDTH);
+            if c >= cluster_min && c <= cluster_max {
+                if cluster_min == cluster_max {
+                    return read_child_pos(Some(bitmap_pos), cluster_pos).map(Some);
+                };
+                let bitmap_len = cluster_bitmap_len(cluster_min, cluster_max);
+                let slots_pos = bitmap_pos.checked_add(bitmap_len).filter(|&pos| pos <= self.data.len()).ok_or(FastrieError::TruncatedData { pos: cluster_pos })?;
+                let bitmap = &self.data[bitmap_pos..slots_pos];
+                let bit = c.to_usize() - cluster_min.to_usize();
+                if bitmap[bit / 8] & (1 << (bit % 8)) == 0 {
+                    return Ok(None);
+                };
+                let preceding = bitmap[..bit / 8].iter().map(|b| b.count_ones()).sum::<u32>()
+                    + (bitmap[bit / 8] & ((1 << (bit % 8)) - 1)).count_ones();
+                return read_child_pos(slots_pos.checked_add(preceding as usize * child_width.0), cluster_pos).map(Some);
+            };
+            if next_cluster_dist == 0 {
+                return Ok(None);
+            };
+            cluster_pos = cluster_pos.checked_add(next_cluster_dist).ok_or(FastrieError::TruncatedData { pos: cluster_pos })?;
+        };
+    }
+
+    /// Returns the value of `key` exactly, i.e. the value at the node reached by walking all of `key`. Keys that are prefixes of longer keys keep their own value. Returns `None` if the key's value isn't available, i.e. the trie has no values or its value index is out of range of them; use [`Fastrie::contains_key`] to check for the key itself.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use fastrie::*;
+    ///
+    /// let mut builder = FastrieBuilderNode::new(IndexWidth(2));
+    /// builder.add(b"ab", 1);
+    /// builder.add(b"abc", 2);
+    /// let build = builder.prebuild().unwrap();
+    ///
+    /// let trie = Fastrie::from_prebuilt(build.index_width, &build.values, &build.data).unwrap();
+    /// assert_eq!(trie.get(b"ab"), Some(&1));
+    /// assert_eq!(trie.get(b"abc"), Some(&2));
+    /// // Nodes without a value have no value even though they have children.
+    /// assert_eq!(trie.get(b"a"), None);
+    /// assert_eq!(trie.get(b"abcd"), None);
+    /// ```
+    pub fn get(&self, key: &[K]) -> Option<&'v V> {
+        let value_idx = self.walker().exact_value_idx(key)?;
+        self.get_value(value_idx)
+    }
+
+    /// Returns the cost of looking up `key` with [`Fastrie::get`], or `None` if it isn't a key. Unlike [`Fastrie::longest_matching_prefix_profiled`], this is always available, and also counts gaps, so it can find keys whose lookups are slow because of how the clusters along them are laid out.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use fastrie::*;
+    ///
+    /// let mut builder = FastrieBuilderNode::new(IndexWidth(2));
+    /// // The root has a cluster of a..=d, which is larger so it's checked first, then a cluster of x and z with a gap for y.
+    /// for key in ["a", "b", "c", "d", "x", "z"].iter() {
+    ///     builder.add(key.as_bytes(), ());
+    /// };
+    /// let build = builder.prebuild().unwrap();
+    ///
+    /// let trie = from_prebuilt_without_values(build.index_width, &build.data);
+    /// assert_eq!(trie.path_cost(b"a"), Some(PathCost { clusters_examined: 1, gaps_skipped: 0, nodes_visited: 2 }));
+    /// assert_eq!(trie.path_cost(b"z"), Some(PathCost { clusters_examined: 2, gaps_skipped: 1, nodes_visited: 2 }));
+    /// assert_eq!(trie.path_cost(b"y"), None);
+    /// ```
+    pub fn path_cost(&self, key: &[K]) -> Option<PathCost> {
+        let mut cost = PathCost::default();
+        self.walker().exact_value_idx_counted(key, &mut cost)?;
+        Some(cost)
+    }
+
+    /// Like [`Fastrie::longest_matching_prefix`], but matches keys starting at `start` in `text`. The match's `start` and `end` are indices into `text`.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use fastrie::*;
+    ///
+    /// let mut builder = FastrieBuilderNode::new(IndexWidth(2));
+    /// builder.add(b"hell", 1);
+    /// let build = builder.prebuild().unwrap();
+    ///
+    /// let trie = Fastrie::from_prebuilt(build.index_width, &build.values, &build.data).unwrap();
+    /// let mat = trie.longest_matching_prefix_at(b"oh hello", 3).unwrap();
+    /// assert_eq!(mat.range(), 3..7);
+    /// assert!(trie.longest_matching_prefix_at(b"oh hello", 2).is_none());
+    /// ```
+    pub fn longest_matching_prefix_at(&self, text: &[K], start: usize) -> Option<FastrieMatch<'v, V>> {
+      self._longest_matching_prefix(&text[start..]).and_then(|(len, value_idx)| Some(FastrieMatch::new(start, len, self.get_value(value_idx)?, value_idx)))
+    }
+
+    /// Like [`Fastrie::longest_matching_prefix`], but matches against `first` followed by `second` as if they were one slice, without concatenating them, e.g. for input split across buffers. The match's `end` is an index into the combined sequence.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use fastrie::*;
+    ///
+    /// let mut builder = FastrieBuilderNode::new(IndexWidth(2));
+    /// builder.add(b"hell", 1);
+    /// builder.add(b"hello", 2);
+    /// let build = builder.prebuild().unwrap();
+    ///
+    /// let trie = Fastrie::from_prebuilt(build.index_width, &build.values, &build.data).unwrap();
+    /// let mat = trie.longest_matching_prefix_chained(b"he", b"llo!").unwrap();
+    /// assert_eq!((mat.end, *mat.value), (4, 2));
+    /// assert_eq!(trie.longest_matching_prefix_chained(b"hell", b"").unwrap().value, &1);
+    /// ```
+    pub fn longest_matching_prefix_chained(&self, first: &[K], second: &[K]) -> Option<FastrieMatch<'v, V>> {
+        let mut cursor = self.cursor();
+        let mut mat = self.root_match();
+        for (i, &c) in first.iter().chain(second).enumerate() {
+            match cursor.step(c) {
+                StepResult::NoChild => break,
+                StepResult::Descended { terminal_value: Some(value) } => mat = Some(FastrieMatch {
+                    start: 0,
+                    end: i,
+                    len: i + 1,
+                    value,
+                    value_index: cursor.value_index().unwrap(),
+                }),
+                StepResult::Descended { terminal_value: None } => {}
+            };
+            if !cursor.has_children() {
+                break;
+            };
+        };
+        mat
+    }
+
+    /// Like [`Fastrie::longest_matching_prefix`], but first skips leading chars of `text` for which `skip` returns true, e.g. whitespace. The match's `start` and `end` are indices into `text`.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use fastrie::*;
+    ///
+    /// let mut builder = FastrieBuilderNode::new(IndexWidth(2));
+    /// builder.add(b"hello", 1);
+    /// builder.add(b" x", 2);
+    /// let build = builder.prebuild().unwrap();
+    ///
+    /// let trie = Fastrie::from_prebuilt(build.index_width, &build.values, &build.data).unwrap();
+    /// let mat = trie.longest_matching_prefix_skip(b"   hello", |c| c == b' ').unwrap();
+    /// assert_eq!((mat.start, mat.end, *mat.value), (3, 7, 1));
+    /// // Skipped chars can't be part of a match.
+    /// assert!(trie.longest_matching_prefix_skip(b" x", |c| c == b' ').is_none());
+    /// assert!(trie.longest_matching_prefix_skip(b"   ", |c| c == b' ').is_none());
+    /// ```
+    pub fn longest_matching_prefix_skip(&self, text: &[K], skip: impl Fn(K) -> bool) -> Option<FastrieMatch<'v, V>> {
+      let start = text.iter().position(|&c| !skip(c)).unwrap_or(text.len());
+      self.longest_matching_prefix_at(text, start)
+    }
+
+    /// Like [`Fastrie::longest_matching_prefix`], but tolerates up to `max_deletions` chars deleted from the key or from `text`, i.e. a char of `text` can be skipped, or a char of the key can be missing from `text`. The longest match in `text` is returned, preferring fewer deletions and then the key that comes first. The match's `end` is the index of the last char of `text` matched.
+    ///
+    /// The search visits every way of placing the deletions, so its cost grows exponentially with `max_deletions`, and it's intended for small budgets such as 1 or 2.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use fastrie::*;
+    ///
+    /// let mut builder = FastrieBuilderNode::new(IndexWidth(2));
+    /// builder.add(b"hello", 1);
+    /// builder.add(b"help", 2);
+    /// let build = builder.prebuild().unwrap();
+    ///
+    /// let trie = Fastrie::from_prebuilt(build.index_width, &build.values, &build.data).unwrap();
+    /// assert!(trie.longest_matching_prefix_fuzzy(b"helo", 0).is_none());
+    /// // A char missing from the text.
+    /// let mat = trie.longest_matching_prefix_fuzzy(b"helo", 1).unwrap();
+    /// assert_eq!((mat.end, *mat.value), (3, 1));
+    /// // An extra char in the text.
+    /// let mat = trie.longest_matching_prefix_fuzzy(b"hexlp!", 1).unwrap();
+    /// assert_eq!((mat.end, *mat.value), (4, 2));
+    /// assert!(trie.longest_matching_prefix_fuzzy(b"hexxlp", 1).is_none());
+    /// ```
+    pub fn longest_matching_prefix_fuzzy(&self, text: &[K], max_deletions: usize) -> Option<FastrieMatch<'v, V>> {
+      // Best match as its inclusive end, deletions, and value index.
+      let mut best: Option<(usize, usize, usize)> = None;
+      let mut record = |pos: TriePos, consumed: usize, deletions: usize| {
+          if consumed == 0 {
+              return;
+          };
+          if let Some(value_idx) = self.pos_value_idx(pos) {
+              let end = consumed - 1;
+              let better = match best {
+                  None => true,
+                  Some((best_end, best_deletions, best_value_idx)) => (end, Reverse(deletions), Reverse(value_idx)) > (best_end, Reverse(best_deletions), Reverse(best_value_idx)),
+              };
+              if better {
+                  best = Some((end, deletions, value_idx));
+              };
+          };
+      };
+      // Positions still to walk from, with the amount of text consumed and deletions used to get there.
+      let mut stack: Vec<(TriePos, usize, usize)> = vec![(TriePos::ROOT, 0, 0)];
+      while let Some((pos, consumed, deletions)) = stack.pop() {
+          if !self.pos_has_children(pos) {
+              continue;
+          };
+          if consumed < text.len() {
+              if let Some(next) = self.step(pos, text[consumed]) {
+                  record(next, consumed + 1, deletions);
+                  stack.push((next, consumed + 1, deletions));
+              };
+          };
+          if deletions < max_deletions {
+              // Skipping the last char of text can't extend a match.
+              if consumed + 1 < text.len() {
+                  stack.push((pos, consumed + 1, deletions + 1));
+              };
+              for (_, child) in self.children(pos) {
+                  record(child, consumed, deletions + 1);
+                  stack.push((child, consumed, deletions + 1));
+              };
+          };
+      };
+      // The empty key needs no text or deletions, so it's only the match if no other key matches.
+      best.and_then(|(end, _, value_idx)| Some(FastrieMatch::new(0, end + 1, self.get_value(value_idx)?, value_idx))).or_else(|| self.root_match())
+    }
+
+    /// Like [`Fastrie::longest_matching_prefix`], but `wildcard` in a key matches any one char of `text`, like `?` in a glob. When several keys match, the one using the fewest wildcards wins, then the longest, then the key that comes first, so literal keys take precedence over patterns covering them. The empty key only matches if no other key does.
+    ///
+    /// Each char of `text` can be matched literally and by a wildcard, so the search can branch at every char; it's intended for key sets where wildcards are rare.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use fastrie::*;
+    ///
+    /// let mut builder = FastrieBuilderNode::new(IndexWidth(2));
+    /// builder.add(b"h?llo", 1);
+    /// builder.add(b"he", 2);
+    /// let build = builder.prebuild().unwrap();
+    ///
+    /// let trie = Fastrie::from_prebuilt(build.index_width, &build.values, &build.data).unwrap();
+    /// assert_eq!(trie.longest_matching_prefix_wildcard(b"hallo", b'?').unwrap().value, &1);
+    /// // Fewer wildcards beat a longer match.
+    /// let mat = trie.longest_matching_prefix_wildcard(b"hello", b'?').unwrap();
+    /// assert_eq!((mat.end, *mat.value), (1, 2));
+    /// ```
+    pub fn longest_matching_prefix_wildcard(&self, text: &[K], wildcard: K) -> Option<FastrieMatch<'v, V>> {
+      // Best match as its wildcards, inclusive end, and value index.
+      let mut best: Option<(usize, usize, usize)> = None;
+      // Positions still to walk from, with the amount of text consumed and wildcards used to get there.
+      let mut stack: Vec<(TriePos, usize, usize)> = vec![(TriePos::ROOT, 0, 0)];
+      while let Some((pos, consumed, wildcards)) = stack.pop() {
+          if consumed == text.len() || !self.pos_has_children(pos) {
+              continue;
+          };
+          let c = text[consumed];
+          let literal = self.step(pos, c).map(|next| (next, wildcards));
+          let wild = if c == wildcard { None } else { self.step(pos, wildcard).map(|next| (next, wildcards + 1)) };
+          for (next, wildcards) in literal.into_iter().chain(wild) {
+              if let Some(value_idx) = self.pos_value_idx(next) {
+                  let better = match best {
+                      None => true,
+                      Some((best_wildcards, best_end, best_value_idx)) => (Reverse(wildcards), consumed, Reverse(value_idx)) > (Reverse(best_wildcards), best_end, Reverse(best_value_idx)),
+                  };
+                  if better {
+                      best = Some((wildcards, consumed, value_idx));
+                  };
+              };
+              stack.push((next, consumed + 1, wildcards));
+          };
+      };
+      best.and_then(|(_, end, value_idx)| Some(FastrieMatch::new(0, end + 1, self.get_value(value_idx)?, value_idx))).or_else(|| self.root_match())
+    }
+
+    /// Scans `text` for non-overlapping occurrences of keys, with the same leftmost-longest semantics as `aho-corasick`'s `MatchKind::LeftmostLongest`: at each position, the longest key starting there is reported and scanning continues after it; if no key starts there, scanning continues at the next position. The empty key is never reported.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use fastrie::*;
+    ///
+    /// let mut builder = FastrieBuilderNode::new(IndexWidth(2));
+    /// builder.add(b"he", 1);
+    /// builder.add(b"hello", 2);
+    /// builder.add(b"lo", 3);
+    /// builder.add(b"low", 4);
+    /// let build = builder.prebuild().unwrap();
+    ///
+    /// let trie = Fastrie::from_prebuilt(build.index_width, &build.values, &build.data).unwrap();
+    /// let text = b"hello helo lowly";
+    /// let matches = trie.scan(text).map(|m| (m.matched(text), *m.value)).collect::<Vec<_>>();
+    /// // "hello" is preferred over "he" at 0, and "lo" within "hello" isn't reported as it overlaps.
+    /// // "lo" in "helo" doesn't overlap "he", and "low" is preferred over "lo".
+    /// assert_eq!(matches, vec![(&b"hello"[..], 2), (&b"he"[..], 1), (&b"lo"[..], 3), (&b"low"[..], 4)]);
+    /// ```
+    pub fn scan<'t>(&'t self, text: &'t [K]) -> FastrieScan<'t, 'v, 'd, V, S, K> {
+      FastrieScan {
+          trie: self,
+          text,
+          pos: 0,
+      }
+    }
+
+    /// Returns every match of a key starting at `start` in `text`, from shortest to longest, i.e. each valued node reached while walking `text[start..]`. Matches are reported with indices into `text`. Calling this for every `start` extracts all occurrences of keys in `text`, n-gram style, without re-slicing. Keys without an available value are skipped, so tries without values, e.g. from [`Fastrie::as_set`], have no matches.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use fastrie::*;
+    ///
+    /// let mut builder = FastrieBuilderNode::new(IndexWidth(2));
+    /// builder.add(b"hell", 1);
+    /// builder.add(b"hello", 2);
+    /// builder.add(b"low", 3);
+    /// let build = builder.prebuild().unwrap();
+    ///
+    /// let trie = Fastrie::from_prebuilt(build.index_width, &build.values, &build.data).unwrap();
+    /// let text = b"hellow";
+    /// let matches = trie.matches_from(text, 0).map(|m| (m.matched(text), *m.value)).collect::<Vec<_>>();
+    /// assert_eq!(matches, vec![(&b"hell"[..], 1), (&b"hello"[..], 2)]);
+    /// let matches = trie.matches_from(text, 3).map(|m| (m.range(), *m.value)).collect::<Vec<_>>();
+    /// assert_eq!(matches, vec![(3..6, 3)]);
+    /// assert_eq!(trie.matches_from(text, 1).count(), 0);
+    /// assert_eq!(trie.matches_from(text, 6).count(), 0);
+    /// ```
+    pub fn matches_from<'a>(&'a self, text: &'a [K], start: usize) -> impl Iterator<Item = FastrieMatch<'a, V>> + 'a {
+      let mut trie_pos: Option<TriePos> = Some(TriePos::ROOT);
+      let mut i = start;
+      std::iter::from_fn(move || {
+          while let Some(pos) = trie_pos {
+              let &c = text.get(i)?;
+              trie_pos = self.step(pos, c);
+              i += 1;
+              let value_idx = trie_pos.and_then(|child_pos| self.pos_value_idx(child_pos));
+              if let Some((value_idx, value)) = value_idx.and_then(|value_idx| Some((value_idx, self.values.as_ref()?.get(value_idx)?))) {
+                  return Some(FastrieMatch {
+                      start,
+                      end: i - 1,
+                      len: i - start,
+                      value,
+                      value_index: value_idx,
+                  });
+              };
+          };
+          None
+      })
+    }
+
+    /// Calls `f` with the inclusive start and end and the value of each occurrence of a key in `text`, in order of start then end, without allocating. If `overlapping`, every key starting at every position is reported, like calling [`Fastrie::matches_from`] for each position; otherwise, occurrences are leftmost-longest and non-overlapping like [`Fastrie::scan`].
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use fastrie::*;
+    ///
+    /// let mut builder = FastrieBuilderNode::new(IndexWidth(2));
+    /// builder.add(b"aa", 1);
+    /// let build = builder.prebuild().unwrap();
+    ///
+    /// let trie = Fastrie::from_prebuilt(build.index_width, &build.values, &build.data).unwrap();
+    /// let mut matches = Vec::new();
+    /// trie.find_all_with(b"aaa", true, |start, end, value| matches.push((start, end, *value)));
+    /// assert_eq!(matches, vec![(0, 1, 1), (1, 2, 1)]);
+    ///
+    /// let mut matches = Vec::new();
+    /// trie.find_all_with(b"aaa", false, |start, end, value| matches.push((start, end, *value)));
+    /// assert_eq!(matches, vec![(0, 1, 1)]);
+    /// ```
+    pub fn find_all_with(&self, text: &[K], overlapping: bool, mut f: impl FnMut(usize, usize, &V)) {
+      if overlapping {
+          for start in 0..text.len() {
+              for mat in self.matches_from(text, start) {
+                  f(mat.start, mat.end, mat.value);
+              };
+          };
+      } else {
+          for mat in self.scan(text) {
+              f(mat.start, mat.end, mat.value);
+          };
+      };
+    }
+
+    /// Like [`Fastrie::longest_matching_prefix`], but also returns how far the walk went along the trie, i.e. the length of the longest prefix of `text` that is a prefix of some key. This can extend past the end of the match, e.g. for error recovery from text that starts like a key but doesn't end like one.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use fastrie::*;
+    ///
+    /// let mut builder = FastrieBuilderNode::new(IndexWidth(2));
+    /// builder.add(b"hello", 1);
+    /// let build = builder.prebuild().unwrap();
+    ///
+    /// let trie = Fastrie::from_prebuilt(build.index_width, &build.values, &build.data).unwrap();
+    /// let (mat, walked) = trie.longest_matching_prefix_with_walk(b"hellish");
+    /// assert!(mat.is_none());
+    /// // "hell" is a path in the trie, but 'i' doesn't follow it.
+    /// assert_eq!(walked, 4);
+    /// let (mat, walked) = trie.longest_matching_prefix_with_walk(b"hello!");
+    /// assert_eq!(mat.unwrap().end, 4);
+    /// assert_eq!(walked, 5);
+    /// assert_eq!(trie.longest_matching_prefix_with_walk(b"world").1, 0);
+    /// ```
+    pub fn longest_matching_prefix_with_walk(&self, text: &[K]) -> (Option<FastrieMatch<'v, V>>, usize) {
+      let (match_opt, walked) = self._longest_matching_prefix_counted(text, &mut ());
+      let mat = match_opt.and_then(|(len, value_idx)| Some(FastrieMatch::new(0, len, self.get_value(value_idx)?, value_idx)));
+      (mat, walked)
+    }
+
+    /// Continues a longest prefix match walk from `pos` over `text`, the next fragment of the query. Once every fragment has been passed, [`Fastrie::finish`] returns the same match as calling [`Fastrie::longest_matching_prefix`] on the whole query.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use fastrie::*;
+    ///
+    /// let mut builder = FastrieBuilderNode::new(IndexWidth(2));
+    /// builder.add(b"hell", 1);
+    /// builder.add(b"hello", 2);
+    /// let build = builder.prebuild().unwrap();
+    ///
+    /// let trie = Fastrie::from_prebuilt(build.index_width, &build.values, &build.data).unwrap();
+    /// let query = b"hello world";
+    /// for split in 0..=query.len() {
+    ///     let pos = trie.advance(FastriePosition::default(), &query[..split]);
+    ///     let pos = trie.advance(pos, &query[split..]);
+    ///     let mat = trie.finish(pos).unwrap();
+    ///     assert_eq!(mat.end, 4);
+    ///     assert_eq!(mat.value, &2);
+    /// };
+    /// assert!(trie.finish(trie.advance(FastriePosition::default(), b"hel")).is_none());
+    /// ```
+    pub fn advance(&self, mut pos: FastriePosition, text: &[K]) -> FastriePosition {
+      for &c in text {
+          let trie_pos = match pos.trie_pos {
+              Some(trie_pos) => trie_pos,
+              None => break,
+          };
+          pos.trie_pos = self.step(trie_pos, c);
+          if let Some(value_idx) = pos.trie_pos.and_then(|child_pos| self.pos_value_idx(child_pos)) {
+              pos.best = Some((pos.consumed + 1, value_idx));
+          };
+          pos.consumed += 1;
+      };
+      pos
+    }
+
+    /// Returns the longest match of a walk continued by [`Fastrie::advance`]. The match's `end` is an index into the concatenated fragments.
+    pub fn finish(&self, pos: FastriePosition) -> Option<FastrieMatch<'v, V>> {
+      match pos.best {
+          Some((len, value_idx)) => Some(FastrieMatch::new(0, len, self.get_value(value_idx)?, value_idx)),
+          // The empty key is a prefix of every text, so it's the match unless a longer key matched.
+          None => self.root_match(),
+      }
+    }
+
+    /// Combines [`Fastrie::advance`] and [`Fastrie::finish`]: continues the walk from `from`, or from the root if `None`, over `text`, and returns the longest match so far along with the position to continue from. This allows matching segments one at a time, e.g. of a path in a router, without walking the segments already matched again. The match's `end` is an index into the concatenated segments.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use fastrie::*;
+    ///
+    /// let mut builder = FastrieBuilderNode::new(IndexWidth(2));
+    /// builder.add(b"/a", 1);
+    /// builder.add(b"/a/b", 2);
+    /// let build = builder.prebuild().unwrap();
+    ///
+    /// let trie = Fastrie::from_prebuilt(build.index_width, &build.values, &build.data).unwrap();
+    /// let (mat, pos) = trie.longest_matching_prefix_resumable(b"/a", None);
+    /// assert_eq!(mat.unwrap().value, &1);
+    /// let (mat, _) = trie.longest_matching_prefix_resumable(b"/b", Some(pos));
+    /// let mat = mat.unwrap();
+    /// assert_eq!((mat.end, *mat.value), (3, 2));
+    /// // The match so far is kept once the walk falls off the trie.
+    /// let (mat, _) = trie.longest_matching_prefix_resumable(b"/c", Some(pos));
+    /// assert_eq!(mat.unwrap().value, &1);
+    /// ```
+    pub fn longest_matching_prefix_resumable(&self, text: &[K], from: Option<FastriePosition>) -> (Option<FastrieMatch<'v, V>>, FastriePosition) {
+      let pos = self.advance(from.unwrap_or_default(), text);
+      (self.finish(pos), pos)
+    }
+
+    /// Like [`Fastrie::longest_matching_prefix`], but returns `tag` alongside the match. This is useful when results are processed out of order, e.g. in batch or async pipelines.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use fastrie::*;
+    ///
+    /// let mut builder = FastrieBuilderNode::new(IndexWidth(2));
+    /// builder.add(b"hell", 1);
+    /// let build = builder.prebuild().unwrap();
+    ///
+    /// let trie = Fastrie::from_prebuilt(build.index_width, &build.values, &build.data).unwrap();
+    /// let (mat, request_id) = trie.longest_matching_prefix_tagged(b"hello", 42u64).unwrap();
+    /// assert_eq!(mat.value, &1);
+    /// assert_eq!(request_id, 42);
+    /// assert!(trie.longest_matching_prefix_tagged(b"world", 43u64).is_none());
+    /// ```
+    pub fn longest_matching_prefix_tagged<T>(&self, text: &[K], tag: T) -> Option<(FastrieMatch<'v, V>, T)> {
+      self.longest_matching_prefix(text).map(|mat| (mat, tag))
+    }
+
+    /// Like [`Fastrie::longest_matching_prefix`], but also counts the work done by the walk. Only available with the `profiling` feature, so that normal builds don't pay for the counters.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use fastrie::*;
+    ///
+    /// let mut builder = FastrieBuilderNode::new(IndexWidth(2));
+    /// builder.add(b"hell", 1);
+    /// builder.add(b"hello", 2);
+    /// builder.add(b"world", 4);
+    /// let build = builder.prebuild().unwrap();
+    ///
+    /// let trie = Fastrie::from_prebuilt(build.index_width, &build.values, &build.data).unwrap();
+    /// let (mat, stats) = trie.longest_matching_prefix_profiled(b"hello!");
+    /// assert_eq!(mat.unwrap().value, &2);
+    /// // The path "ell" from 'h' to "hell" is matched as a block, without visiting the nodes along it.
+    /// assert_eq!(stats.nodes_visited, 4);
+    /// // The root has two clusters ('h' and 'w' are too far apart), but the one containing 'h' is first. The path and the single child 'o' of "hell" count as one each.
+    /// assert_eq!(stats.clusters_examined, 3);
+    /// ```
+    #[cfg(feature = "profiling")]
+    pub fn longest_matching_prefix_profiled(&self, text: &[K]) -> (Option<FastrieMatch<'v, V>>, QueryStats) {
+      let mut stats = QueryStats::default();
+      let mat = self._longest_matching_prefix_counted(text, &mut stats).0.and_then(|(len, value_idx)| Some(FastrieMatch::new(0, len, self.get_value(value_idx)?, value_idx)));
+      (mat, stats)
+    }
+}
+
+impl<'v, 'd, V: ?Sized, S, K: Symbol> Fastrie<'v, 'd, V, S, K> {
+    pub fn memory_size(&self) -> usize {
+        self.data.len()
+    }
+
+    /// Replaces the values of this trie (if any) with `values`, keeping the same data. This allows one data blob to be shared by consumers that each bind their own values, or a set trie to gain values later.
+    ///
+    /// `values` must be ordered like the `values` of the [`FastrieBuild`] that produced the data, as the data refers to values by their index in it. Any slice of that length ordered the same way (e.g. a parallel array of different data per key) works.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use fastrie::*;
+    ///
+    /// let mut builder = FastrieBuilderNode::new(IndexWidth(2));
+    /// builder.add(b"hell", "hell");
+    /// builder.add(b"hello", "hello");
+    /// let build = builder.prebuild().unwrap();
+    ///
+    /// let set = from_prebuilt_without_values(build.index_width, &build.data);
+    /// let lens = build.values.iter().map(|v| v.len()).collect::<Vec<usize>>();
+    /// let trie = set.with_values(&lens);
+    /// assert_eq!(trie.longest_matching_prefix(b"hello!").unwrap().value, &5);
+    /// assert_eq!(trie.longest_matching_prefix(b"hell!").unwrap().value, &4);
+    /// ```
+    pub fn with_values<'w, W>(self, values: &'w [W]) -> Fastrie<'w, 'd, W, &'w [W], K> {
+        Fastrie {
+            data: self.data,
+            index_width: self.index_width,
+            values: Some(values),
+            value_type: PhantomData,
+            symbol_type: PhantomData,
+        }
+    }
+
+    /// Returns a view of the same data without values, i.e. a set of the keys, so that code only needing membership doesn't depend on the value types.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use fastrie::*;
+    ///
+    /// fn has_greeting(set: Fastrie<()>) -> bool {
+    ///     set.contains_key(b"hello")
+    /// }
+    ///
+    /// let mut builder = FastrieBuilderNode::new(IndexWidth(2));
+    /// builder.add(b"hell", 1);
+    /// builder.add(b"hello", 2);
+    /// let build = builder.prebuild().unwrap();
+    ///
+    /// let trie = Fastrie::from_prebuilt(build.index_width, &build.values, &build.data).unwrap();
+    /// assert!(has_greeting(trie.as_set()));
+    /// for key in [&b"hell"[..], b"hello", b"hel", b"help", b""].iter() {
+    ///     assert_eq!(trie.as_set().contains_key(key), trie.contains_key(key));
+    ///     assert_eq!(trie.as_set().contains_prefix(key), trie.contains_prefix(key));
+    /// };
+    /// ```
+    pub fn as_set(&self) -> Fastrie<'d, 'd, (), &'d [()], K> {
+        Fastrie {
+            data: self.data,
+            index_width: self.index_width,
+            values: None,
+            value_type: PhantomData,
+            symbol_type: PhantomData,
+        }
+    }
+
+    fn walker(&self) -> Walker<'d, [u8], K> {
+        Walker {
+            data: self.data,
+            index_width: self.index_width,
+            symbol_type: PhantomData,
+        }
+    }
+
+    /// Returns the zero-based value index of the node at `node_pos`, or `None` if it has no value.
+    fn node_value_idx(&self, node_pos: usize) -> Option<usize> {
+        self.walker().node_value_idx(node_pos)
+    }
+
+    fn has_children(&self, node_pos: usize) -> bool {
+        self.walker().has_children(node_pos)
+    }
+
+    fn has_single_child(&self, node_pos: usize) -> bool {
+        self.walker().has_single_child(node_pos)
+    }
+
+    fn has_path(&self, node_pos: usize) -> bool {
+        self.walker().has_path(node_pos)
+    }
+
+    /// Returns the position of the first char and the length of the path of the node at `node_pos`, which must have a path.
+    fn path(&self, node_pos: usize) -> (usize, usize) {
+        self.walker().path(node_pos)
+    }
+
+    fn pos_value_idx(&self, pos: TriePos) -> Option<usize> {
+        if pos.path_offset != 0 {
+            // Nodes along a path never have values.
+            return None;
+        };
+        self.node_value_idx(pos.node_pos)
+    }
+
+    fn pos_has_children(&self, pos: TriePos) -> bool {
+        pos.path_offset != 0 || self.has_children(pos.node_pos)
+    }
+
+    /// Returns the position after walking `c` from `pos`, if it's a child.
+    fn step(&self, pos: TriePos, c: K) -> Option<TriePos> {
+        if pos.path_offset == 0 && !self.has_path(pos.node_pos) {
+            return self.child(pos.node_pos, c, &mut ()).map(TriePos::node);
+        };
+        let (chars_pos, len) = self.path(pos.node_pos);
+        if read_symbol::<K>(self.data, chars_pos + pos.path_offset * K::WIDTH) != c {
+            return None;
+        };
+        Some(if pos.path_offset + 1 == len {
+            // The node at the end of the path immediately follows it.
+            TriePos::node(chars_pos + len * K::WIDTH)
+        } else {
+            TriePos { node_pos: pos.node_pos, path_offset: pos.path_offset + 1 }
+        })
+    }
+
+    /// Returns the position reached by walking all of `key`, or `None` if the walk falls off the trie.
+    fn walk_to(&self, key: &[K]) -> Option<TriePos> {
+        let mut pos = TriePos::ROOT;
+        for &c in key {
+            pos = self.step(pos, c)?;
+        };
+        Some(pos)
+    }
+
+    /// Returns the children of `pos` as `(char, child_pos)` pairs, sorted by char.
+    fn children(&self, pos: TriePos) -> Vec<(K, TriePos)> {
+        let idx_bytes = self.index_width.0;
+        let node_pos = pos.node_pos;
+        let mut children = Vec::new();
+        if !self.pos_has_children(pos) {
+            return children;
+        };
+        if pos.path_offset != 0 || self.has_path(node_pos) {
+            let (chars_pos, _) = self.path(node_pos);
+            let c = read_symbol(self.data, chars_pos + pos.path_offset * K::WIDTH);
+            children.push((c, self.step(pos, c).unwrap()));
+            return children;
+        };
+        let child_width = IndexWidth::of_children_present(self.data[node_pos + idx_bytes]);
+        if self.has_single_child(node_pos) {
+            children.push((read_symbol(self.data, node_pos + idx_bytes + 1), TriePos::node(node_pos + child_width.read_idx(self.data, node_pos + idx_bytes + 1 + K::WIDTH))));
+            return children;
+        };
+        let mut cluster_pos: usize = node_pos + idx_bytes + 1;
+        loop {
+            let next_cluster_dist = self.data[cluster_pos] as usize;
+            let cluster_min: K = read_symbol(self.data, cluster_pos + 1);
+            let cluster_max: K = read_symbol(self.data, cluster_pos + 1 + K::WIDTH);
+            let bitmap_pos = cluster_pos + 1 + 2 * K::WIDTH;
+            let mut child_slot_pos = bitmap_pos + cluster_bitmap_len(cluster_min, cluster_max);
+            for bit in 0..=cluster_max.to_usize() - cluster_min.to_usize() {
+                if cluster_min == cluster_max || self.data[bitmap_pos + bit / 8] & (1 << (bit % 8)) != 0 {
+                    let c = K::from_usize(cluster_min.to_usize() + bit);
+                    children.push((c, TriePos::node(node_pos + child_width.read_idx(self.data, child_slot_pos))));
+                    child_slot_pos += child_width.0;
+                };
+            };
+            if next_cluster_dist == 0 {
+                break;
+            };
+            cluster_pos += next_cluster_dist;
+        };
+        // Clusters are ordered by size in `data`, not by char.
+        children.sort_unstable_by_key(|(c, _)| *c);
+        children
+    }
+
+    /// Decodes the node at `pos` in the data, so tools can inspect the structure without repeating the layout arithmetic. `pos` must be the start of a node, such as [`Layout::HEADER_LEN`] for the root, a position from [`FastrieBuilderNode::prebuild_with_offsets`], or the position of a child or path end of another node.
+    ///
+    /// Panics if the node extends past the end of the data. Data that isn't a node at `pos` is decoded as if it were.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use fastrie::*;
+    ///
+    /// let mut builder = FastrieBuilderNode::new(IndexWidth(2));
+    /// builder.add(b"a", 1);
+    /// builder.add(b"hello", 2);
+    /// let build = builder.prebuild().unwrap();
+    ///
+    /// let trie = Fastrie::from_prebuilt(build.index_width, &build.values, &build.data).unwrap();
+    /// let root = trie.node_at(Layout::HEADER_LEN);
+    /// assert_eq!(root.value_idx, None);
+    /// assert_eq!(root.children.iter().map(|&(c, _)| c).collect::<Vec<_>>(), b"ah".to_vec());
+    /// let h = trie.node_at(root.children[1].1);
+    /// let (path, end) = h.path.unwrap();
+    /// assert_eq!(path, b"ello".to_vec());
+    /// assert_eq!(trie.node_at(end).value_idx, Some(1));
+    /// ```
+    pub fn node_at(&self, pos: usize) -> NodeView<K> {
+        let pos = TriePos::node(pos);
+        let children_present = self.data[pos.node_pos + self.index_width.0];
+        let path = if self.has_path(pos.node_pos) {
+            let (chars_pos, len) = self.path(pos.node_pos);
+            Some(((0..len).map(|i| read_symbol(self.data, chars_pos + i * K::WIDTH)).collect(), chars_pos + len * K::WIDTH))
+        } else {
+            None
+        };
+        NodeView {
+            value_idx: self.pos_value_idx(pos),
+            children_present,
+            children: if path.is_some() { Vec::new() } else { self.children(pos).into_iter().map(|(c, child)| (c, child.node_pos)).collect() },
+            path,
+        }
+    }
+
+    /// Returns an iterator over all keys in the trie.
+    ///
+    /// Keys are guaranteed to be yielded in lexicographic byte order (a key is yielded before any key it is a prefix of), independent of how clusters are laid out in the built data.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use fastrie::*;
+    ///
+    /// let mut builder = FastrieBuilderNode::new(IndexWidth(2));
+    /// builder.add(b"world", ());
+    /// builder.add(b"hello", ());
+    /// builder.add(b"hell", ());
+    /// builder.add(b"a", ());
+    /// builder.add(b"help", ());
+    /// let build = builder.prebuild().unwrap();
+    ///
+    /// let trie = from_prebuilt_without_values(build.index_width, &build.data);
+    /// let keys = trie.keys().collect::<Vec<Vec<u8>>>();
+    /// assert_eq!(keys, vec![b"a".to_vec(), b"hell".to_vec(), b"hello".to_vec(), b"help".to_vec(), b"world".to_vec()]);
+    /// ```
+    pub fn keys(&self) -> FastrieKeys<'d, K> {
+        FastrieKeys {
+            trie: self.as_set(),
+            stack: vec![(Vec::new(), TriePos::ROOT)],
+        }
+    }
+
+    /// Returns the lexicographically smallest key, or `None` if the trie is empty. This descends the smallest child of each node until reaching a key, so it takes time proportional to the length of the key rather than the number of keys.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use fastrie::*;
+    ///
+    /// let mut builder = FastrieBuilderNode::new(IndexWidth(2));
+    /// builder.add(b"hell", 1);
+    /// builder.add(b"hello", 2);
+    /// builder.add(b"world", 4);
+    /// let build = builder.prebuild().unwrap();
+    ///
+    /// let trie = Fastrie::from_prebuilt(build.index_width, &build.values, &build.data).unwrap();
+    /// assert_eq!(trie.min_key(), Some(b"hell".to_vec()));
+    /// assert_eq!(trie.max_key(), Some(b"world".to_vec()));
+    /// ```
+    pub fn min_key(&self) -> Option<Vec<K>> {
+        let mut key = Vec::new();
+        let mut pos = TriePos::ROOT;
+        // A key is smaller than any key it's a prefix of, so the first key reached is the smallest.
+        while self.pos_value_idx(pos).is_none() {
+            let (c, child_pos) = *self.children(pos).first()?;
+            key.push(c);
+            pos = child_pos;
+        };
+        Some(key)
+    }
+
+    /// Returns the lexicographically largest key, or `None` if the trie is empty. This descends the largest child of each node, so like [`Fastrie::min_key`] it takes time proportional to the length of the key.
+    pub fn max_key(&self) -> Option<Vec<K>> {
+        let mut key = Vec::new();
+        let mut pos = TriePos::ROOT;
+        // Any key is smaller than the keys it's a prefix of, so the largest is the last key reached.
+        let mut max_key_len = None;
+        loop {
+            if self.pos_value_idx(pos).is_some() {
+                max_key_len = Some(key.len());
+            };
+            match self.children(pos).last() {
+                Some(&(c, child_pos)) => {
+                    key.push(c);
+                    pos = child_pos;
+                }
+                None => break,
+            };
+        };
+        key.truncate(max_key_len?);
+        Some(key)
+    }
+
+    /// Returns up to `limit` keys sharing the longest common prefix with `query` that any key shares, in lexicographic order. This walks as far along `query` as the trie goes, then returns keys below that point, e.g. as suggestions after `query` fails to match.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use fastrie::*;
+    ///
+    /// let mut builder = FastrieBuilderNode::new(IndexWidth(2));
+    /// builder.add(b"hello", ());
+    /// builder.add(b"help", ());
+    /// builder.add(b"world", ());
+    /// let build = builder.prebuild().unwrap();
+    ///
+    /// let trie = from_prebuilt_without_values(build.index_width, &build.data);
+    /// assert_eq!(trie.nearest_keys(b"helium", 10), vec![b"hello".to_vec(), b"help".to_vec()]);
+    /// assert_eq!(trie.nearest_keys(b"helium", 1), vec![b"hello".to_vec()]);
+    /// assert_eq!(trie.nearest_keys(b"hello", 10), vec![b"hello".to_vec()]);
+    /// assert_eq!(trie.nearest_keys(b"xyz", 10).len(), 3);
+    /// ```
+    pub fn nearest_keys(&self, query: &[K], limit: usize) -> Vec<Vec<K>> {
+        let mut pos = TriePos::ROOT;
+        let mut depth = 0;
+        for &c in query {
+            match self.step(pos, c) {
+                Some(next) => pos = next,
+                None => break,
+            };
+            depth += 1;
+        };
+        FastrieKeys {
+            trie: self.as_set(),
+            stack: vec![(query[..depth].to_vec(), pos)],
+        }.take(limit).collect()
+    }
+
+    /// Returns the position of the child of the node at `node_pos` for the char `c`, if it exists. The node must not have a path.
+    fn child<C: WalkCounters>(&self, node_pos: usize, c: K, counters: &mut C) -> Option<usize> {
+        if !self.has_children(node_pos) {
+            return None;
+        };
+        self.child_in_clusters(node_pos, c, counters)
+    }
+
+    /// Like `child`, but the node at `node_pos` must have children and not have a path.
+    fn child_in_clusters<C: WalkCounters>(&self, node_pos: usize, c: K, counters: &mut C) -> Option<usize> {
+        self.walker().child_in_clusters(node_pos, c, counters)
+    }
+
+    fn _longest_matching_prefix(&self, text: &[K]) -> Option<(usize, usize)> {
+      self._longest_matching_prefix_counted(text, &mut ()).0
+    }
+
+    /// Returns the inclusive end and value index of the longest nonempty match, as the empty key has no inclusive end.
+    fn _longest_matching_prefix_end(&self, text: &[K]) -> Option<(usize, usize)> {
+      self._longest_matching_prefix(text).filter(|&(len, _)| len > 0).map(|(len, value_idx)| (len - 1, value_idx))
+    }
+
+    /// Returns the length and value index of the longest match, including the empty key's, and the amount of `text` walked along the trie.
+    fn _longest_matching_prefix_counted<C: WalkCounters>(&self, text: &[K], counters: &mut C) -> (Option<(usize, usize)>, usize) {
+        self.walker().longest_matching_prefix_counted(text, counters)
+    }
+
+    /// Checks that the data is structured exactly as a build would produce it: every node and cluster is within bounds, nodes are laid out contiguously in depth-first cluster order, and each node's children-present byte matches whether a cluster chain (or single child) actually follows it. This should be used before querying data from an untrusted source, as queries assume the data is well formed.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use fastrie::*;
+    ///
+    /// let mut builder = FastrieBuilderNode::new(IndexWidth(1));
+    /// builder.add(b"hello", ());
+    /// builder.add(b"world", ());
+    /// let build = builder.prebuild().unwrap();
+    ///
+    /// let trie = from_prebuilt_without_values(build.index_width, &build.data);
+    /// assert_eq!(trie.verify(), Ok(()));
+    ///
+    /// // The root node follows the data header byte, and is two header bytes followed by two singleton clusters of four bytes each, so the 'h' node is at 11.
+    /// // Clear its children-present byte, which indicates the path "ello".
+    /// let mut corrupt = build.data.clone();
+    /// assert_eq!(corrupt[12], 4);
+    /// corrupt[12] = 0;
+    /// let trie = from_prebuilt_without_values(build.index_width, &corrupt);
+    /// assert_eq!(trie.verify(), Err(FastrieError::PresenceMismatch { node_pos: 11 }));
+    ///
+    /// // The wrong index width is detected from the header byte.
+    /// let trie = from_prebuilt_without_values(IndexWidth(2), &build.data);
+    /// assert_eq!(trie.verify(), Err(FastrieError::IndexWidthMismatch { given: IndexWidth(2), built: IndexWidth(1) }));
+    ///
+    /// // Set the clusters bit of the children-present byte of the last node, which has no clusters following it.
+    /// let mut corrupt = build.data.clone();
+    /// let last = corrupt.len() - 1;
+    /// corrupt[last] |= 1;
+    /// let trie = from_prebuilt_without_values(build.index_width, &corrupt);
+    /// assert_eq!(trie.verify(), Err(FastrieError::PresenceMismatch { node_pos: last - 1 }));
+    /// ```
+    pub fn verify(&self) -> Result<(), FastrieError> {
+        self.index_width.check_header(self.data)?;
+        let mut cursor = ROOT_POS;
+        let mut last_leaf = None;
+        // Nodes are verified with an explicit stack, as untrusted data may have arbitrarily deep keys. Children are pushed in reverse, so nodes are popped in the order they're laid out.
+        let mut stack = vec![ROOT_POS];
+        while let Some(node_pos) = stack.pop() {
+            self.verify_node(node_pos, &mut cursor, &mut last_leaf, &mut stack)?;
+        };
+        if cursor != self.data.len() {
+            // Trailing bytes after the last leaf are clusters that its children-present byte doesn't account for.
+            return Err(FastrieError::PresenceMismatch { node_pos: last_leaf.unwrap() });
+        };
+        Ok(())
+    }
+
+    /// Verifies the record of the node at `node_pos`, which must start at `cursor`, and pushes its children onto `stack` to be verified next. Advances `cursor` to the end of the record, and sets `last_leaf` to the node if it's a leaf.
+    fn verify_node(&self, node_pos: usize, cursor: &mut usize, last_leaf: &mut Option<usize>, stack: &mut Vec<usize>) -> Result<(), FastrieError> {
+        let idx_bytes = self.index_width.0;
+        if node_pos != *cursor {
+            return Err(match *last_leaf {
+                // Bytes were skipped after a leaf, so they're clusters that its children-present byte doesn't account for.
+                Some(leaf_pos) if node_pos > *cursor => FastrieError::PresenceMismatch { node_pos: leaf_pos },
+                _ => FastrieError::MalformedData { pos: node_pos },
+            });
+        };
+        if node_pos + idx_bytes + 1 > self.data.len() {
+            return Err(FastrieError::TruncatedData { pos: node_pos });
+        };
+        *cursor = node_pos + idx_bytes + 1;
+        let children_present = self.data[node_pos + idx_bytes];
+        // Walks only read the value index if the value bit is set, so they must agree.
+        if (children_present & NODE_HAS_VALUE != 0) != (self.index_width.read_idx(self.data, node_pos) != 0) {
+            return Err(FastrieError::MalformedData { pos: node_pos + idx_bytes });
+        };
+        let children_present = children_present & !NODE_HAS_VALUE;
+        let child_width = IndexWidth::of_children_present(children_present);
+        match children_present & !NODE_CHILD_WIDTH_MASK {
+            // Only nodes with child indices declare a child width.
+            kind if kind != children_present && kind & (NODE_HAS_SINGLE_CHILD | NODE_HAS_CLUSTERS) == 0 => Err(FastrieError::MalformedData { pos: node_pos + idx_bytes }),
+            0 => {
+                // A build never emits a leaf without a value, except for the root of an empty trie.
+                if node_pos != ROOT_POS && self.node_value_idx(node_pos).is_none() {
+                    return Err(FastrieError::PresenceMismatch { node_pos });
+                };
+                *last_leaf = Some(node_pos);
+                Ok(())
+            }
+            NODE_HAS_SINGLE_CHILD => {
+                if *cursor == self.data.len() {
+                    return Err(FastrieError::PresenceMismatch { node_pos });
+                };
+                *cursor += Layout::new(self.index_width).symbol_single_child_len::<K>(child_width);
+                if *cursor > self.data.len() {
+                    return Err(FastrieError::TruncatedData { pos: node_pos });
+                };
+                *last_leaf = None;
+                stack.push(node_pos.saturating_add(child_width.read_idx(self.data, node_pos + idx_bytes + 1 + K::WIDTH)));
+                Ok(())
+            }
+            NODE_HAS_PATH => {
+                if *cursor == self.data.len() {
+                    return Err(FastrieError::PresenceMismatch { node_pos });
+                };
+                let len = self.data[*cursor] as usize;
+                if len < 2 {
+                    return Err(FastrieError::MalformedData { pos: *cursor });
+                };
+                *cursor += Layout::new(self.index_width).symbol_path_len::<K>(len);
+                if *cursor > self.data.len() {
+                    return Err(FastrieError::TruncatedData { pos: node_pos });
+                };
+                *last_leaf = None;
+                // The node at the end of the path immediately follows it.
+                stack.push(*cursor);
+                Ok(())
+            }
+            NODE_HAS_CLUSTERS => {
+                let mut children = Vec::new();
+                loop {
+                    let cluster_pos = *cursor;
+                    if cluster_pos == self.data.len() {
+                        return Err(FastrieError::PresenceMismatch { node_pos });
+                    };
+                    if cluster_pos + 1 + 2 * K::WIDTH > self.data.len() {
+                        return Err(FastrieError::TruncatedData { pos: cluster_pos });
+                    };
+                    let next_cluster_dist = self.data[cluster_pos] as usize;
+                    let cluster_min: K = read_symbol(self.data, cluster_pos + 1);
+                    let cluster_max: K = read_symbol(self.data, cluster_pos + 1 + K::WIDTH);
+                    if cluster_min > cluster_max {
+                        return Err(FastrieError::PresenceMismatch { node_pos });
+                    };
+                    let bitmap_pos = cluster_pos + 1 + 2 * K::WIDTH;
+                    let bitmap_len = cluster_bitmap_len(cluster_min, cluster_max);
+                    if bitmap_pos + bitmap_len > self.data.len() {
+                        return Err(FastrieError::TruncatedData { pos: cluster_pos });
+                    };
+                    let child_count = if bitmap_len == 0 {
+                        1
+                    } else {
+                        self.data[bitmap_pos..bitmap_pos + bitmap_len].iter().map(|b| b.count_ones() as usize).sum()
+                    };
+                    let child_slots_pos = bitmap_pos + bitmap_len;
+                    *cursor = child_slots_pos + child_count * child_width.0;
+                    if child_count == 0 {
+                        return Err(FastrieError::PresenceMismatch { node_pos });
+                    };
+                    if *cursor > self.data.len() {
+                        return Err(FastrieError::TruncatedData { pos: cluster_pos });
+                    };
+                    for i in 0..child_count {
+                        children.push(node_pos.saturating_add(child_width.read_idx(self.data, child_slots_pos + i * child_width.0)));
+                    };
+                    if next_cluster_dist == 0 {
+                        break;
+                    };
+                    if cluster_pos + next_cluster_dist != *cursor {
+                        return Err(FastrieError::PresenceMismatch { node_pos });
+                    };
+                };
+                *last_leaf = None;
+                stack.extend(children.into_iter().rev());
+                Ok(())
+            }
+            _ => Err(FastrieError::PresenceMismatch { node_pos }),
+        }
+    }
+
+    /// Classifies `key` in a single walk as an exact key, a strict prefix of one or more keys, or neither.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use fastrie::*;
+    ///
+    /// let mut builder = FastrieBuilderNode::new(IndexWidth(2));
+    /// builder.add(b"hell", ());
+    /// builder.add(b"hello", ());
+    /// let build = builder.prebuild().unwrap();
+    ///
+    /// let trie = from_prebuilt_without_values(build.index_width, &build.data);
+    /// assert_eq!(trie.classify(b"hell"), KeyClass::Exact);
+    /// assert_eq!(trie.classify(b"hello"), KeyClass::Exact);
+    /// assert_eq!(trie.classify(b"hel"), KeyClass::Prefix);
+    /// assert_eq!(trie.classify(b"hellos"), KeyClass::None);
+    /// assert_eq!(trie.classify(b"world"), KeyClass::None);
+    /// ```
+    pub fn classify(&self, key: &[K]) -> KeyClass {
+        match self.walk_to(key) {
+            Some(pos) if self.pos_value_idx(pos).is_some() => KeyClass::Exact,
+            Some(pos) if self.pos_has_children(pos) => KeyClass::Prefix,
+            _ => KeyClass::None,
+        }
+    }
+
+    /// Returns whether any key starts with `prefix`, including a key equal to `prefix`.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use fastrie::*;
+    ///
+    /// let mut builder = FastrieBuilderNode::new(IndexWidth(2));
+    /// builder.add(b"hello", ());
+    /// let build = builder.prebuild().unwrap();
+    ///
+    /// let trie = from_prebuilt_without_values(build.index_width, &build.data);
+    /// assert!(trie.contains_prefix(b"hel"));
+    /// assert!(trie.contains_prefix(b"hello"));
+    /// assert!(!trie.contains_prefix(b"help"));
+    /// ```
+    pub fn contains_prefix(&self, prefix: &[K]) -> bool {
+        self.classify(prefix) != KeyClass::None
+    }
+
+    /// Returns whether `key` is a key, i.e. whether [`Fastrie::get`] would return a value for it. This includes the empty key, which is the root.
+    pub fn contains_key(&self, key: &[K]) -> bool {
+      self.walker().exact_value_idx(key).is_some()
+    }
+
+    /// Returns whether each of `keys` is a key, in the same order, like calling [`Fastrie::contains_key`] for each. Filtering many candidates in one call leaves room for the lookups to be interleaved or prefetched.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use fastrie::*;
+    ///
+    /// let mut builder = FastrieBuilderNode::new(IndexWidth(2));
+    /// builder.add(b"hell", ());
+    /// builder.add(b"hello", ());
+    /// let build = builder.prebuild().unwrap();
+    ///
+    /// let trie = from_prebuilt_without_values(build.index_width, &build.data);
+    /// assert_eq!(trie.contains_keys(&[&b"hello"[..], b"help", b"hell", b""]), vec![true, false, true, false]);
+    /// ```
+    pub fn contains_keys(&self, keys: &[&[K]]) -> Vec<bool> {
+        let walker = self.walker();
+        keys.iter().map(|key| walker.exact_value_idx(key).is_some()).collect()
     }
 }
@@ -0,0 +1,153 @@
+use std::convert::TryFrom;
+
+use crate::packed::{pack, parse_packed, Packed};
+use crate::{Fastrie, FastrieBuild, FastrieError, FastrieMatch, IndexWidth, ValueStore};
+
+/// A trie that owns its data and values, which can be serialised to and loaded from the packed format of [`FastrieBuild::pack`].
+///
+/// # Example
+///
+/// ```
+/// use std::convert::TryFrom;
+/// use fastrie::*;
+///
+/// let mut builder = FastrieBuilderNode::new(IndexWidth(2));
+/// builder.add(b"hell", b"underworld".to_vec());
+/// builder.add(b"hello", b"greeting".to_vec());
+/// let bytes = OwnedFastrie::from(builder.prebuild().unwrap()).to_bytes();
+///
+/// let owned = OwnedFastrie::<Vec<u8>>::try_from(bytes.as_slice()).unwrap();
+/// assert_eq!(owned.as_fastrie().longest_matching_prefix(b"hello!").unwrap().value, b"greeting");
+/// assert_eq!(owned.to_bytes(), bytes);
+///
+/// let mut corrupt = bytes.clone();
+/// corrupt[0] = b'X';
+/// assert_eq!(OwnedFastrie::<Vec<u8>>::try_from(corrupt).err(), Some(FastrieError::BadMagic));
+/// // The layout version follows the magic bytes.
+/// let mut corrupt = bytes.clone();
+/// corrupt[4..8].copy_from_slice(&(Layout::VERSION - 1).to_le_bytes());
+/// assert_eq!(OwnedFastrie::<Vec<u8>>::try_from(corrupt).err(), Some(FastrieError::UnsupportedVersion(Layout::VERSION - 1)));
+/// let mut corrupt = bytes.clone();
+/// corrupt[8] = 9;
+/// assert_eq!(OwnedFastrie::<Vec<u8>>::try_from(corrupt).err(), Some(FastrieError::InvalidIndexWidth(9)));
+/// assert_eq!(OwnedFastrie::<Vec<u8>>::try_from(&bytes[..24]).err(), Some(FastrieError::TruncatedData { pos: 24 }));
+/// ```
+///
+/// As it owns everything it queries, it can be stored in a struct field, which a [`Fastrie`] borrowing a [`FastrieBuild`] stored next to it can't be:
+///
+/// ```
+/// use fastrie::*;
+///
+/// struct Tokenizer {
+///     keywords: OwnedFastrie<&'static str>,
+/// }
+///
+/// impl Tokenizer {
+///     fn new() -> Tokenizer {
+///         let mut builder = FastrieBuilderNode::new(IndexWidth(2));
+///         builder.add(b"fn", "function");
+///         builder.add(b"for", "loop");
+///         Tokenizer { keywords: OwnedFastrie::from(builder.prebuild().unwrap()) }
+///     }
+///
+///     fn keyword(&self, text: &[u8]) -> Option<&'static str> {
+///         self.keywords.longest_matching_prefix(text).map(|m| *m.value)
+///     }
+/// }
+///
+/// let tokenizer = Tokenizer::new();
+/// assert_eq!(tokenizer.keyword(b"for x in xs"), Some("loop"));
+/// assert_eq!(tokenizer.keyword(b"fn main()"), Some("function"));
+/// assert_eq!(tokenizer.keyword(b"let"), None);
+/// ```
+pub struct OwnedFastrie<V> {
+    data: Vec<u8>,
+    index_width: IndexWidth,
+    values: Vec<V>,
+    metadata: Vec<u8>,
+}
+
+impl<V> OwnedFastrie<V> {
+    pub fn as_fastrie(&self) -> Fastrie<'_, '_, V> {
+        Fastrie::from_prebuilt_unchecked(self.index_width, &self.values, &self.data)
+    }
+
+    /// Like [`Fastrie::get`].
+    pub fn get(&self, key: &[u8]) -> Option<&V> {
+        self.as_fastrie().get(key)
+    }
+
+    /// Like [`Fastrie::contains_key`].
+    pub fn contains_key(&self, key: &[u8]) -> bool {
+        self.as_fastrie().contains_key(key)
+    }
+
+    /// Like [`Fastrie::longest_matching_prefix`].
+    pub fn longest_matching_prefix(&self, text: &[u8]) -> Option<FastrieMatch<'_, V>> {
+        self.as_fastrie().longest_matching_prefix(text)
+    }
+
+    pub fn index_width(&self) -> IndexWidth {
+        self.index_width
+    }
+
+    pub fn data(&self) -> &[u8] {
+        &self.data
+    }
+
+    pub fn values(&self) -> &[V] {
+        &self.values
+    }
+
+    /// Returns the metadata stored alongside the trie by [`FastrieBuild::pack_with_metadata`] or [`OwnedFastrie::set_metadata`], which is empty if none was.
+    pub fn metadata(&self) -> &[u8] {
+        &self.metadata
+    }
+
+    /// Sets the metadata written by [`OwnedFastrie::to_bytes`].
+    pub fn set_metadata(&mut self, metadata: Vec<u8>) {
+        self.metadata = metadata;
+    }
+}
+
+impl<V: AsRef<[u8]>> OwnedFastrie<V> {
+    /// Serialises into the packed format of [`FastrieBuild::pack`], including the metadata.
+    pub fn to_bytes(&self) -> Vec<u8> {
+        pack(self.index_width, &self.data, &self.values, &self.metadata)
+    }
+}
+
+impl<V> From<FastrieBuild<V>> for OwnedFastrie<V> {
+    fn from(build: FastrieBuild<V>) -> OwnedFastrie<V> {
+        OwnedFastrie {
+            data: build.data,
+            index_width: build.index_width,
+            values: build.values,
+            metadata: Vec::new(),
+        }
+    }
+}
+
+impl<V: for<'a> From<&'a [u8]>> TryFrom<&[u8]> for OwnedFastrie<V> {
+    type Error = FastrieError;
+
+    /// Parses and validates the packed format of [`FastrieBuild::pack`], copying the data and converting each value from its bytes.
+    fn try_from(packed: &[u8]) -> Result<OwnedFastrie<V>, FastrieError> {
+        let Packed { index_width, data, values: packed_values, metadata } = parse_packed(packed)?;
+        let values = (0..packed_values.len()).map(|i| V::from(packed_values.get(i).unwrap())).collect();
+        Ok(OwnedFastrie {
+            data: data.to_vec(),
+            index_width,
+            values,
+            metadata: metadata.to_vec(),
+        })
+    }
+}
+
+impl<V: for<'a> From<&'a [u8]>> TryFrom<Vec<u8>> for OwnedFastrie<V> {
+    type Error = FastrieError;
+
+    fn try_from(packed: Vec<u8>) -> Result<OwnedFastrie<V>, FastrieError> {
+        OwnedFastrie::try_from(packed.as_slice())
+    }
+}
@@ -0,0 +1,279 @@
+use std::convert::TryInto;
+use std::marker::PhantomData;
+
+use crate::{Fastrie, FastrieBuild, FastrieError, IndexWidth, Layout, ValueStore};
+
+/// Magic bytes at the start of a packed buffer produced by [`FastrieBuild::pack`].
+pub const PACKED_MAGIC: [u8; 4] = *b"FTRP";
+
+// Packed layout, where all integers are u32 little endian:
+// - PACKED_MAGIC
+// - Layout::VERSION of the data
+// - index width (1 byte)
+// - content hash of the data and values (u64 little endian), see `content_hash`
+// - data length
+// - data
+// - value count
+// - value count + 1 offsets into the value bytes, where value i is `offsets[i]..offsets[i + 1]`
+// - value bytes
+// - metadata length
+// - metadata, which isn't interpreted or covered by the content hash
+
+const fn read_u32(bytes: &[u8], pos: usize) -> usize {
+    u32::from_le_bytes([bytes[pos], bytes[pos + 1], bytes[pos + 2], bytes[pos + 3]]) as usize
+}
+
+const VERSION_POS: usize = 4;
+const INDEX_WIDTH_POS: usize = VERSION_POS + 4;
+// Offset of the data length, after the magic bytes, layout version, index width, and content hash.
+const DATA_LEN_POS: usize = INDEX_WIDTH_POS + 1 + 8;
+const HEADER_LEN: usize = DATA_LEN_POS + 4;
+
+/// 64-bit FNV-1a, which unlike `std`'s hashers is guaranteed to be the same across Rust versions and platforms, so hashes can be persisted.
+struct Fnv1a(u64);
+
+impl Fnv1a {
+    fn new() -> Fnv1a {
+        Fnv1a(0xcbf2_9ce4_8422_2325)
+    }
+
+    fn write(&mut self, bytes: &[u8]) {
+        for &b in bytes {
+            self.0 ^= b as u64;
+            self.0 = self.0.wrapping_mul(0x0000_0100_0000_01b3);
+        };
+    }
+}
+
+/// Hashes the data and the bytes of each value. Each value is prefixed by its length, so moving bytes between adjacent values changes the hash.
+fn content_hash<'a>(data: &[u8], values: impl Iterator<Item = &'a [u8]>) -> u64 {
+    let mut hasher = Fnv1a::new();
+    hasher.write(data);
+    for v in values {
+        hasher.write(&(v.len() as u64).to_le_bytes());
+        hasher.write(v);
+    };
+    hasher.0
+}
+
+fn push_u32(out: &mut Vec<u8>, v: usize) {
+    let v: u32 = v.try_into().expect("packed section is too large");
+    out.extend_from_slice(&v.to_le_bytes());
+}
+
+/// Values stored in the value section of a packed buffer, resolved by slicing into the buffer.
+#[derive(Clone, Copy)]
+pub struct PackedValues<'d> {
+    offsets: &'d [u8],
+    bytes: &'d [u8],
+}
+
+impl<'d> ValueStore<'d, [u8]> for PackedValues<'d> {
+    fn get(&self, idx: usize) -> Option<&'d [u8]> {
+        // `idx` comes from the data, which may not belong to these values, so it may be arbitrarily large.
+        if idx.checked_add(2)?.checked_mul(4)? > self.offsets.len() {
+            return None;
+        };
+        self.bytes.get(read_u32(self.offsets, idx * 4)..read_u32(self.offsets, (idx + 1) * 4))
+    }
+}
+
+impl<'d> PackedValues<'d> {
+    pub fn len(&self) -> usize {
+        self.offsets.len() / 4 - 1
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+}
+
+pub(crate) fn pack<V: AsRef<[u8]>>(index_width: IndexWidth, data: &[u8], values: &[V], metadata: &[u8]) -> Vec<u8> {
+    let value_bytes_len = values.iter().map(|v| v.as_ref().len()).sum::<usize>();
+    let mut out = Vec::with_capacity(HEADER_LEN + data.len() + 4 + (values.len() + 1) * 4 + value_bytes_len + 4 + metadata.len());
+    out.extend_from_slice(&PACKED_MAGIC);
+    out.extend_from_slice(&Layout::VERSION.to_le_bytes());
+    out.push(index_width.0 as u8);
+    out.extend_from_slice(&content_hash(data, values.iter().map(|v| v.as_ref())).to_le_bytes());
+    push_u32(&mut out, data.len());
+    out.extend_from_slice(data);
+    push_u32(&mut out, values.len());
+    let mut offset = 0;
+    push_u32(&mut out, offset);
+    for v in values.iter() {
+        offset += v.as_ref().len();
+        push_u32(&mut out, offset);
+    };
+    for v in values.iter() {
+        out.extend_from_slice(v.as_ref());
+    };
+    push_u32(&mut out, metadata.len());
+    out.extend_from_slice(metadata);
+    out
+}
+
+/// The sections of a packed buffer.
+pub(crate) struct Packed<'d> {
+    pub(crate) index_width: IndexWidth,
+    pub(crate) data: &'d [u8],
+    pub(crate) values: PackedValues<'d>,
+    pub(crate) metadata: &'d [u8],
+}
+
+/// Parses and validates a packed buffer, including verifying its data.
+pub(crate) fn parse_packed(packed: &[u8]) -> Result<Packed<'_>, FastrieError> {
+    if packed.len() < HEADER_LEN {
+        return Err(FastrieError::TruncatedData { pos: 0 });
+    };
+    if packed[..4] != PACKED_MAGIC {
+        return Err(FastrieError::BadMagic);
+    };
+    let version = read_u32(packed, VERSION_POS) as u32;
+    if version != Layout::VERSION {
+        return Err(FastrieError::UnsupportedVersion(version));
+    };
+    let index_width = packed[INDEX_WIDTH_POS] as usize;
+    if !(1..=8).contains(&index_width) {
+        return Err(FastrieError::InvalidIndexWidth(index_width));
+    };
+    // Lengths are read from the buffer, which may be untrusted, so sums of them may overflow where `usize` is 32 bits.
+    let data_len = read_u32(packed, DATA_LEN_POS);
+    let data_end = HEADER_LEN.checked_add(data_len).ok_or(FastrieError::MalformedData { pos: DATA_LEN_POS })?;
+    if packed.len() < data_end.saturating_add(4) {
+        return Err(FastrieError::TruncatedData { pos: packed.len() });
+    };
+    let data = &packed[HEADER_LEN..data_end];
+    let values_len = read_u32(packed, data_end);
+    let offsets_end = values_len.checked_add(1)
+        .and_then(|count| count.checked_mul(4))
+        .and_then(|offsets_len| offsets_len.checked_add(data_end + 4))
+        .ok_or(FastrieError::MalformedData { pos: data_end })?;
+    if packed.len() < offsets_end {
+        return Err(FastrieError::TruncatedData { pos: packed.len() });
+    };
+    let offsets = &packed[data_end + 4..offsets_end];
+    // Offsets must be non-decreasing and end within the buffer, leaving room for the metadata length.
+    let mut last_offset = 0;
+    for i in 0..=values_len {
+        let offset = read_u32(offsets, i * 4);
+        if offset < last_offset || offsets_end.checked_add(offset).filter(|&end| end <= packed.len()).is_none() {
+            return Err(FastrieError::MalformedData { pos: data_end + 4 + i * 4 });
+        };
+        last_offset = offset;
+    };
+    // Checked by the loop above to be within the buffer.
+    let values_end = offsets_end + last_offset;
+    if packed.len() < values_end.saturating_add(4) {
+        return Err(FastrieError::TruncatedData { pos: packed.len() });
+    };
+    let values = PackedValues {
+        offsets,
+        bytes: &packed[offsets_end..values_end],
+    };
+    // The metadata must end exactly at the end of the buffer.
+    let metadata_len = read_u32(packed, values_end);
+    if packed.len() - (values_end + 4) != metadata_len {
+        return Err(FastrieError::MalformedData { pos: values_end });
+    };
+    let metadata = &packed[values_end + 4..];
+    let mut hash = [0; 8];
+    hash.copy_from_slice(&packed[INDEX_WIDTH_POS + 1..DATA_LEN_POS]);
+    if content_hash(data, (0..values_len).map(|i| values.get(i).unwrap())) != u64::from_le_bytes(hash) {
+        return Err(FastrieError::ContentHashMismatch);
+    };
+    Fastrie::<[u8], PackedValues>::from_prebuilt_with_store(IndexWidth(index_width), values, data).verify()?;
+    Ok(Packed {
+        index_width: IndexWidth(index_width),
+        data,
+        values,
+        metadata,
+    })
+}
+
+impl<V: AsRef<[u8]>> FastrieBuild<V> {
+    /// Returns a hash of the data and the bytes of the values, which is stable across Rust versions and platforms, so it can be persisted to check whether a cached build matches the current one. It's stored in the header of [`FastrieBuild::pack`], so loading a packed buffer detects corrupted data and values.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use fastrie::*;
+    ///
+    /// let mut builder = FastrieBuilderNode::new(IndexWidth(2));
+    /// builder.add(b"hell", "underworld");
+    /// builder.add(b"hello", "greeting");
+    /// let mut build = builder.prebuild().unwrap();
+    /// let hash = build.content_hash();
+    /// build.values[0] = "overworld";
+    /// assert_ne!(build.content_hash(), hash);
+    /// ```
+    pub fn content_hash(&self) -> u64 {
+        content_hash(&self.data, self.values.iter().map(|v| v.as_ref()))
+    }
+
+    /// Packs the data and values into one self-describing buffer, which can be loaded with [`from_prebuilt_packed`]. Values are stored as their bytes.
+    ///
+    /// Panics if the data or values are larger than 4 GiB.
+    pub fn pack(&self) -> Vec<u8> {
+        pack(self.index_width, &self.data, &self.values, &[])
+    }
+
+    /// Like [`FastrieBuild::pack`], but also stores `metadata`, e.g. a version or the source the keys were built from. It's kept verbatim and returned by [`OwnedFastrie::metadata`](crate::OwnedFastrie::metadata), and doesn't change the data or values sections.
+    ///
+    /// Panics if the data, values, or metadata are larger than 4 GiB.
+    pub fn pack_with_metadata(&self, metadata: &[u8]) -> Vec<u8> {
+        pack(self.index_width, &self.data, &self.values, metadata)
+    }
+}
+
+/// Creates a trie from a buffer produced by [`FastrieBuild::pack`]. Values are resolved as byte slices of `packed` without copying, so a single `&'static [u8]` (e.g. from `include_bytes!`) is all that's needed to ship a trie, which is convenient for WebAssembly. This is a `const fn`, so it can also initialise a `static`.
+///
+/// Panics if `packed` isn't a valid packed buffer, including if it was packed with another [`Layout::VERSION`]. Like [`Fastrie::from_prebuilt_unchecked`], the data isn't verified, and neither is the content hash.
+///
+/// # Example
+///
+/// ```
+/// use fastrie::*;
+///
+/// let mut builder = FastrieBuilderNode::new(IndexWidth(2));
+/// builder.add(b"hell", "underworld");
+/// builder.add(b"hello", "greeting");
+/// builder.add(b"world", "");
+/// let packed = builder.prebuild().unwrap().pack();
+///
+/// let trie = from_prebuilt_packed(&packed);
+/// assert_eq!(trie.longest_matching_prefix(b"hello!").unwrap().value, b"greeting");
+/// assert_eq!(trie.longest_matching_prefix(b"hell").unwrap().value, b"underworld");
+/// assert_eq!(trie.longest_matching_prefix(b"world").unwrap().value, b"");
+/// assert!(trie.longest_matching_prefix(b"help").is_none());
+/// ```
+pub const fn from_prebuilt_packed(packed: &[u8]) -> Fastrie<'_, '_, [u8], PackedValues<'_>> {
+    assert!(packed.len() >= HEADER_LEN, "packed buffer is truncated");
+    assert!(
+        packed[0] == PACKED_MAGIC[0] && packed[1] == PACKED_MAGIC[1] && packed[2] == PACKED_MAGIC[2] && packed[3] == PACKED_MAGIC[3],
+        "packed buffer has invalid magic bytes",
+    );
+    assert!(read_u32(packed, VERSION_POS) as u32 == Layout::VERSION, "packed buffer has an unsupported layout version");
+    let index_width = packed[INDEX_WIDTH_POS] as usize;
+    assert!(index_width >= 1 && index_width <= 8, "packed buffer has invalid index width");
+    let data_len = read_u32(packed, DATA_LEN_POS);
+    let (_, rest) = packed.split_at(HEADER_LEN);
+    let (data, rest) = rest.split_at(data_len);
+    let values_len = read_u32(rest, 0);
+    let (_, rest) = rest.split_at(4);
+    let offsets_len = match values_len.checked_add(1) {
+        Some(count) => count.checked_mul(4),
+        None => None,
+    };
+    let (offsets, rest) = match offsets_len {
+        Some(offsets_len) => rest.split_at(offsets_len),
+        None => panic!("packed buffer has too many values"),
+    };
+    let (bytes, _) = rest.split_at(read_u32(offsets, values_len * 4));
+    Fastrie {
+        data,
+        index_width: IndexWidth(index_width),
+        values: Some(PackedValues { offsets, bytes }),
+        value_type: PhantomData,
+        symbol_type: PhantomData,
+    }
+}
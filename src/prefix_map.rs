@@ -0,0 +1,88 @@
+use crate::{ByteSource, ChunkedFastrie, Fastrie, FastrieMatch, FixedFastrie, OwnedFastrie, Symbol, ValueStore};
+
+/// Lookups shared by the tries of this crate, so code can be generic over anything that does longest prefix matching, including maps from other crates wrapped to implement this.
+///
+/// # Example
+///
+/// ```
+/// use fastrie::*;
+///
+/// fn first_word<M: PrefixMap<Value = str>>(map: &M, text: &[u8]) -> Option<String> {
+///     map.longest_matching_prefix(text).map(|m| m.value.to_string())
+/// }
+///
+/// let mut builder = FastrieBuilderNode::new(IndexWidth(2));
+/// builder.add(b"hell", Box::<str>::from("place"));
+/// builder.add(b"hello", Box::<str>::from("greeting"));
+/// let build = builder.prebuild().unwrap();
+///
+/// let trie: Fastrie<str, _> = Fastrie::from_prebuilt_boxed(build.index_width, &build.values, &build.data).unwrap();
+/// assert_eq!(first_word(&trie, b"hello world"), Some("greeting".to_string()));
+/// assert!(PrefixMap::contains_key(&trie, b"hell"));
+/// ```
+pub trait PrefixMap<K = u8> {
+    type Value: ?Sized;
+
+    /// Returns the value of `key` exactly, like [`Fastrie::get`].
+    fn get(&self, key: &[K]) -> Option<&Self::Value>;
+
+    /// Returns whether `key` is a key, like [`Fastrie::contains_key`].
+    fn contains_key(&self, key: &[K]) -> bool {
+        self.get(key).is_some()
+    }
+
+    /// Returns the longest key that is a prefix of `text`, like [`Fastrie::longest_matching_prefix`].
+    fn longest_matching_prefix(&self, text: &[K]) -> Option<FastrieMatch<'_, Self::Value>>;
+}
+
+impl<'v, V: ?Sized, S: ValueStore<'v, V>, K: Symbol> PrefixMap<K> for Fastrie<'v, '_, V, S, K> {
+    type Value = V;
+
+    fn get(&self, key: &[K]) -> Option<&V> {
+        Fastrie::get(self, key)
+    }
+
+    fn contains_key(&self, key: &[K]) -> bool {
+        Fastrie::contains_key(self, key)
+    }
+
+    fn longest_matching_prefix(&self, text: &[K]) -> Option<FastrieMatch<'_, V>> {
+        Fastrie::longest_matching_prefix(self, text)
+    }
+}
+
+impl<V, B: ByteSource, K: Symbol> PrefixMap<K> for ChunkedFastrie<'_, V, B, K> {
+    type Value = V;
+
+    fn get(&self, key: &[K]) -> Option<&V> {
+        ChunkedFastrie::get(self, key)
+    }
+
+    fn longest_matching_prefix(&self, text: &[K]) -> Option<FastrieMatch<'_, V>> {
+        ChunkedFastrie::longest_matching_prefix(self, text)
+    }
+}
+
+impl<V, const W: usize, K: Symbol> PrefixMap<K> for FixedFastrie<'_, '_, V, W, K> {
+    type Value = V;
+
+    fn get(&self, key: &[K]) -> Option<&V> {
+        FixedFastrie::get(self, key)
+    }
+
+    fn longest_matching_prefix(&self, text: &[K]) -> Option<FastrieMatch<'_, V>> {
+        FixedFastrie::longest_matching_prefix(self, text)
+    }
+}
+
+impl<V> PrefixMap for OwnedFastrie<V> {
+    type Value = V;
+
+    fn get(&self, key: &[u8]) -> Option<&V> {
+        OwnedFastrie::get(self, key)
+    }
+
+    fn longest_matching_prefix(&self, text: &[u8]) -> Option<FastrieMatch<'_, V>> {
+        OwnedFastrie::longest_matching_prefix(self, text)
+    }
+}
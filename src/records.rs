@@ -0,0 +1,80 @@
+use crate::{cluster_bitmap_len, IndexWidth, Layout, NODE_CHILDREN_MASK, NODE_HAS_CLUSTERS, NODE_HAS_PATH, NODE_HAS_SINGLE_CHILD};
+
+// Built data is a sequence of node records in depth-first order, where each record is a node header followed by its path, single child, or clusters. This is what allows subtrees to be copied as contiguous ranges. Child indices are relative to their node, so a copied subtree stays valid wherever it's copied to, and only records whose children move relative to them need patching. These only support byte keys, as builds don't record their symbol type.
+
+/// Returns the length of the node record at `pos`.
+pub(crate) fn record_len(data: &[u8], index_width: IndexWidth, pos: usize) -> usize {
+    let idx_bytes = index_width.0;
+    let layout = Layout::new(index_width);
+    let children_present = data[pos + idx_bytes];
+    let child_width = layout.child_width(children_present);
+    match children_present & NODE_CHILDREN_MASK {
+        NODE_HAS_PATH => layout.node_header_len() + layout.path_len(data[pos + idx_bytes + 1] as usize),
+        NODE_HAS_SINGLE_CHILD => layout.node_header_len() + layout.single_child_len(child_width),
+        NODE_HAS_CLUSTERS => {
+            let mut cluster_pos = pos + layout.node_header_len();
+            loop {
+                let min = data[cluster_pos + 1];
+                let max = data[cluster_pos + 2];
+                let bitmap_pos = cluster_pos + layout.cluster_header_len();
+                let bitmap_len = cluster_bitmap_len(min, max);
+                let child_count = if bitmap_len == 0 {
+                    1
+                } else {
+                    data[bitmap_pos..bitmap_pos + bitmap_len].iter().map(|b| b.count_ones() as usize).sum()
+                };
+                let next_cluster_dist = data[cluster_pos];
+                cluster_pos = bitmap_pos + bitmap_len + child_count * child_width.0;
+                if next_cluster_dist == 0 {
+                    return cluster_pos - pos;
+                };
+            };
+        }
+        _ => layout.node_header_len(),
+    }
+}
+
+/// Rewrites the indices of the node record at `pos`: child indices with `map_child_idx`, and stored value indices with `map_value`.
+///
+/// Panics if a child index no longer fits in the node's child width.
+pub(crate) fn patch_record(data: &mut [u8], index_width: IndexWidth, pos: usize, map_child_idx: &impl Fn(usize) -> usize, map_value: &impl Fn(usize) -> usize) {
+    let idx_bytes = index_width.0;
+    let layout = Layout::new(index_width);
+    let value_idx = index_width.read_idx(data, pos);
+    if value_idx != 0 {
+        index_width.write_idx(data, pos, map_value(value_idx));
+    };
+    let children_present = data[pos + idx_bytes];
+    let child_width = layout.child_width(children_present);
+    let patch_child_idx = |data: &mut [u8], slot_pos: usize| {
+        let child_idx = map_child_idx(child_width.read_idx(data, slot_pos));
+        assert!(child_width.can_represent(child_idx), "child index is too large for the node's child width");
+        child_width.write_idx(data, slot_pos, child_idx);
+    };
+    match children_present & NODE_CHILDREN_MASK {
+        NODE_HAS_SINGLE_CHILD => patch_child_idx(data, pos + layout.node_header_len() + 1),
+        NODE_HAS_CLUSTERS => {
+            let mut cluster_pos = pos + layout.node_header_len();
+            loop {
+                let min = data[cluster_pos + 1];
+                let max = data[cluster_pos + 2];
+                let bitmap_pos = cluster_pos + layout.cluster_header_len();
+                let bitmap_len = cluster_bitmap_len(min, max);
+                let child_count = if bitmap_len == 0 {
+                    1
+                } else {
+                    data[bitmap_pos..bitmap_pos + bitmap_len].iter().map(|b| b.count_ones() as usize).sum()
+                };
+                for i in 0..child_count {
+                    patch_child_idx(data, bitmap_pos + bitmap_len + i * child_width.0);
+                };
+                // Distances to next clusters are relative, so they don't need patching.
+                if data[cluster_pos] == 0 {
+                    break;
+                };
+                cluster_pos = bitmap_pos + bitmap_len + child_count * child_width.0;
+            };
+        }
+        _ => {}
+    };
+}
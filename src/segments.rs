@@ -0,0 +1,81 @@
+use crate::{Fastrie, FastrieBuilderNode, FastrieMatch, StepResult, ValueStore};
+
+// A key of segments is stored as each segment followed by SEPARATOR. Within segments, SEPARATOR and ESCAPE are escaped as ESCAPE followed by ESCAPED_SEPARATOR or ESCAPED_ESCAPE, so SEPARATOR only ever ends a segment and keys match whole segments.
+const SEPARATOR: u8 = 0x00;
+const ESCAPE: u8 = 0x01;
+const ESCAPED_SEPARATOR: u8 = 0x01;
+const ESCAPED_ESCAPE: u8 = 0x02;
+
+/// Calls `f` with each byte of `segment` as stored, excluding the separator.
+fn for_each_escaped(segment: &[u8], mut f: impl FnMut(u8) -> bool) -> bool {
+    for &b in segment {
+        let continued = match b {
+            SEPARATOR => f(ESCAPE) && f(ESCAPED_SEPARATOR),
+            ESCAPE => f(ESCAPE) && f(ESCAPED_ESCAPE),
+            _ => f(b),
+        };
+        if !continued {
+            return false;
+        };
+    };
+    true
+}
+
+impl<V> FastrieBuilderNode<V> {
+    /// Adds a key made of `segments`, e.g. the segments of a path, for querying with [`Fastrie::longest_matching_prefix_segments`]. Segments can contain any bytes, as they're escaped instead of joined by a plain separator byte. Keys added this way shouldn't be mixed with keys added by [`FastrieBuilderNode::add`].
+    pub fn add_segments(&mut self, segments: &[&[u8]], value: V) {
+        let mut key = Vec::new();
+        for segment in segments {
+            for_each_escaped(segment, |b| {
+                key.push(b);
+                true
+            });
+            key.push(SEPARATOR);
+        };
+        self.add(&key, value);
+    }
+}
+
+impl<'v, V: ?Sized, S: ValueStore<'v, V>> Fastrie<'v, '_, V, S> {
+    /// Returns the key added by [`FastrieBuilderNode::add_segments`] with the most segments that are a prefix of `segments`. Only whole segments match. The match's `end` is the index of the last matched segment in `segments`.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use fastrie::*;
+    ///
+    /// let mut builder = FastrieBuilderNode::new(IndexWidth(2));
+    /// builder.add_segments(&[b"api"], 1);
+    /// builder.add_segments(&[b"api", b"users"], 2);
+    /// let build = builder.prebuild().unwrap();
+    ///
+    /// let trie = Fastrie::from_prebuilt(build.index_width, &build.values, &build.data).unwrap();
+    /// let mat = trie.longest_matching_prefix_segments(&[b"api", b"users", b"42"]).unwrap();
+    /// assert_eq!((mat.end, *mat.value), (1, 2));
+    /// // Segments only match whole.
+    /// let mat = trie.longest_matching_prefix_segments(&[b"api", b"user"]).unwrap();
+    /// assert_eq!((mat.end, *mat.value), (0, 1));
+    /// assert!(trie.longest_matching_prefix_segments(&[b"apis"]).is_none());
+    /// ```
+    pub fn longest_matching_prefix_segments(&self, segments: &[&[u8]]) -> Option<FastrieMatch<'v, V>> {
+        let mut cursor = self.cursor();
+        let mut mat = self.root_match();
+        for (i, segment) in segments.iter().enumerate() {
+            if !for_each_escaped(segment, |b| !matches!(cursor.step(b), StepResult::NoChild)) {
+                break;
+            };
+            match cursor.step(SEPARATOR) {
+                StepResult::NoChild => break,
+                StepResult::Descended { terminal_value: Some(value) } => mat = Some(FastrieMatch {
+                    start: 0,
+                    end: i,
+                    len: i + 1,
+                    value,
+                    value_index: cursor.value_index().unwrap(),
+                }),
+                StepResult::Descended { terminal_value: None } => {}
+            };
+        };
+        mat
+    }
+}
@@ -0,0 +1,307 @@
+use std::collections::HashMap;
+use std::error::Error;
+use std::fmt;
+use std::io::{self, Write};
+
+use crate::symbol::push_symbol;
+use crate::{push_cluster_header, AlreadyBuilt, ClusterOptions, FastrieBuilderNode, IndexWidth, Layout, Symbol, NODE_HAS_CLUSTERS, NODE_HAS_PATH, NODE_HAS_SINGLE_CHILD, NODE_HAS_VALUE, StoredValueIdx};
+
+/// Returned by [`FastrieBuilderNode::build_to_writer`].
+#[derive(Debug)]
+pub enum WriteError {
+    /// The builder has already been built, so there's nothing to write.
+    AlreadyBuilt,
+    /// Writing failed. The builder still has its values, so it can be built again.
+    Io(io::Error),
+}
+
+impl fmt::Display for WriteError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            WriteError::AlreadyBuilt => write!(f, "builder has already been built"),
+            WriteError::Io(err) => write!(f, "failed to write data: {}", err),
+        }
+    }
+}
+
+impl Error for WriteError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match self {
+            WriteError::AlreadyBuilt => None,
+            WriteError::Io(err) => Some(err),
+        }
+    }
+}
+
+impl From<AlreadyBuilt> for WriteError {
+    fn from(_: AlreadyBuilt) -> WriteError {
+        WriteError::AlreadyBuilt
+    }
+}
+
+impl From<io::Error> for WriteError {
+    fn from(err: io::Error) -> WriteError {
+        WriteError::Io(err)
+    }
+}
+
+// Each node record is written once, in its final form, so nothing is backpatched. A node's child indices are distances to its children, whose width depends on the lengths of the subtrees before them, so a first pass measures every subtree and chooses each node's child width, and a second pass writes the records. Both `prebuild` and `build_to_writer` build this way.
+
+/// How a node's children are stored in its record.
+enum Children<K> {
+    None,
+    Path(Vec<K>),
+    Single(K),
+    Clusters(Vec<Vec<Option<K>>>),
+}
+
+/// A node's record as chosen by the first pass, and the length of its subtree.
+struct MeasuredRecord<K> {
+    children: Children<K>,
+    child_width: IndexWidth,
+    len: usize,
+    // Records of the nodes following this one, in the order they're written.
+    child_records: Vec<MeasuredRecord<K>>,
+}
+
+impl<K> Drop for MeasuredRecord<K> {
+    fn drop(&mut self) {
+        // Like the builder, records are dropped without recursing once per level.
+        let mut stack = std::mem::take(&mut self.child_records);
+        while let Some(mut record) = stack.pop() {
+            stack.append(&mut record.child_records);
+        };
+    }
+}
+
+/// Writes node records measured by the first pass.
+struct RecordWriter<'w, W> {
+    layout: Layout,
+    // Reused to encode each record.
+    record: Vec<u8>,
+    w: &'w mut W,
+}
+
+impl<W: Write> RecordWriter<'_, W> {
+    /// Writes the records of the subtree of `node`, which was measured as `measured`.
+    fn write_records<V, K: Symbol>(&mut self, node: &FastrieBuilderNode<V, K>, measured: &MeasuredRecord<K>) -> io::Result<()> {
+        let layout = self.layout;
+        // Records are written in depth-first order with an explicit stack instead of recursion, so long keys can't overflow the stack.
+        let mut stack = vec![(node, measured)];
+        while let Some((node, measured)) = stack.pop() {
+            let child_width = measured.child_width;
+            let child_nodes = node.record_child_nodes(&measured.children);
+            let child_offsets = FastrieBuilderNode::<V, K>::child_offsets(layout, &measured.children, child_width, &measured.child_records);
+
+            let record = &mut self.record;
+            record.clear();
+            layout.index_width.push_value_idx(record, node.value_idx);
+            let has_value = if node.value_idx.is_some() { NODE_HAS_VALUE } else { 0 };
+            match &measured.children {
+                Children::None => record.push(has_value),
+                Children::Path(path) => {
+                    record.push(has_value | NODE_HAS_PATH);
+                    record.push(path.len() as u8);
+                    for &c in path {
+                        push_symbol(record, c);
+                    };
+                }
+                Children::Single(c) => {
+                    record.push(has_value | NODE_HAS_SINGLE_CHILD | child_width.children_present_bits());
+                    push_symbol(record, *c);
+                    child_width.push_idx(record, child_offsets[0]);
+                }
+                Children::Clusters(clusters) => {
+                    record.push(has_value | NODE_HAS_CLUSTERS | child_width.children_present_bits());
+                    let mut child_offsets = child_offsets.iter();
+                    for (i, cluster) in clusters.iter().enumerate() {
+                        push_cluster_header(layout, child_width, record, cluster, i + 1 == clusters.len());
+                        for _ in cluster.iter().flatten() {
+                            child_width.push_idx(record, *child_offsets.next().unwrap());
+                        };
+                    };
+                }
+            };
+            self.w.write_all(record)?;
+
+            // Popped in the order the children's records follow this one.
+            stack.extend(child_nodes.into_iter().zip(&measured.child_records).rev());
+        };
+        Ok(())
+    }
+}
+
+/// A node being measured by `_measure`, which is finished once all of its children have been.
+struct MeasureFrame<'n, V, K> {
+    // The char of the node in its parent's children, or `None` for the subtree being measured.
+    c: Option<K>,
+    node: &'n FastrieBuilderNode<V, K>,
+    // The record's children if they don't depend on the children's lengths, and `None` for clusters.
+    children: Option<Children<K>>,
+    // Children still to be measured, popped from the end.
+    unmeasured: Vec<(K, &'n FastrieBuilderNode<V, K>)>,
+    measured: Vec<(K, MeasuredRecord<K>)>,
+}
+
+impl<'n, V, K: Symbol> MeasureFrame<'n, V, K> {
+    fn new(c: Option<K>, node: &'n FastrieBuilderNode<V, K>) -> MeasureFrame<'n, V, K> {
+        let (children, unmeasured) = match node.children.len() {
+            0 => (Some(Children::None), Vec::new()),
+            1 => {
+                let path = node.single_child_path();
+                let c = path[0];
+                let children = if path.len() > 1 { Children::Path(path) } else { Children::Single(c) };
+                let child = node.record_child_nodes(&children)[0];
+                (Some(children), vec![(c, child)])
+            }
+            _ => (None, node.children.iter().map(|(&c, child)| (c, child)).collect()),
+        };
+        MeasureFrame { c, node, children, unmeasured, measured: Vec::new() }
+    }
+
+    /// Chooses the record of the node, with child widths of at least `min_child_width`, now that its children have been measured.
+    fn finish(self, layout: Layout, min_child_width: IndexWidth, options: ClusterOptions<K>) -> MeasuredRecord<K> {
+        let node = self.node;
+        let mut child_widths = (min_child_width.0..=8).map(IndexWidth);
+        let (children, child_width, child_records) = match self.children {
+            Some(Children::None) => (Children::None, min_child_width, Vec::new()),
+            Some(children) => {
+                // The child immediately follows its parent, so a single child's index is the length of the record. Paths have no child index, as the node at their end always follows.
+                let child_width = child_widths.find(|&w| w.can_represent(FastrieBuilderNode::<V, K>::record_len(layout, &children, w))).unwrap();
+                (children, child_width, self.measured.into_iter().map(|(_, child_record)| child_record).collect())
+            }
+            None => {
+                let mut measured_children = self.measured.into_iter().collect::<HashMap<K, MeasuredRecord<K>>>();
+                let children_len = measured_children.values().map(|child_record| child_record.len).sum::<usize>();
+                // Wider child indices make the clusters longer, which moves the children further away, so use the narrowest width that can represent the index of the last child, which follows the subtrees of all the others.
+                let (clusters, child_width) = child_widths.find_map(|w| {
+                    let clusters = node.child_char_clusters(layout, w, options);
+                    let last_child_len = measured_children[clusters.iter().flatten().flatten().last().unwrap()].len;
+                    let clusters = Children::Clusters(clusters);
+                    let last_child_offset = FastrieBuilderNode::<V, K>::record_len(layout, &clusters, w) + children_len - last_child_len;
+                    if w.can_represent(last_child_offset) { Some((clusters, w)) } else { None }
+                }).unwrap();
+                let child_records = match &clusters {
+                    Children::Clusters(chars) => chars.iter().flatten().flatten().map(|c| measured_children.remove(c).unwrap()).collect(),
+                    _ => unreachable!(),
+                };
+                (clusters, child_width, child_records)
+            }
+        };
+        let len = FastrieBuilderNode::<V, K>::record_len(layout, &children, child_width) + child_records.iter().map(|child_record| child_record.len).sum::<usize>();
+        MeasuredRecord { children, child_width, len, child_records }
+    }
+}
+
+impl<V, K: Symbol> FastrieBuilderNode<V, K> {
+    /// Returns the nodes whose records follow this node's record, in the order they're written.
+    fn record_child_nodes(&self, children: &Children<K>) -> Vec<&FastrieBuilderNode<V, K>> {
+        match children {
+            Children::None => Vec::new(),
+            Children::Path(path) => {
+                let mut path_end = self;
+                for c in path {
+                    path_end = &path_end.children[c];
+                };
+                vec![path_end]
+            }
+            Children::Single(c) => vec![&self.children[c]],
+            Children::Clusters(clusters) => clusters.iter().flatten().flatten().map(|c| &self.children[c]).collect(),
+        }
+    }
+
+    fn record_len(layout: Layout, children: &Children<K>, child_width: IndexWidth) -> usize {
+        layout.node_header_len() + match children {
+            Children::None => 0,
+            Children::Path(path) => layout.symbol_path_len::<K>(path.len()),
+            Children::Single(_) => layout.symbol_single_child_len::<K>(child_width),
+            Children::Clusters(clusters) => clusters.iter()
+                .map(|cluster| layout.symbol_cluster_len(cluster.first().unwrap().unwrap(), cluster.last().unwrap().unwrap(), cluster.iter().flatten().count(), child_width))
+                .sum(),
+        }
+    }
+
+    /// Returns the distance from the start of the record to each of the records following it, whose subtrees are `child_records`.
+    fn child_offsets(layout: Layout, children: &Children<K>, child_width: IndexWidth, child_records: &[MeasuredRecord<K>]) -> Vec<usize> {
+        let mut offset = Self::record_len(layout, children, child_width);
+        child_records.iter().map(|child_record| {
+            let child_offset = offset;
+            offset += child_record.len;
+            child_offset
+        }).collect()
+    }
+
+    /// Chooses the record of every node in this subtree, with child widths of at least `min_child_width`, and measures their subtrees.
+    fn _measure(&self, layout: Layout, min_child_width: IndexWidth, options: ClusterOptions<K>) -> MeasuredRecord<K> {
+        // A node's record depends on the lengths of its children's subtrees, so nodes are measured after their children.
+        let mut stack = vec![MeasureFrame::new(None, self)];
+        loop {
+            if let Some((c, child)) = stack.last_mut().unwrap().unmeasured.pop() {
+                stack.push(MeasureFrame::new(Some(c), child));
+                continue;
+            };
+            let frame = stack.pop().unwrap();
+            let c = frame.c;
+            let record = frame.finish(layout, min_child_width, options);
+            match stack.last_mut() {
+                Some(parent) => parent.measured.push((c.unwrap(), record)),
+                None => return record,
+            };
+        };
+    }
+
+    /// Writes the records of this subtree to `w`, with child widths of at least `min_child_width`. Value indices must have been assigned by `_assign_value_indices`.
+    pub(crate) fn _write_records<W: Write>(&self, index_width: IndexWidth, min_child_width: IndexWidth, options: ClusterOptions<K>, w: &mut W) -> io::Result<()> {
+        let layout = Layout::new(index_width);
+        let measured = self._measure(layout, min_child_width, options);
+        RecordWriter {
+            layout,
+            record: Vec::new(),
+            w,
+        }.write_records(self, &measured)
+    }
+
+    /// Builds the trie like [`FastrieBuilderNode::prebuild`], but writes the data to `w` as it's built instead of returning it, so the builder and the whole data don't have to be in memory at once. Returns the values, ordered as by `prebuild`. The data written is identical to the data `prebuild` would return.
+    ///
+    /// Each node is written once with its final indices, which requires a first pass over the builder to measure every subtree. Instead of the data, this holds the chosen layout and subtree length of every node record during the build, in addition to the builder, and `w` is never seeked. Wrap `w` in a `BufWriter` if it's unbuffered, as each record is a separate write.
+    ///
+    /// Returns an error if this builder has already been built, or if writing fails. The values are only taken from the builder once all the data has been written, so after a write error the builder is left unbuilt and can be built again, e.g. to another writer.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use fastrie::*;
+    ///
+    /// let mut builder = FastrieBuilderNode::new(IndexWidth(2));
+    /// builder.add(b"hell", 1);
+    /// builder.add(b"hello", 2);
+    /// builder.add(b"world", 3);
+    /// let mut data = Vec::new();
+    /// let values = builder.build_to_writer(&mut data).unwrap();
+    /// assert!(builder.is_built());
+    ///
+    /// let trie = Fastrie::from_prebuilt(IndexWidth(2), &values, &data).unwrap();
+    /// assert_eq!(trie.verify(), Ok(()));
+    /// assert_eq!(trie.longest_matching_prefix(b"hello!").unwrap().value, &2);
+    /// ```
+    pub fn build_to_writer<W: Write>(&mut self, w: &mut W) -> Result<Vec<V>, WriteError> {
+        if self.built {
+            return Err(WriteError::AlreadyBuilt);
+        };
+        let index_width = self.index_width;
+        // Assign the value indices `_assign_value_indices` would, but leave the values in place until the data has been written.
+        let mut next_idx = 0;
+        self._for_each_node_mut(&mut |node| {
+            node.value_idx = node.value.as_ref().map(|_| {
+                next_idx += 1;
+                StoredValueIdx::new(next_idx - 1)
+            });
+        });
+        w.write_all(&[index_width.header_byte()])?;
+        self._write_records(index_width, IndexWidth(1), self.cluster_options(), w)?;
+        let mut values: Vec<V> = Vec::with_capacity(next_idx);
+        self._for_each_node_mut(&mut |node| values.extend(node.value.take()));
+        self._set_built(true);
+        Ok(values)
+    }
+}
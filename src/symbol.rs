@@ -0,0 +1,73 @@
+use std::fmt::Debug;
+use std::hash::Hash;
+
+/// A unit of keys. Keys are sequences of symbols, and each symbol is stored in built data as `WIDTH` bytes, little endian. Tries are keyed by bytes (`u8`) by default, but wider symbols such as UTF-16 code units (`u16`) avoid re-encoding text to bytes.
+///
+/// # Example
+///
+/// ```
+/// use fastrie::*;
+///
+/// let units = |s: &str| s.encode_utf16().collect::<Vec<u16>>();
+/// let mut builder = FastrieBuilderNode::new_with_symbols(IndexWidth(2));
+/// builder.add(&units("héllo"), 1);
+/// builder.add(&units("hé"), 2);
+/// // Outside the BMP, so this is a surrogate pair of two code units.
+/// builder.add(&units("😀"), 3);
+/// let build = builder.prebuild().unwrap();
+///
+/// let trie = Fastrie::<_, _, u16>::from_prebuilt_with_symbols(build.index_width, &build.values, &build.data).unwrap();
+/// assert_eq!(trie.verify(), Ok(()));
+/// let text = units("héllo wörld 😀");
+/// let matches = trie.scan(&text).map(|m| (m.range(), *m.value)).collect::<Vec<_>>();
+/// assert_eq!(matches, vec![(0..5, 1), (12..14, 3)]);
+/// assert_eq!(trie.longest_matching_prefix(&units("hél")).unwrap().value, &2);
+/// assert!(!trie.contains_key(&units("😀")[..1]));
+/// assert_eq!(trie.keys().collect::<Vec<Vec<u16>>>(), vec![units("hé"), units("héllo"), units("😀")]);
+/// ```
+pub trait Symbol: Copy + Ord + Hash + Debug {
+    /// Amount of bytes used to store a symbol in built data.
+    const WIDTH: usize;
+
+    fn to_usize(self) -> usize;
+
+    fn from_usize(value: usize) -> Self;
+}
+
+macro_rules! impl_symbol {
+    ($($t:ty),*) => {
+        $(
+            impl Symbol for $t {
+                const WIDTH: usize = std::mem::size_of::<$t>();
+
+                #[inline(always)]
+                fn to_usize(self) -> usize {
+                    self as usize
+                }
+
+                #[inline(always)]
+                fn from_usize(value: usize) -> $t {
+                    value as $t
+                }
+            }
+        )*
+    };
+}
+
+impl_symbol!(u8, u16, u32);
+
+pub(crate) fn read_symbol<K: Symbol>(data: &[u8], pos: usize) -> K {
+    let mut value = 0usize;
+    for i in 0..K::WIDTH {
+        value |= (data[pos + i] as usize) << (8 * i);
+    };
+    K::from_usize(value)
+}
+
+pub(crate) fn push_symbol<K: Symbol>(data: &mut Vec<u8>, c: K) {
+    let mut value = c.to_usize();
+    for _ in 0..K::WIDTH {
+        data.push(value as u8);
+        value >>= 8;
+    };
+}
@@ -0,0 +1,89 @@
+use std::fmt::Display;
+use std::io::{self, BufRead, BufReader, Read, Write};
+use std::str::FromStr;
+
+use crate::{Fastrie, FastrieBuild, FastrieBuilderNode, IndexWidth};
+
+// Text format: one `key<TAB>value` line per key, in key order. Keys are bytes, written as is if printable ASCII other than `\`, and as `\xHH` otherwise, so tabs and newlines in keys are escaped. Values are written with `Display` and read with `FromStr`.
+
+fn escape_key(key: &[u8]) -> String {
+    key.iter()
+        .map(|&b| if (b' '..=b'~').contains(&b) && b != b'\\' {
+            (b as char).to_string()
+        } else {
+            format!("\\x{:02x}", b)
+        })
+        .collect()
+}
+
+fn unescape_key(escaped: &str) -> Option<Vec<u8>> {
+    let bytes = escaped.as_bytes();
+    let mut key = Vec::with_capacity(bytes.len());
+    let mut i = 0;
+    while i < bytes.len() {
+        if bytes[i] == b'\\' {
+            if bytes.get(i + 1) != Some(&b'x') {
+                return None;
+            };
+            let hex = escaped.get(i + 2..i + 4)?;
+            key.push(u8::from_str_radix(hex, 16).ok()?);
+            i += 4;
+        } else {
+            key.push(bytes[i]);
+            i += 1;
+        };
+    };
+    Some(key)
+}
+
+fn invalid_line(line_no: usize, reason: &str) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, format!("line {}: {}", line_no, reason))
+}
+
+impl<V: Display> FastrieBuild<V> {
+    /// Writes the keys and values of this build as text, with one `key<TAB>value` line per key in key order, which can be read back with [`FastrieBuilderNode::read_text`]. This is a diffable, human-reviewable source for regenerating the build. Keys are written as is if printable ASCII other than `\`, and each other byte is escaped as `\xHH`. Values are written with `Display`, which must not produce tabs or newlines.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use fastrie::*;
+    ///
+    /// let mut builder = FastrieBuilderNode::new(IndexWidth(2));
+    /// builder.add(b"hello", 2);
+    /// builder.add(b"hell\t", 1);
+    /// let mut text = Vec::new();
+    /// builder.prebuild().unwrap().write_text(&mut text).unwrap();
+    /// assert_eq!(String::from_utf8(text.clone()).unwrap(), "hell\\x09\t1\nhello\t2\n");
+    ///
+    /// let build = FastrieBuilderNode::<u32>::read_text(IndexWidth(2), text.as_slice()).unwrap().prebuild().unwrap();
+    /// let trie = Fastrie::from_prebuilt(build.index_width, &build.values, &build.data).unwrap();
+    /// assert_eq!(trie.get(b"hell\t"), Some(&1));
+    /// assert_eq!(trie.get(b"hello"), Some(&2));
+    /// ```
+    pub fn write_text<W: Write>(&self, mut w: W) -> io::Result<()> {
+        let trie = Fastrie::from_prebuilt_unchecked(self.index_width, &self.values, &self.data);
+        for (key, value) in trie.iter() {
+            writeln!(w, "{}\t{}", escape_key(&key), value)?;
+        };
+        Ok(())
+    }
+}
+
+impl<V: FromStr> FastrieBuilderNode<V> {
+    /// Reads keys and values written by [`FastrieBuild::write_text`] into a new builder with `index_width`. Lines can be in any order, and empty lines are ignored. Returns an error of kind [`io::ErrorKind::InvalidData`] naming the line if a line has no tab, a key has an invalid escape, or a value can't be parsed.
+    pub fn read_text<R: Read>(index_width: IndexWidth, r: R) -> io::Result<FastrieBuilderNode<V>> {
+        let mut builder = FastrieBuilderNode::new(index_width);
+        for (i, line) in BufReader::new(r).lines().enumerate() {
+            let line = line?;
+            if line.is_empty() {
+                continue;
+            };
+            let line_no = i + 1;
+            let (key, value) = line.split_once('\t').ok_or_else(|| invalid_line(line_no, "missing tab"))?;
+            let key = unescape_key(key).ok_or_else(|| invalid_line(line_no, "invalid escape in key"))?;
+            let value = value.parse().map_err(|_| invalid_line(line_no, "invalid value"))?;
+            builder.add(&key, value);
+        };
+        Ok(builder)
+    }
+}
@@ -0,0 +1,244 @@
+use std::marker::PhantomData;
+use std::num::NonZeroUsize;
+
+use crate::chunked::ByteSource;
+use crate::{cluster_bitmap_len, IndexWidth, StoredValueIdx, Symbol, WalkCounters, NODE_CHILDREN_MASK, NODE_HAS_PATH, NODE_HAS_SINGLE_CHILD, NODE_HAS_VALUE, ROOT_POS};
+
+/// An index width known at run time, or at compile time for [`crate::FixedFastrie`].
+pub(crate) trait WalkIndexWidth: Copy {
+    fn width(self) -> usize;
+
+    /// Reads an index from the first `width` bytes of `bytes`.
+    fn read_idx(self, bytes: &[u8]) -> usize;
+
+    fn read_value_idx(self, bytes: &[u8]) -> Option<StoredValueIdx> {
+        NonZeroUsize::new(self.read_idx(bytes)).map(StoredValueIdx)
+    }
+}
+
+impl WalkIndexWidth for IndexWidth {
+    #[inline(always)]
+    fn width(self) -> usize {
+        self.0
+    }
+
+    #[inline(always)]
+    fn read_idx(self, bytes: &[u8]) -> usize {
+        IndexWidth::read_idx(self, bytes, 0)
+    }
+}
+
+/// An index width of `W` bytes, so reads are unrolled for it.
+#[derive(Clone, Copy)]
+pub(crate) struct ConstIndexWidth<const W: usize>;
+
+impl<const W: usize> WalkIndexWidth for ConstIndexWidth<W> {
+    #[inline(always)]
+    fn width(self) -> usize {
+        W
+    }
+
+    #[inline(always)]
+    fn read_idx(self, bytes: &[u8]) -> usize {
+        let mut idx = 0usize;
+        for (i, &b) in bytes[..W].iter().enumerate() {
+            idx |= (b as usize) << (8 * i);
+        };
+        idx
+    }
+}
+
+/// Reads nodes of built data from any [`ByteSource`], so that [`crate::Fastrie`], [`crate::ChunkedFastrie`], and [`crate::FixedFastrie`] share the walk. Reads from a `[u8]` borrow, so this compiles down to direct slice indexing.
+pub(crate) struct Walker<'d, B: ?Sized, K, I = IndexWidth> {
+    pub(crate) data: &'d B,
+    pub(crate) index_width: I,
+    pub(crate) symbol_type: PhantomData<K>,
+}
+
+impl<B: ByteSource + ?Sized, K: Symbol, I: WalkIndexWidth> Walker<'_, B, K, I> {
+    #[inline(always)]
+    fn read_byte(&self, pos: usize) -> u8 {
+        self.data.read(pos, 1)[0]
+    }
+
+    /// Returns the position of the child whose index, of `child_width` bytes, is at `pos` in the node at `node_pos`.
+    #[inline(always)]
+    fn read_child_pos(&self, node_pos: usize, pos: usize, child_width: IndexWidth) -> usize {
+        node_pos + child_width.read_idx(&self.data.read(pos, child_width.0), 0)
+    }
+
+    #[inline(always)]
+    pub(crate) fn read_symbol(&self, pos: usize) -> K {
+        crate::symbol::read_symbol(&self.data.read(pos, K::WIDTH), 0)
+    }
+
+    /// Returns the zero-based value index of the node at `node_pos`, or `None` if it has no value.
+    pub(crate) fn node_value_idx(&self, node_pos: usize) -> Option<usize> {
+        let children_present = self.read_byte(node_pos + self.index_width.width());
+        self.value_idx_of(node_pos, children_present)
+    }
+
+    /// Like `node_value_idx`, but for a node whose children present byte has already been read as `children_present`, so the value index is only read if the node has a value.
+    #[inline(always)]
+    fn value_idx_of(&self, node_pos: usize, children_present: u8) -> Option<usize> {
+        if children_present & NODE_HAS_VALUE == 0 {
+            return None;
+        };
+        self.index_width.read_value_idx(&self.data.read(node_pos, self.index_width.width())).map(StoredValueIdx::value_idx)
+    }
+
+    pub(crate) fn has_children(&self, node_pos: usize) -> bool {
+        self.read_byte(node_pos + self.index_width.width()) & NODE_CHILDREN_MASK != 0
+    }
+
+    pub(crate) fn has_single_child(&self, node_pos: usize) -> bool {
+        self.read_byte(node_pos + self.index_width.width()) & NODE_HAS_SINGLE_CHILD != 0
+    }
+
+    pub(crate) fn has_path(&self, node_pos: usize) -> bool {
+        self.read_byte(node_pos + self.index_width.width()) & NODE_HAS_PATH != 0
+    }
+
+    /// Returns the position of the first char and the length of the path of the node at `node_pos`, which must have a path.
+    pub(crate) fn path(&self, node_pos: usize) -> (usize, usize) {
+        let len_pos = node_pos + self.index_width.width() + 1;
+        (len_pos + 1, self.read_byte(len_pos) as usize)
+    }
+
+    /// Returns the position of the child of the node at `node_pos` for the char `c`, if it exists. The node must have children and not have a path.
+    pub(crate) fn child_in_clusters<C: WalkCounters>(&self, node_pos: usize, c: K, counters: &mut C) -> Option<usize> {
+        let children_present = self.read_byte(node_pos + self.index_width.width());
+        self.child_of(node_pos, children_present, c, counters)
+    }
+
+    /// Like `child_in_clusters`, but for a node whose children present byte has already been read as `children_present`.
+    #[inline(always)]
+    pub(crate) fn child_of<C: WalkCounters>(&self, node_pos: usize, children_present: u8, c: K, counters: &mut C) -> Option<usize> {
+        let idx_bytes = self.index_width.width();
+        let child_width = IndexWidth::of_children_present(children_present);
+        if children_present & NODE_HAS_SINGLE_CHILD != 0 {
+            counters.examine_cluster();
+            if self.read_symbol(node_pos + idx_bytes + 1) != c {
+                return None;
+            };
+            return Some(self.read_child_pos(node_pos, node_pos + idx_bytes + 1 + K::WIDTH, child_width));
+        };
+        let mut cluster_pos: usize = node_pos + idx_bytes + 1;
+        loop {
+            counters.examine_cluster();
+            let next_cluster_dist = self.read_byte(cluster_pos) as usize;
+            let cluster_min: K = self.read_symbol(cluster_pos + 1);
+            let cluster_max: K = self.read_symbol(cluster_pos + 1 + K::WIDTH);
+            if c >= cluster_min && c <= cluster_max {
+                let bitmap_pos = cluster_pos + 1 + 2 * K::WIDTH;
+                if cluster_min == cluster_max {
+                    // Singleton clusters have no gaps or bitmap.
+                    return Some(self.read_child_pos(node_pos, bitmap_pos, child_width));
+                };
+                // Character is in this cluster, but it might be a gap.
+                let bit = c.to_usize() - cluster_min.to_usize();
+                let bitmap = self.data.read(bitmap_pos, bit / 8 + 1);
+                let bitmap_byte = bitmap[bit / 8];
+                if bitmap_byte & (1 << (bit % 8)) == 0 {
+                    // Character is not a child, as its bit is unset which means it's a gap.
+                    return None;
+                };
+                // Only present children have slots, so the slot is at the count of set bits before this char's bit.
+                let preceding = bitmap[..bit / 8].iter().map(|b| b.count_ones()).sum::<u32>()
+                    + (bitmap_byte & ((1 << (bit % 8)) - 1)).count_ones();
+                counters.skip_gaps(bit - preceding as usize);
+                let child_slot_pos = bitmap_pos + cluster_bitmap_len(cluster_min, cluster_max) + preceding as usize * child_width.0;
+                return Some(self.read_child_pos(node_pos, child_slot_pos, child_width));
+            };
+            if next_cluster_dist == 0 {
+                // Distance to the next cluster is zero, which means this is last cluster.
+                return None;
+            };
+            cluster_pos += next_cluster_dist;
+        };
+    }
+
+    /// Returns the zero-based value index of `key` exactly, or `None` if it isn't a key. Each node's children present byte is read once, paths are read as a block, and only the value index of the node reached by the last char is read, not its children.
+    pub(crate) fn exact_value_idx(&self, key: &[K]) -> Option<usize> {
+        self.exact_value_idx_counted(key, &mut ())
+    }
+
+    pub(crate) fn exact_value_idx_counted<C: WalkCounters>(&self, key: &[K], counters: &mut C) -> Option<usize> {
+        let idx_bytes = self.index_width.width();
+        let mut node_pos: usize = ROOT_POS;
+        let mut i = 0;
+        counters.visit_node();
+        while i < key.len() {
+            let children_present = self.read_byte(node_pos + idx_bytes);
+            if children_present & NODE_CHILDREN_MASK == 0 {
+                return None;
+            };
+            if children_present & NODE_HAS_PATH != 0 {
+                let len = self.read_byte(node_pos + idx_bytes + 1) as usize;
+                // Keys never end part way along a path, as nodes along it have no values.
+                if key.len() - i < len {
+                    return None;
+                };
+                counters.examine_cluster();
+                let chars_pos = node_pos + idx_bytes + 2;
+                let chars = self.data.read(chars_pos, len * K::WIDTH);
+                if (0..len).any(|j| crate::symbol::read_symbol::<K>(&chars, j * K::WIDTH) != key[i + j]) {
+                    return None;
+                };
+                i += len;
+                node_pos = chars_pos + len * K::WIDTH;
+            } else {
+                node_pos = self.child_of(node_pos, children_present, key[i], counters)?;
+                i += 1;
+            };
+            counters.visit_node();
+        };
+        self.node_value_idx(node_pos)
+    }
+
+    /// Returns the length and value index of the longest match, and the amount of `text` walked along the trie. The empty key is a prefix of every text, so if the root has a value, it's matched with a length of zero unless a longer key matches.
+    pub(crate) fn longest_matching_prefix_counted<C: WalkCounters>(&self, text: &[K], counters: &mut C) -> (Option<(usize, usize)>, usize) {
+        let idx_bytes = self.index_width.width();
+        let mut node_pos: usize = ROOT_POS;
+        counters.visit_node();
+        // The children present byte says both whether and how a node has children, so it's read once per node.
+        let mut children_present = self.read_byte(node_pos + idx_bytes);
+        let mut match_opt: Option<(usize, usize)> = self.value_idx_of(node_pos, children_present).map(|value_idx| (0, value_idx));
+        if children_present & NODE_CHILDREN_MASK == 0 {
+            return (match_opt, 0);
+        };
+        let mut i = 0;
+        while i < text.len() {
+            if children_present & NODE_HAS_PATH != 0 {
+                counters.examine_cluster();
+                // Match the path as a block, as no node along it has a value.
+                let (chars_pos, len) = self.path(node_pos);
+                for j in 0..len {
+                    if i + j == text.len() || self.read_symbol(chars_pos + j * K::WIDTH) != text[i + j] {
+                        return (match_opt, i + j);
+                    };
+                };
+                i += len;
+                node_pos = chars_pos + len * K::WIDTH;
+            } else {
+                node_pos = match self.child_of(node_pos, children_present, text[i], counters) {
+                    Some(child_pos) => child_pos,
+                    None => break,
+                };
+                i += 1;
+            };
+
+            counters.visit_node();
+            children_present = self.read_byte(node_pos + idx_bytes);
+            if let Some(value_idx) = self.value_idx_of(node_pos, children_present) {
+                match_opt = Some((i, value_idx));
+            };
+            if children_present & NODE_CHILDREN_MASK == 0 {
+                // No longer key can match, so stop without looking at the rest of the text.
+                break;
+            };
+        };
+
+        (match_opt, i)
+    }
+}
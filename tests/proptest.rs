@@ -0,0 +1,80 @@
+#![cfg(feature = "proptest")]
+
+use std::collections::BTreeMap;
+
+use fastrie::*;
+use proptest::prelude::*;
+
+/// Favours the edges of the byte range and a small alphabet, so keys share prefixes and contain 0x00 and 0xFF.
+fn byte() -> impl Strategy<Value = u8> {
+    prop_oneof![Just(0x00), Just(0xFF), b'a'..=b'd', any::<u8>()]
+}
+
+/// Keys can be empty, and are short so that many are prefixes of each other.
+fn key() -> impl Strategy<Value = Vec<u8>> {
+    prop::collection::vec(byte(), 0..7)
+}
+
+/// Builds `byte_keys` mapped through `to_symbol`, which must preserve order, and checks that the trie matches exactly those keys.
+fn check_round_trips<K: Symbol>(byte_keys: &BTreeMap<Vec<u8>, u64>, non_keys: &[Vec<u8>], merge_clusters: bool, to_symbol: fn(u8) -> K) {
+    let expected = byte_keys.iter().map(|(key, value)| (key.iter().copied().map(to_symbol).collect(), *value)).collect::<BTreeMap<Vec<K>, u64>>();
+    let mut builder = FastrieBuilderNode::new_with_symbols(IndexWidth(8));
+    for (key, value) in expected.iter() {
+        builder.add(key, *value);
+    };
+    builder.set_merge_clusters(merge_clusters);
+    let build = builder.prebuild_auto().unwrap();
+    // Values are ordered by key.
+    assert!(build.values.iter().eq(expected.values()));
+    let trie = Fastrie::<_, _, K>::from_prebuilt_with_symbols(build.index_width, &build.values, &build.data).unwrap();
+    assert_eq!(trie.verify(), Ok(()));
+
+    for (i, (key, value)) in expected.iter().enumerate() {
+        assert!(trie.contains_key(key), "missing key {:?}", key);
+        let mat = trie.longest_matching_prefix(key).unwrap();
+        assert_eq!(mat.len, key.len());
+        assert_eq!(mat.value, value);
+        assert_eq!(mat.value_index, i);
+        // Resolving the value index without the values agrees, including as a set.
+        assert_eq!(trie.as_set().longest_matching_prefix_len_resolve(key, |value_idx| value_idx), Some((key.len(), i)));
+        let mat = trie.longest_matching_prefix_fuzzy(key, 1).unwrap();
+        assert_eq!((mat.len, mat.value_index), (key.len(), i));
+    };
+    for key in expected.keys() {
+        // Prefixes can end part way along a path.
+        for len in 0..key.len() {
+            assert!(trie.contains_prefix(&key[..len]), "missing prefix {:?}", &key[..len]);
+            assert_eq!(trie.longest_matching_prefix_with_walk(&key[..len]).1, len);
+        };
+    };
+    for non_key in non_keys {
+        if !byte_keys.contains_key(non_key) {
+            let non_key = non_key.iter().copied().map(to_symbol).collect::<Vec<K>>();
+            assert!(!trie.contains_key(&non_key), "unexpected key {:?}", non_key);
+        };
+    };
+    let entries = trie.iter().map(|(key, value)| (key, *value)).collect::<BTreeMap<Vec<K>, u64>>();
+    assert_eq!(entries, expected);
+    assert!(trie.iter().map(|(key, _)| key).collect::<Vec<Vec<K>>>().windows(2).all(|w| w[0] < w[1]));
+}
+
+proptest! {
+    #[test]
+    fn builder_round_trips_random_keys(
+        keys in prop::collection::btree_map(key(), any::<u64>(), 0..100),
+        non_keys in prop::collection::vec(key(), 0..20),
+        merge_clusters in any::<bool>(),
+    ) {
+        check_round_trips(&keys, &non_keys, merge_clusters, |b| b);
+    }
+
+    #[test]
+    fn builder_round_trips_random_u16_keys(
+        keys in prop::collection::btree_map(key(), any::<u64>(), 0..100),
+        non_keys in prop::collection::vec(key(), 0..20),
+        merge_clusters in any::<bool>(),
+    ) {
+        // Spread bytes to both ends of the u16 range, so clusters and bitmaps cover multi-byte chars including 0xFFFF.
+        check_round_trips(&keys, &non_keys, merge_clusters, |b| if b < 0x80 { b as u16 } else { 0xFF00 | b as u16 });
+    }
+}
@@ -0,0 +1,1794 @@
+use std::collections::{BTreeMap, BTreeSet};
+use std::convert::TryFrom;
+
+use fastrie::*;
+
+// Small xorshift PRNG, so tests are reproducible and need no dependencies. The builder round trips are property tested in `tests/proptest.rs`.
+struct Rng(u64);
+
+impl Rng {
+    fn next(&mut self) -> u64 {
+        self.0 ^= self.0 << 13;
+        self.0 ^= self.0 >> 7;
+        self.0 ^= self.0 << 17;
+        self.0
+    }
+
+    fn below(&mut self, n: usize) -> usize {
+        (self.next() % n as u64) as usize
+    }
+
+    fn byte(&mut self) -> u8 {
+        // Favour the edges of the byte range and a small alphabet, so keys share prefixes and contain 0x00 and 0xFF.
+        match self.below(4) {
+            0 => 0x00,
+            1 => 0xFF,
+            2 => b'a' + self.below(4) as u8,
+            _ => self.next() as u8,
+        }
+    }
+
+    fn key(&mut self, existing: &BTreeMap<Vec<u8>, u64>) -> Vec<u8> {
+        let mut key = match existing.keys().nth(self.below(existing.len().max(1))) {
+            // Extend or truncate an existing key to create keys that are prefixes of each other.
+            Some(base) if self.below(2) == 0 => base[..self.below(base.len() + 1)].to_vec(),
+            _ => Vec::new(),
+        };
+        for _ in 0..1 + self.below(6) {
+            key.push(self.byte());
+        };
+        key
+    }
+}
+
+#[test]
+fn value_indices_at_boundaries() {
+    // 256 values are stored as the value indices 1 to 256, so the last needs both bytes of the index.
+    let mut builder = FastrieBuilderNode::new(IndexWidth(2));
+    for i in 0..255u8 {
+        builder.add(&[b'k', i], i);
+    };
+    builder.add(b"", 255);
+    let build = builder.prebuild().unwrap();
+    // The empty key is the lexicographically first, so it has the first value index.
+    assert_eq!(build.values.first(), Some(&255));
+    assert_eq!(build.values.last(), Some(&254));
+    let trie = Fastrie::from_prebuilt(build.index_width, &build.values, &build.data).unwrap();
+    assert_eq!(trie.verify(), Ok(()));
+    assert_eq!(trie.get(b""), Some(&255));
+    assert_eq!(trie.get(b"k"), None);
+    for i in 0..255u8 {
+        assert_eq!(trie.get(&[b'k', i]), Some(&i));
+    };
+    assert_eq!(trie.iter().count(), 256);
+}
+
+#[test]
+fn long_chains_split_into_paths() {
+    // Chains longer than the maximum path length of 255 chars are split into consecutive paths.
+    let long = (0..700).map(|i| (i % 251) as u8).collect::<Vec<u8>>();
+    let mut builder = FastrieBuilderNode::new(IndexWidth(2));
+    builder.add(&long, 1);
+    builder.add(&long[..300], 2);
+    builder.add(b"", 3);
+    let build = builder.prebuild().unwrap();
+    let trie = Fastrie::from_prebuilt(build.index_width, &build.values, &build.data).unwrap();
+    assert_eq!(trie.verify(), Ok(()));
+    assert_eq!(trie.get(&long), Some(&1));
+    assert_eq!(trie.get(&long[..300]), Some(&2));
+    assert_eq!(trie.get(&long[..299]), None);
+    assert_eq!(trie.classify(&long[..256]), KeyClass::Prefix);
+    let mat = trie.longest_matching_prefix(&long[..699]).unwrap();
+    assert_eq!((mat.end, *mat.value), (299, 2));
+    let mut text = long.clone();
+    text.push(0);
+    assert_eq!(trie.longest_matching_prefix(&text).unwrap().end, 699);
+    assert_eq!(trie.keys().collect::<Vec<Vec<u8>>>(), vec![Vec::new(), long[..300].to_vec(), long.clone()]);
+}
+
+#[test]
+fn chunked_data_matches_flat() {
+    let mut rng = Rng(0x2545_F491_4F6C_DD1D);
+    for _ in 0..50 {
+        let mut keys: BTreeMap<Vec<u8>, u64> = BTreeMap::new();
+        for _ in 0..rng.below(100) {
+            let key = rng.key(&keys);
+            keys.insert(key, rng.next());
+        };
+        let mut builder = FastrieBuilderNode::new(IndexWidth(3));
+        for (key, value) in keys.iter() {
+            builder.add(key, *value);
+        };
+        let build = builder.prebuild().unwrap();
+        let trie = Fastrie::from_prebuilt(build.index_width, &build.values, &build.data).unwrap();
+
+        // Split into small chunks, including empty ones, so that most reads of indices and bitmaps span chunks.
+        let mut chunks: Vec<&[u8]> = Vec::new();
+        let mut rest = build.data.as_slice();
+        while !rest.is_empty() {
+            let (chunk, next) = rest.split_at(rng.below(4).min(rest.len()));
+            chunks.push(chunk);
+            rest = next;
+        };
+        let source = Chunks::new(&chunks).unwrap();
+        assert_eq!(source.len(), build.data.len());
+        let chunked = ChunkedFastrie::new(build.index_width, &build.values, source).unwrap();
+
+        let queries = keys.keys().cloned().chain((0..20).map(|_| rng.key(&keys))).collect::<Vec<Vec<u8>>>();
+        for key in queries {
+            let mut text = key.clone();
+            text.push(rng.byte());
+            assert_eq!(chunked.get(&key), trie.get(&key));
+            assert_eq!(
+                chunked.longest_matching_prefix(&text).map(|m| (m.end, m.value)),
+                trie.longest_matching_prefix(&text).map(|m| (m.end, m.value)),
+            );
+        };
+        assert_eq!(chunked.get(b""), trie.get(b""));
+    };
+    // Data always has a header byte, so chunks without any bytes are rejected instead of failing on the first read.
+    assert_eq!(Chunks::new(&[]).err(), Some(FastrieError::TruncatedData { pos: 0 }));
+    assert_eq!(Chunks::new(&[&[][..], &[]]).err(), Some(FastrieError::TruncatedData { pos: 0 }));
+}
+
+#[test]
+fn empty_trie() {
+    // The data of an empty trie is just the header and a root node without a value or children.
+    let mut builder: FastrieBuilderNode<u64> = FastrieBuilderNode::new(IndexWidth(2));
+    assert!(builder.is_empty());
+    let build = builder.prebuild().unwrap();
+    assert!(build.is_empty());
+    assert_eq!(build.data.len(), Layout::HEADER_LEN + Layout::new(build.index_width).node_header_len());
+    let trie = Fastrie::from_prebuilt(build.index_width, &build.values, &build.data).unwrap();
+    assert_eq!(trie.verify(), Ok(()));
+    for text in [&b""[..], b"x", b"\x00\xFF"].iter() {
+        assert!(trie.longest_matching_prefix(text).is_none());
+        assert!(trie.longest_matching_prefix_at(text, 0).is_none());
+        let (mat, walked) = trie.longest_matching_prefix_with_walk(text);
+        assert!(mat.is_none());
+        assert_eq!(walked, 0);
+        assert!(matches!(trie.try_longest_matching_prefix(text), Ok(None)));
+        assert!(trie.finish(trie.advance(FastriePosition::default(), text)).is_none());
+        assert_eq!(trie.get(text), None);
+        assert_eq!(trie.scan(text).count(), 0);
+        assert_eq!(trie.matches_from(text, 0).count(), 0);
+        let mut found = 0;
+        trie.find_all_with(text, true, |_, _, _| found += 1);
+        assert_eq!(found, 0);
+    };
+    assert!(!trie.contains_key(b"x"));
+    assert!(!trie.contains_key(b""));
+    // No key starts with the empty prefix, as there are no keys.
+    assert!(!trie.contains_prefix(b""));
+    assert!(!trie.contains_prefix(b"x"));
+    assert_eq!(trie.classify(b"x"), KeyClass::None);
+    assert_eq!(trie.keys().count(), 0);
+    assert_eq!(trie.iter().count(), 0);
+    assert_eq!(trie.as_set().keys().count(), 0);
+
+    let chunks = [&build.data[..1], &build.data[1..]];
+    let chunked = ChunkedFastrie::new(build.index_width, &build.values, Chunks::new(&chunks).unwrap()).unwrap();
+    assert!(chunked.longest_matching_prefix(b"x").is_none());
+    assert_eq!(chunked.get(b""), None);
+
+    let mut growable = GrowableFastrie::new(Fastrie::from_prebuilt(build.index_width, &build.values, &build.data).unwrap());
+    assert!(growable.longest_matching_prefix(b"x").is_none());
+    growable.add(b"x", 1);
+    assert!(growable.contains_key(b"x"));
+
+    let packed = FastrieBuilderNode::<Vec<u8>>::new(IndexWidth(2)).prebuild().unwrap().pack();
+    assert!(from_prebuilt_packed(&packed).longest_matching_prefix(b"x").is_none());
+    assert_eq!(OwnedFastrie::<Vec<u8>>::try_from(packed.as_slice()).unwrap().as_fastrie().keys().count(), 0);
+
+    let build = FastrieBuilderNode::<u64>::new(IndexWidth(8)).prebuild_auto().unwrap();
+    assert_eq!(build.index_width, IndexWidth(1));
+    assert_eq!(from_prebuilt_without_values(build.index_width, &build.data).keys().count(), 0);
+}
+
+fn check_fixed_matches_dynamic<const W: usize>(rng: &mut Rng) {
+    let mut keys: BTreeMap<Vec<u8>, u64> = BTreeMap::new();
+    for _ in 0..rng.below(100) {
+        let key = rng.key(&keys);
+        keys.insert(key, rng.next());
+    };
+    let mut builder = FastrieBuilderNode::new(IndexWidth(W));
+    for (key, value) in keys.iter() {
+        builder.add(key, *value);
+    };
+    let build = builder.prebuild().unwrap();
+    let trie = Fastrie::from_prebuilt(build.index_width, &build.values, &build.data).unwrap();
+    let fixed = FixedFastrie::<_, W>::from_prebuilt(&build.values, &build.data).unwrap();
+    let queries = keys.keys().cloned().chain((0..20).map(|_| rng.key(&keys))).collect::<Vec<Vec<u8>>>();
+    for key in queries {
+        assert_eq!(fixed.get(&key), trie.get(&key));
+        assert_eq!(
+            fixed.longest_matching_prefix(&key).map(|m| (m.end, m.value, m.value_index)),
+            trie.longest_matching_prefix(&key).map(|m| (m.end, m.value, m.value_index)),
+        );
+    };
+}
+
+#[test]
+fn fixed_width_matches_dynamic() {
+    let mut rng = Rng(0xD1B5_4A32_D192_ED03);
+    for _ in 0..20 {
+        check_fixed_matches_dynamic::<2>(&mut rng);
+        check_fixed_matches_dynamic::<3>(&mut rng);
+        check_fixed_matches_dynamic::<4>(&mut rng);
+        check_fixed_matches_dynamic::<8>(&mut rng);
+    };
+}
+
+#[test]
+fn concat_matches_union() {
+    let mut rng = Rng(0x94D0_49BB_1331_11EB);
+    for round in 0..50 {
+        let mut keys: BTreeMap<Vec<u8>, u64> = BTreeMap::new();
+        for _ in 0..rng.below(200) {
+            let key = rng.key(&keys);
+            keys.insert(key, rng.next());
+        };
+        // Shard by the first byte, or the first two bytes for some first bytes, so routing has clusters, single children, and paths.
+        let shard_prefix = |key: &[u8]| key[..if key[0] < 0x80 { 1 } else { key.len().min(2) }].to_vec();
+        let mut shard_builders: BTreeMap<Vec<u8>, FastrieBuilderNode<u64>> = BTreeMap::new();
+        let mut union = FastrieBuilderNode::new(IndexWidth(3));
+        for (key, value) in keys.iter() {
+            // Keys that are themselves a two-byte-shard prefix would overlap with their own shard, so they're left out.
+            if key[0] >= 0x80 && key.len() <= 2 {
+                continue;
+            };
+            let prefix = shard_prefix(key);
+            shard_builders.entry(prefix.clone()).or_insert_with(|| FastrieBuilderNode::new(IndexWidth(3))).add(&key[prefix.len()..], *value);
+            union.add(key, *value);
+        };
+        if round % 10 == 0 {
+            // An empty shard contributes nothing.
+            shard_builders.entry(b"\xFE\xFE".to_vec()).or_insert_with(|| FastrieBuilderNode::new(IndexWidth(3)));
+        };
+        if shard_builders.is_empty() {
+            continue;
+        };
+        let mut shards = shard_builders.into_iter().map(|(prefix, mut builder)| (prefix, builder.prebuild().unwrap())).collect::<Vec<_>>();
+        // Order of shards doesn't matter.
+        shards.reverse();
+        let build = FastrieBuild::concat(shards);
+        let union = union.prebuild().unwrap();
+        assert_eq!(build.values, union.values);
+
+        let trie = Fastrie::from_prebuilt(build.index_width, &build.values, &build.data).unwrap();
+        let union = Fastrie::from_prebuilt(union.index_width, &union.values, &union.data).unwrap();
+        assert_eq!(trie.verify(), Ok(()));
+        assert_eq!(trie.iter().map(|(key, value)| (key, *value)).collect::<Vec<_>>(), union.iter().map(|(key, value)| (key, *value)).collect::<Vec<_>>());
+        let queries = keys.keys().cloned().chain((0..20).map(|_| rng.key(&keys))).collect::<Vec<Vec<u8>>>();
+        for key in queries {
+            assert_eq!(trie.get(&key), union.get(&key));
+            assert_eq!(
+                trie.longest_matching_prefix(&key).map(|m| (m.end, m.value, m.value_index)),
+                union.longest_matching_prefix(&key).map(|m| (m.end, m.value, m.value_index)),
+            );
+        };
+    };
+}
+
+#[test]
+fn text_round_trips() {
+    let mut rng = Rng(0xBF58_476D_1CE4_E5B9);
+    for _ in 0..50 {
+        let mut keys: BTreeMap<Vec<u8>, u64> = BTreeMap::new();
+        for _ in 0..rng.below(100) {
+            let key = rng.key(&keys);
+            keys.insert(key, rng.next());
+        };
+        let mut builder = FastrieBuilderNode::new(IndexWidth(3));
+        for (key, value) in keys.iter() {
+            builder.add(key, *value);
+        };
+        let build = builder.prebuild().unwrap();
+        let mut text = Vec::new();
+        build.write_text(&mut text).unwrap();
+        assert_eq!(text.iter().filter(|&&b| b == b'\n').count(), keys.len());
+
+        let rebuilt = FastrieBuilderNode::<u64>::read_text(IndexWidth(3), text.as_slice()).unwrap().prebuild().unwrap();
+        assert_eq!(rebuilt.data, build.data);
+        assert_eq!(rebuilt.values, build.values);
+        let trie = Fastrie::from_prebuilt(rebuilt.index_width, &rebuilt.values, &rebuilt.data).unwrap();
+        for (key, value) in keys.iter() {
+            assert_eq!(trie.get(key), Some(value));
+        };
+    };
+
+    for bad in ["no tab\n", "\\x4\t1\n", "\\y41\t1\n", "a\tnot a number\n"].iter() {
+        let err = FastrieBuilderNode::<u64>::read_text(IndexWidth(3), bad.as_bytes()).err().unwrap();
+        assert_eq!(err.kind(), std::io::ErrorKind::InvalidData);
+        assert!(err.to_string().starts_with("line 1: "));
+    };
+}
+
+#[test]
+fn near_identical_keys() {
+    // Keys that are strict prefixes of each other, and keys that differ only in their last byte, including the bytes at the edges of the byte range, which can also appear along paths.
+    let keys: Vec<&[u8]> = vec![
+        b"hell", b"hello", b"world", b"worle", b"worl\x00", b"worl\xFF", b"\x00\x00\x00", b"\x00\x00\x00\x00", b"\x00\x00\x01",
+        b"\xFF\xFF\xFF\xFF\xFF\xFE", b"\xFF\xFF\xFF\xFF\xFF\xFF", b"\xFF\xFF",
+    ];
+    let mut builder = FastrieBuilderNode::new(IndexWidth(2));
+    for (i, key) in keys.iter().enumerate() {
+        builder.add(key, i);
+    };
+    let build = builder.prebuild().unwrap();
+    let trie = Fastrie::from_prebuilt(build.index_width, &build.values, &build.data).unwrap();
+    assert_eq!(trie.verify(), Ok(()));
+    for (i, key) in keys.iter().enumerate() {
+        assert_eq!(trie.get(key), Some(&i));
+        let mat = trie.longest_matching_prefix(key).unwrap();
+        assert_eq!((mat.end, *mat.value), (key.len() - 1, i));
+        // A following byte doesn't change the match.
+        let mut text = key.to_vec();
+        text.push(b'!');
+        let mat = trie.longest_matching_prefix(&text).unwrap();
+        assert_eq!((mat.end, *mat.value), (key.len() - 1, i));
+    };
+    let mat = trie.longest_matching_prefix(b"hellx").unwrap();
+    assert_eq!((mat.end, *mat.value), (3, 0));
+    assert!(trie.longest_matching_prefix(b"worlf").is_none());
+    assert!(trie.longest_matching_prefix(b"worl").is_none());
+    let mat = trie.longest_matching_prefix(b"\xFF\xFF\xFF\xFF\xFF").unwrap();
+    assert_eq!((mat.end, *mat.value), (1, 11));
+    assert_eq!(trie.get(b"\x00\x00"), None);
+}
+
+#[test]
+fn prebuild_twice_errs() {
+    let mut builder = FastrieBuilderNode::new(IndexWidth(2));
+    builder.add(b"hell", 1);
+    builder.add(b"hello", 2);
+    assert!(!builder.is_built());
+    let build = builder.prebuild().unwrap();
+    assert!(builder.is_built());
+    assert_eq!(builder.prebuild().err(), Some(AlreadyBuilt));
+    assert_eq!(builder.prebuild_with_offsets().err(), Some(AlreadyBuilt));
+    assert_eq!(builder.prebuild_dedup().err(), Some(AlreadyBuilt));
+    assert_eq!(builder.prebuild_auto().err(), Some(AlreadyBuilt));
+    assert_eq!(builder.prebuild_weighted(|_| 1).err(), Some(AlreadyBuilt));
+    assert_eq!(builder.prebuild_widths(&[IndexWidth(2)]).err(), Some(AlreadyBuilt));
+    // The first build is unaffected.
+    let trie = Fastrie::from_prebuilt(build.index_width, &build.values, &build.data).unwrap();
+    assert_eq!(trie.get(b"hello"), Some(&2));
+}
+
+#[test]
+fn fuzzy_tolerates_deletions() {
+    let mut builder = FastrieBuilderNode::new(IndexWidth(2));
+    builder.add(b"hello", 1);
+    builder.add(b"world", 2);
+    builder.add(b"wo", 3);
+    let build = builder.prebuild().unwrap();
+    let trie = Fastrie::from_prebuilt(build.index_width, &build.values, &build.data).unwrap();
+    let fuzzy = |text: &[u8], max_deletions| trie.longest_matching_prefix_fuzzy(text, max_deletions).map(|mat| (mat.end, *mat.value));
+    assert_eq!(fuzzy(b"helo", 1), Some((3, 1)));
+    assert_eq!(fuzzy(b"helo", 0), None);
+    assert_eq!(fuzzy(b"hllo", 1), Some((3, 1)));
+    assert_eq!(fuzzy(b"hel", 2), Some((2, 1)));
+    assert_eq!(fuzzy(b"hel", 1), None);
+    assert_eq!(fuzzy(b"xhello", 1), Some((5, 1)));
+    assert_eq!(fuzzy(b"wo", 1), Some((1, 3)));
+    // Longer matches are preferred even if they need more deletions.
+    assert_eq!(fuzzy(b"worl", 1), Some((3, 2)));
+    assert_eq!(fuzzy(b"wrld", 1), Some((3, 2)));
+    // Only "wo" is within one deletion, with the "o" missing.
+    assert_eq!(fuzzy(b"wrd", 1), Some((0, 3)));
+    assert_eq!(fuzzy(b"wrd", 2), Some((2, 2)));
+}
+
+#[test]
+fn content_hash_detects_corruption() {
+    let mut builder = FastrieBuilderNode::new(IndexWidth(2));
+    builder.add(b"hell", b"underworld".to_vec());
+    builder.add(b"hello", b"greeting".to_vec());
+    let build = builder.prebuild().unwrap();
+    let packed = build.pack();
+    assert!(OwnedFastrie::<Vec<u8>>::try_from(packed.as_slice()).is_ok());
+    for pos in 0..build.data.len() {
+        let mut corrupt = FastrieBuild { data: build.data.clone(), index_width: build.index_width, values: build.values.clone() };
+        corrupt.data[pos] ^= 1;
+        assert_ne!(corrupt.content_hash(), build.content_hash());
+        // The data starts after the header, which ends with the data length.
+        let mut corrupt = packed.clone();
+        corrupt[21 + pos] ^= 1;
+        assert_eq!(OwnedFastrie::<Vec<u8>>::try_from(corrupt).err(), Some(FastrieError::ContentHashMismatch));
+    };
+    // Moving a byte between adjacent values changes the hash.
+    let moved = FastrieBuild { data: build.data.clone(), index_width: build.index_width, values: vec![b"underworldg".to_vec(), b"reeting".to_vec()] };
+    assert_ne!(moved.content_hash(), build.content_hash());
+}
+
+#[test]
+fn packed_lengths_are_checked() {
+    let mut builder = FastrieBuilderNode::new(IndexWidth(8));
+    builder.add(b"a", b"x".to_vec());
+    let (mut build, offsets) = builder.prebuild_with_offsets().unwrap();
+    let packed = build.pack();
+    // The value count follows the data, whose length is at 17.
+    let values_len_pos = 21 + build.data.len();
+    let mut corrupt = packed.clone();
+    corrupt[values_len_pos..values_len_pos + 4].copy_from_slice(&u32::MAX.to_le_bytes());
+    assert!(OwnedFastrie::<Vec<u8>>::try_from(corrupt).is_err());
+    let mut corrupt = packed.clone();
+    corrupt[17..21].copy_from_slice(&u32::MAX.to_le_bytes());
+    assert!(OwnedFastrie::<Vec<u8>>::try_from(corrupt).is_err());
+
+    // A value index near `usize::MAX` is out of range instead of overflowing.
+    let slot = offsets[&b"a".to_vec()];
+    build.data[slot..slot + 8].copy_from_slice(&u64::MAX.to_le_bytes());
+    let packed = build.pack();
+    let trie = from_prebuilt_packed(&packed);
+    assert_eq!(trie.try_longest_matching_prefix(b"a").err(), Some(FastrieError::ValueIndexOutOfRange { idx: usize::MAX - 1, len: 1 }));
+}
+
+#[test]
+fn packed_metadata_roundtrips() {
+    let mut builder = FastrieBuilderNode::new(IndexWidth(2));
+    builder.add(b"hell", b"underworld".to_vec());
+    builder.add(b"hello", b"greeting".to_vec());
+    let build = builder.prebuild().unwrap();
+    let plain = build.pack();
+    for metadata in [&b""[..], b"v2 built from entities.json", &[0, 255, 0, 1]].iter() {
+        let packed = build.pack_with_metadata(metadata);
+        // Metadata is appended, so the data and values sections are unchanged.
+        assert_eq!(packed[..plain.len() - 4], plain[..plain.len() - 4]);
+        assert_eq!(from_prebuilt_packed(&packed).longest_matching_prefix(b"hello!").unwrap().value, b"greeting");
+        let mut owned = OwnedFastrie::<Vec<u8>>::try_from(packed.as_slice()).unwrap();
+        assert_eq!(owned.metadata(), *metadata);
+        assert_eq!(owned.values(), &build.values[..]);
+        assert_eq!(owned.to_bytes(), packed);
+        owned.set_metadata(b"replaced".to_vec());
+        assert_eq!(OwnedFastrie::<Vec<u8>>::try_from(owned.to_bytes()).unwrap().metadata(), b"replaced");
+        // The metadata length must match the rest of the buffer.
+        assert!(OwnedFastrie::<Vec<u8>>::try_from(&packed[..packed.len() - 1]).is_err());
+    };
+    assert_eq!(build.pack_with_metadata(b""), plain);
+    assert!(OwnedFastrie::from(build).metadata().is_empty());
+}
+
+#[test]
+fn decoded_values_from_length_prefixed_region() {
+    let mut builder = FastrieBuilderNode::new(IndexWidth(2));
+    for (i, key) in ["a", "ab", "abc", "b", "bcd", "zz"].iter().enumerate() {
+        builder.add(key.as_bytes(), format!("{}-{}", key, "x".repeat(i * 50)));
+    };
+    let build = builder.prebuild().unwrap();
+    // The value region holds each value as a u32 length then its bytes, in value index order, like a value section of a mapped file.
+    let mut region = Vec::new();
+    for v in build.values.iter() {
+        region.extend_from_slice(&(v.len() as u32).to_le_bytes());
+        region.extend_from_slice(v.as_bytes());
+    };
+    let mut starts = Vec::new();
+    let mut pos = 0;
+    while pos < region.len() {
+        starts.push(pos);
+        pos += 4 + u32::from_le_bytes([region[pos], region[pos + 1], region[pos + 2], region[pos + 3]]) as usize;
+    };
+    let decode = |idx: usize| {
+        let len = u32::from_le_bytes([region[starts[idx]], region[starts[idx] + 1], region[starts[idx] + 2], region[starts[idx] + 3]]) as usize;
+        std::str::from_utf8(&region[starts[idx] + 4..starts[idx] + 4 + len]).unwrap()
+    };
+    let decoded = Fastrie::from_prebuilt_with_decoder(build.index_width, &build.data, decode).unwrap();
+    let sliced = Fastrie::from_prebuilt(build.index_width, &build.values, &build.data).unwrap();
+    for text in ["abcd", "ab", "a", "bc", "bcde", "zz", "z", ""].iter() {
+        let text = text.as_bytes();
+        assert_eq!(decoded.get(text), sliced.get(text).map(|v| v.as_str()));
+        assert_eq!(
+            decoded.longest_matching_prefix(text).map(|m| (m.end, m.value)),
+            sliced.longest_matching_prefix(text).map(|m| (m.end, m.value.as_str())),
+        );
+    };
+    assert_eq!(decoded.iter().map(|(k, v)| (k, v.to_string())).collect::<Vec<_>>(), sliced.iter().map(|(k, v)| (k, v.clone())).collect::<Vec<_>>());
+    assert!(Fastrie::from_prebuilt_with_decoder(IndexWidth(3), &build.data, decode).is_err());
+}
+
+#[test]
+fn very_long_key_builds_without_overflowing() {
+    // Building and dropping used to recurse once per char, which overflowed the stack for a key this long.
+    let key = vec![b'a'; 100_000];
+    let mut builder = FastrieBuilderNode::new(IndexWidth(4));
+    builder.add(&key, 0);
+    builder.add(&key[..50_000], 1);
+    builder.add(&[&key[..70_000], &b"b"[..]].concat(), 2);
+    assert_eq!(builder.len(), 3);
+    assert!(builder.heap_size() > 0);
+    let build = builder.prebuild().unwrap();
+    let trie = Fastrie::from_prebuilt(build.index_width, &build.values, &build.data).unwrap();
+    assert_eq!(trie.get(&key), Some(&0));
+    assert_eq!(trie.get(&key[..50_000]), Some(&1));
+    assert_eq!(trie.get(&key[..50_001]), None);
+    let mut text = key.clone();
+    text.push(b'!');
+    assert_eq!(trie.longest_matching_prefix(&text).map(|m| (m.end, *m.value)), Some((99_999, 0)));
+    assert_eq!(trie.longest_matching_prefix(&key[..80_000]).map(|m| (m.end, *m.value)), Some((49_999, 1)));
+    assert_eq!(trie.keys().count(), 3);
+    assert_eq!(trie.verify(), Ok(()));
+
+    let mut builder = FastrieBuilderNode::new(IndexWidth(4));
+    builder.add(&key, 0);
+    builder.add(&key[..50_000], 1);
+    builder.add(&[&key[..70_000], &b"b"[..]].concat(), 2);
+    let mut data = Vec::new();
+    assert_eq!(builder.build_to_writer(&mut data).unwrap(), vec![1, 0, 2]);
+    assert_eq!(data, build.data);
+    drop(builder);
+
+    let mut builder = FastrieBuilderNode::new(IndexWidth(4));
+    builder.add(&key, 0);
+    builder.add(&key[..1], 0);
+    assert_eq!(builder.duplicate_values(), vec![(key[..1].to_vec(), key.clone())]);
+    let build = builder.prebuild_auto().unwrap();
+    let trie = Fastrie::from_prebuilt(build.index_width, &build.values, &build.data).unwrap();
+    assert_eq!(trie.get(&key), Some(&0));
+}
+
+#[test]
+fn deep_trie_verifies_without_overflowing() {
+    // Every prefix has a value, so there are no paths and each char is its own node. Verifying used to recurse once per node.
+    // Tracked keys hold every prefix in full, which takes quadratic memory, so the trie is kept shallow when they're tracked.
+    let depth = if cfg!(feature = "track-keys") { 2_000 } else { 100_000 };
+    let key = vec![b'a'; depth];
+    // Built from the deepest node up, as adding every prefix from the root would take quadratic time.
+    let mut builder = FastrieBuilderNode::new(IndexWidth(4));
+    for len in (1..=key.len()).rev() {
+        let mut parent = FastrieBuilderNode::new(IndexWidth(4));
+        parent.add(b"", len.to_le_bytes().to_vec());
+        parent.replace_subtree(b"a", builder);
+        builder = parent;
+    };
+    let mut root = FastrieBuilderNode::new(IndexWidth(4));
+    root.replace_subtree(b"a", builder);
+    assert_eq!(root.len(), depth);
+    let packed = root.prebuild_auto().unwrap().pack();
+    let owned = OwnedFastrie::<Vec<u8>>::try_from(packed.as_slice()).unwrap();
+    assert_eq!(owned.as_fastrie().get(&key).map(|v| v.as_slice()), Some(&key.len().to_le_bytes()[..]));
+    assert_eq!(owned.as_fastrie().longest_matching_prefix(&key[..1234]).map(|m| m.len), Some(1234));
+}
+
+#[test]
+fn matches_from_skips_keys_without_values() {
+    let mut builder = FastrieBuilderNode::new(IndexWidth(2));
+    builder.add_unique(b"hell");
+    builder.add_unique(b"hello");
+    let build = builder.prebuild().unwrap();
+    let trie = Fastrie::from_prebuilt(build.index_width, &build.values, &build.data).unwrap();
+    let text = b"hellow";
+    assert_eq!(trie.matches_from(text, 0).map(|m| (m.range(), m.value_index)).collect::<Vec<_>>(), vec![(0..4, 0), (0..5, 1)]);
+    // Sets don't have values to match with.
+    assert_eq!(trie.as_set().matches_from(text, 0).count(), 0);
+    assert_eq!(from_prebuilt_without_values(build.index_width, &build.data).matches_from(text, 0).count(), 0);
+    let mut found = 0;
+    trie.as_set().find_all_with(text, true, |_, _, _| found += 1);
+    assert_eq!(found, 0);
+}
+
+#[test]
+fn matched_prefix_len_is_zero_without_match() {
+    let mut builder = FastrieBuilderNode::new(IndexWidth(2));
+    builder.add(b"hell", 1);
+    builder.add(b"hello", 2);
+    let build = builder.prebuild().unwrap();
+    let trie = Fastrie::from_prebuilt(build.index_width, &build.values, &build.data).unwrap();
+    let set = from_prebuilt_without_values(build.index_width, &build.data);
+    for (text, len) in [("hello", 5), ("hello world", 5), ("hellish", 4), ("help", 0), ("", 0), ("world", 0)].iter() {
+        assert_eq!(trie.matched_prefix_len(text.as_bytes()), *len);
+        assert_eq!(set.matched_prefix_len(text.as_bytes()), *len);
+        assert_eq!(trie.matched_prefix_len(text.as_bytes()), trie.longest_matching_prefix_len(text.as_bytes()).map_or(0, |end| end + 1));
+    };
+}
+
+#[test]
+fn fixed_len_matches_keys() {
+    let mut rng = Rng(0xA54F_F53A_5F1D_36F1);
+    // Enough keys for each amount of leading levels to be flattened, and keys short enough to have no tails.
+    for &(key_len, count) in [(6, 0), (6, 1), (6, 50), (6, 2000), (6, 300_000), (1, 200), (2, 3000)].iter() {
+        let mut keys: BTreeMap<Vec<u8>, u64> = BTreeMap::new();
+        while keys.len() < count {
+            keys.insert((0..key_len).map(|_| rng.byte()).collect(), rng.next());
+        };
+        let mut builder = FastrieBuilderNode::new(IndexWidth(4));
+        for (key, value) in keys.iter() {
+            builder.add(key, *value);
+        };
+        let build = builder.prebuild_fixed_len(key_len).unwrap();
+        assert!(builder.is_built());
+        assert_eq!(build.values, keys.values().copied().collect::<Vec<_>>());
+        let trie = FixedLenFastrie::from_prebuilt(&build.values, &build.data).unwrap();
+        assert_eq!((trie.key_len(), trie.len()), (key_len, count));
+        assert_eq!(Fastrie::from_prebuilt(IndexWidth(4), &build.values, &build.data).err(), Some(FastrieError::BadMagic));
+        for (i, (key, value)) in keys.iter().enumerate() {
+            assert_eq!(trie.key_index(key), Some(i));
+            assert_eq!(trie.get(key), Some(value));
+        };
+        for _ in 0..1000 {
+            let query: Vec<u8> = (0..key_len).map(|_| rng.byte()).collect();
+            assert_eq!(trie.get(&query), keys.get(&query));
+            assert_eq!(trie.contains_key(&query), keys.contains_key(&query));
+        };
+        let mut truncated = build.data.clone();
+        truncated.pop();
+        assert!(FixedLenFastrie::from_prebuilt(&build.values, &truncated).is_err());
+    };
+    let mut builder = FastrieBuilderNode::new(IndexWidth(4));
+    builder.add(b"abc", ());
+    builder.prebuild_fixed_len(3).unwrap();
+    assert_eq!(builder.prebuild_fixed_len(3).err(), Some(AlreadyBuilt));
+}
+
+#[test]
+fn node_at_walks_whole_structure() {
+    let mut rng = Rng(0x510E_527F_ADE6_82D1);
+    let mut keys: BTreeMap<Vec<u8>, u64> = BTreeMap::new();
+    for _ in 0..300 {
+        let key = rng.key(&keys);
+        keys.insert(key, rng.next());
+    };
+    let mut builder = FastrieBuilderNode::new(IndexWidth(2));
+    for (key, value) in keys.iter() {
+        builder.add(key, *value);
+    };
+    let (build, offsets) = builder.prebuild_with_offsets().unwrap();
+    let trie = Fastrie::from_prebuilt(build.index_width, &build.values, &build.data).unwrap();
+
+    // The root is at the start of the nodes, after the header, and its children are the distinct first bytes.
+    let root = trie.node_at(Layout::HEADER_LEN);
+    let first_bytes = keys.keys().filter_map(|key| key.first().copied()).collect::<BTreeSet<u8>>();
+    assert_eq!(root.children.iter().map(|&(c, _)| c).collect::<BTreeSet<u8>>(), first_bytes);
+    assert_eq!(root.value_idx, keys.keys().position(|key| key.is_empty()));
+
+    // Following children and paths from the root reaches every key's node at its offset, with its value index.
+    let mut found = BTreeMap::new();
+    let mut stack = vec![(Vec::new(), Layout::HEADER_LEN)];
+    while let Some((key, pos)) = stack.pop() {
+        let node = trie.node_at(pos);
+        assert_eq!(node.children_present, build.data[pos + build.index_width.0]);
+        if let Some(value_idx) = node.value_idx {
+            assert_eq!(offsets[&key], pos);
+            found.insert(key.clone(), build.values[value_idx]);
+        };
+        if let Some((path, end)) = node.path {
+            assert!(node.children.is_empty());
+            stack.push(([key.as_slice(), &path].concat(), end));
+        };
+        for (c, child_pos) in node.children {
+            stack.push(([key.as_slice(), &[c]].concat(), child_pos));
+        };
+    };
+    assert_eq!(found, keys);
+}
+
+#[test]
+fn add_unique_detects_duplicates() {
+    let mut builder = FastrieBuilderNode::new(IndexWidth(2));
+    assert!(builder.add_unique(b"hello"));
+    assert!(!builder.add_unique(b"hello"));
+    // A key's node can exist without a value as part of a longer key, so it's still new.
+    assert!(builder.add_unique(b"hello world"));
+    assert!(builder.add_unique(b"hell"));
+    assert!(!builder.add_unique(b"hell"));
+    assert!(builder.add_unique(b""));
+    assert!(!builder.add_unique(b""));
+    builder.add(b"world", ());
+    assert!(!builder.add_unique(b"world"));
+    let build = builder.prebuild().unwrap();
+    let trie = from_prebuilt_without_values(build.index_width, &build.data);
+    assert_eq!(trie.keys().collect::<Vec<Vec<u8>>>(), vec![b"".to_vec(), b"hell".to_vec(), b"hello".to_vec(), b"hello world".to_vec(), b"world".to_vec()]);
+}
+
+#[test]
+fn resumable_matches_whole_path() {
+    let mut builder = FastrieBuilderNode::new(IndexWidth(2));
+    for (i, key) in ["/", "/a", "/a/b", "/a/bc/d", "/b"].iter().enumerate() {
+        builder.add(key.as_bytes(), i);
+    };
+    let build = builder.prebuild().unwrap();
+    let trie = Fastrie::from_prebuilt(build.index_width, &build.values, &build.data).unwrap();
+    let summarize = |mat: Option<FastrieMatch<usize>>| mat.map(|mat| (mat.end, mat.value_index));
+    for path in ["/a/b", "/a/bc/d/e", "/a/x", "/b/a", "/c"].iter() {
+        let path = path.as_bytes();
+        let whole = summarize(trie.longest_matching_prefix(path));
+        // Feed each segment, starting at its slash.
+        let mut pos = None;
+        let mut segmented = None;
+        let mut start = 0;
+        while start < path.len() {
+            let end = path[start + 1..].iter().position(|&c| c == b'/').map_or(path.len(), |i| start + 1 + i);
+            let (mat, next) = trie.longest_matching_prefix_resumable(&path[start..end], pos);
+            segmented = summarize(mat);
+            pos = Some(next);
+            start = end;
+        };
+        assert_eq!(segmented, whole, "{:?}", path);
+    };
+}
+
+#[test]
+fn entry_inserts_or_modifies() {
+    let mut builder = FastrieBuilderNode::new(IndexWidth(2));
+    builder.add(b"hello", 1);
+    // Existing key.
+    assert_eq!(*builder.entry(b"hello").or_insert(10), 1);
+    *builder.entry(b"hello").or_insert(10) += 1;
+    // New key, including one along an existing key and one that is a prefix of it.
+    assert_eq!(*builder.entry(b"help").or_insert(20), 20);
+    assert_eq!(*builder.entry(b"hell").or_default(), 0);
+    builder.entry(b"hell").and_modify(|v| *v += 5);
+    // Modifying a missing key neither inserts it nor creates nodes for it.
+    builder.entry(b"world").and_modify(|v| *v += 1);
+    let build = builder.prebuild().unwrap();
+    let trie = Fastrie::from_prebuilt(build.index_width, &build.values, &build.data).unwrap();
+    assert_eq!(trie.get(b"hello"), Some(&2));
+    assert_eq!(trie.get(b"help"), Some(&20));
+    assert_eq!(trie.get(b"hell"), Some(&5));
+    assert!(!trie.contains_prefix(b"w"));
+}
+
+#[test]
+fn matches_outlive_trie() {
+    let mut builder = FastrieBuilderNode::new(IndexWidth(2));
+    builder.add(b"hell", 1);
+    builder.add(b"hello", 2);
+    let build = builder.prebuild().unwrap();
+    // Each query creates and drops its own trie, but the matches borrow from the values.
+    let query = |text: &[u8]| Fastrie::from_prebuilt(build.index_width, &build.values, &build.data).unwrap().longest_matching_prefix(text);
+    let matches = [query(b"hello!"), query(b"hell"), query(b"help")];
+    assert_eq!(matches.iter().map(|mat| mat.as_ref().map(|mat| *mat.value)).collect::<Vec<_>>(), vec![Some(2), Some(1), None]);
+    let mat = {
+        let trie = Fastrie::from_prebuilt(build.index_width, &build.values, &build.data).unwrap();
+        let (mat, _) = trie.longest_matching_prefix_resumable(b"hello", None);
+        mat.unwrap()
+    };
+    assert_eq!(mat.value, &2);
+}
+
+#[test]
+fn duplicate_values_pair_keys() {
+    let mut builder = FastrieBuilderNode::new(IndexWidth(2));
+    builder.add(b"", 0);
+    builder.add(b"a", 1);
+    builder.add(b"ab", 0);
+    builder.add(b"b", 2);
+    builder.add(b"ba", 1);
+    builder.add(b"c", 0);
+    assert_eq!(builder.duplicate_values(), vec![
+        (b"".to_vec(), b"ab".to_vec()),
+        (b"a".to_vec(), b"ba".to_vec()),
+        (b"".to_vec(), b"c".to_vec()),
+    ]);
+    let build = builder.prebuild().unwrap();
+    let histogram = build.value_histogram();
+    assert_eq!((histogram[&0], histogram[&1], histogram[&2]), (3, 2, 1));
+    // Values have been moved into the build.
+    assert!(builder.duplicate_values().is_empty());
+}
+
+#[test]
+fn dense_nodes_split_into_short_clusters() {
+    // Clusters are at most 255 bytes, as the distance to the next cluster is one byte, so a node with every byte as a child needs several clusters at any index width.
+    for width in 2..=8 {
+        let mut builder = FastrieBuilderNode::new(IndexWidth(width));
+        for b in 0..=255u8 {
+            builder.add(&[b, b], b as usize);
+        };
+        builder.set_merge_clusters(width % 2 == 0);
+        let build = builder.prebuild().unwrap();
+        let trie = Fastrie::from_prebuilt(build.index_width, &build.values, &build.data).unwrap();
+        assert_eq!(trie.verify(), Ok(()));
+        for b in 0..=255u8 {
+            assert_eq!(trie.get(&[b, b]), Some(&(b as usize)));
+            assert_eq!(trie.get(&[b, b.wrapping_add(1)]), None);
+        };
+        assert_eq!(trie.keys().count(), 256);
+    };
+}
+
+#[test]
+fn offsets_locate_value_slots() {
+    let mut rng = Rng(0x5DEE_CE66_D1CE_4E5B);
+    let mut keys: BTreeMap<Vec<u8>, u64> = BTreeMap::new();
+    for _ in 0..200 {
+        let key = rng.key(&keys);
+        keys.insert(key, rng.next());
+    };
+    let mut builder = FastrieBuilderNode::new(IndexWidth(3));
+    for (key, value) in keys.iter() {
+        builder.add(key, *value);
+    };
+    let (mut build, offsets) = builder.prebuild_with_offsets().unwrap();
+    assert_eq!(offsets.len(), keys.len());
+    // Rotate every key's value index by one.
+    let values_len = build.values.len();
+    for (i, key) in keys.keys().enumerate() {
+        let slot = offsets[key];
+        assert_eq!(build.data[slot..slot + 3], (i as u32 + 1).to_le_bytes()[..3]);
+        build.data[slot..slot + 3].copy_from_slice(&((i + 1) % values_len + 1).to_le_bytes()[..3]);
+    };
+    let trie = Fastrie::from_prebuilt(build.index_width, &build.values, &build.data).unwrap();
+    assert_eq!(trie.verify(), Ok(()));
+    let values = keys.values().collect::<Vec<_>>();
+    for (i, key) in keys.keys().enumerate() {
+        assert_eq!(trie.get(key), Some(values[(i + 1) % values_len]));
+    };
+}
+
+#[test]
+fn empty_key_and_empty_input() {
+    for &with_empty_key in &[false, true] {
+        let mut builder = FastrieBuilderNode::new(IndexWidth(2));
+        builder.add(b"a", 1);
+        let mut set_builder = FastrieBuilderNode::new(IndexWidth(2));
+        set_builder.add_unique(b"a");
+        if with_empty_key {
+            builder.add(b"", 0);
+            set_builder.add_unique(b"");
+        };
+        let build = builder.prebuild().unwrap();
+        let trie = Fastrie::from_prebuilt(build.index_width, &build.values, &build.data).unwrap();
+        let set = trie.as_set();
+        let set_build = set_builder.prebuild().unwrap();
+        let unit_set = Fastrie::from_prebuilt(set_build.index_width, &set_build.values, &set_build.data).unwrap();
+        // The empty key is a prefix of every text, so it matches with a length of zero unless a longer key matches.
+        assert_eq!(trie.get(b""), if with_empty_key { Some(&0) } else { None });
+        assert_eq!(trie.contains_key(b""), with_empty_key);
+        assert_eq!(set.contains_key(b""), with_empty_key);
+        assert!(trie.contains_prefix(b""));
+        assert!(set.contains_prefix(b""));
+        for text in [&b""[..], b"b"].iter() {
+            let mat = trie.longest_matching_prefix(text);
+            assert_eq!(mat.as_ref().map(|m| (m.len, m.range(), *m.value, m.value_index)), if with_empty_key { Some((0, 0..0, 0, 0)) } else { None });
+            assert_eq!(unit_set.longest_matching_prefix(text).map(|m| (m.len, m.value_index)), if with_empty_key { Some((0, 0)) } else { None });
+            assert_eq!(set.matched_prefix_len(text), 0);
+            let chunks = [&build.data[..]];
+            let chunked = ChunkedFastrie::new(build.index_width, &build.values, Chunks::new(&chunks).unwrap()).unwrap();
+            assert_eq!(chunked.longest_matching_prefix(text).map(|m| (m.len, m.value_index)), mat.as_ref().map(|m| (m.len, m.value_index)));
+        };
+        assert_eq!(trie.match_detail(b"").exact, trie.get(b""));
+        assert_eq!(trie.longest_matching_prefix(b"ab").map(|m| (m.len, *m.value)), Some((1, 1)));
+        assert!(trie.contains_key(b"a"));
+        assert!(!set.contains_key(b"ab"));
+    };
+
+    // Only the root has a value.
+    let mut builder = FastrieBuilderNode::new(IndexWidth(1));
+    builder.add(b"", "root");
+    let build = builder.prebuild().unwrap();
+    let trie = Fastrie::from_prebuilt(build.index_width, &build.values, &build.data).unwrap();
+    for text in [&b""[..], b"x", b"xyz"].iter() {
+        let mat = trie.longest_matching_prefix(text).unwrap();
+        assert_eq!((mat.len, mat.matched(text), *mat.value), (0, &b""[..], "root"));
+        assert_eq!(trie.matched_prefix_len(text), 0);
+    };
+}
+
+/// Returns the length and value index of the longest match of `text` from every query built on a longest prefix walk that doesn't need values of a particular type.
+fn longest_matches<V>(trie: &Fastrie<'_, '_, V>, text: &[u8]) -> Vec<Option<(usize, usize)>> {
+    let of = |mat: Option<FastrieMatch<'_, V>>| mat.map(|m| (m.len, m.value_index));
+    let mut matches = vec![
+        of(trie.longest_matching_prefix(text)),
+        of(trie.try_longest_matching_prefix(text).unwrap()),
+        of(trie.longest_matching_prefix_at(text, 0)),
+        of(trie.longest_matching_prefix_skip(text, |_| false)),
+        of(trie.longest_matching_prefix_read(text).unwrap().0),
+        of(trie.longest_matching_prefix_with_walk(text).0),
+        of(trie.longest_matching_prefix_fuzzy(text, 0)),
+        of(trie.longest_matching_prefix_wildcard(text, b'?')),
+        of(trie.longest_matching_prefix_tagged(text, ()).map(|(m, _)| m)),
+        of(trie.match_detail(text).longest),
+        trie.longest_matching_prefix_len_resolve(text, |value_idx| value_idx),
+    ];
+    for split in 0..=text.len() {
+        matches.push(of(trie.longest_matching_prefix_chained(&text[..split], &text[split..])));
+        let pos = trie.advance(FastriePosition::default(), &text[..split]);
+        matches.push(of(trie.finish(trie.advance(pos, &text[split..]))));
+        matches.push(of(trie.longest_matching_prefix_resumable(&text[split..], Some(pos)).0));
+    };
+    matches
+}
+
+#[test]
+fn empty_key_matches_agree_across_queries() {
+    let keys = [&b""[..], b"a", b"abc"];
+    let mut builder = FastrieBuilderNode::new(IndexWidth(2));
+    let mut set_builder = FastrieBuilderNode::new(IndexWidth(2));
+    for (i, key) in keys.iter().enumerate() {
+        builder.add(key, i);
+        set_builder.add_unique(key);
+    };
+    let build = builder.prebuild().unwrap();
+    let trie = Fastrie::from_prebuilt(build.index_width, &build.values, &build.data).unwrap();
+    let set = trie.as_set();
+    let set_build = set_builder.prebuild().unwrap();
+    let unit_set = Fastrie::from_prebuilt(set_build.index_width, &set_build.values, &set_build.data).unwrap();
+    let fixed = FixedFastrie::<_, 2>::from_prebuilt(&build.values, &build.data).unwrap();
+    let chunks = [&build.data[..]];
+    let chunked = ChunkedFastrie::new(build.index_width, &build.values, Chunks::new(&chunks).unwrap()).unwrap();
+    let owned = OwnedFastrie::from(FastrieBuild { index_width: build.index_width, values: build.values.clone(), data: build.data.clone() });
+    let growable = GrowableFastrie::new(Fastrie::from_prebuilt(build.index_width, &build.values, &build.data).unwrap());
+    // Only the empty key matches "" and "b", with a length of zero.
+    for &(text, len, value_idx) in [(&b""[..], 0, 0), (b"b", 0, 0), (b"a", 1, 1), (b"ab", 1, 1), (b"abcd", 3, 2)].iter() {
+        let expected = Some((len, value_idx));
+        for (i, mat) in longest_matches(&trie, text).into_iter().enumerate() {
+            assert_eq!(mat, expected, "query {} of {:?}", i, text);
+        };
+        for (i, mat) in longest_matches(&unit_set, text).into_iter().enumerate() {
+            assert_eq!(mat, expected, "set query {} of {:?}", i, text);
+        };
+        let mat = trie.longest_matching_prefix(text).unwrap();
+        assert_eq!((mat.range(), mat.matched(text)), (0..len, &text[..len]));
+        // Queries reporting an inclusive end never match the empty key.
+        let expected_end = Some((len, value_idx)).filter(|&(len, _)| len > 0).map(|(len, value_idx)| (len - 1, value_idx));
+        assert_eq!(trie.with_longest_matching_prefix(text, |end, value| (end, *value)), expected_end);
+        for set in [&set, &unit_set.as_set()].iter() {
+            assert_eq!(set.longest_matching_prefix_len(text), expected_end.map(|(end, _)| end));
+            assert_eq!(set.matched_prefix_len(text), len);
+            assert_eq!(set.longest_matching_prefix_resolve(text, |value_idx| value_idx), expected_end);
+            assert_eq!(set.longest_matching_prefix_len_resolve(text, |value_idx| value_idx), expected);
+        };
+        assert_eq!(fixed.longest_matching_prefix(text).map(|m| (m.len, m.value_index)), expected);
+        assert_eq!(chunked.longest_matching_prefix(text).map(|m| (m.len, m.value_index)), expected);
+        assert_eq!(owned.longest_matching_prefix(text).map(|m| (m.len, m.value_index)), expected);
+        assert_eq!(growable.longest_matching_prefix(text).map(|m| (m.len, m.value_index)), expected);
+    };
+    // The empty key isn't reported by scans, as it would match at every position.
+    assert_eq!(trie.scan(b"bab").map(|m| m.range()).collect::<Vec<_>>(), vec![1..2]);
+
+    // The overlay's empty key takes precedence over the base's, but not over longer keys.
+    let mut growable = growable;
+    growable.add(b"", 9);
+    assert_eq!(growable.longest_matching_prefix(b"b").map(|m| (m.len, *m.value)), Some((0, 9)));
+    assert_eq!(growable.longest_matching_prefix(b"ab").map(|m| (m.len, *m.value)), Some((1, 1)));
+    assert!(growable.contains_key(b""));
+}
+
+#[test]
+fn wildcard_prefers_literal_keys() {
+    let mut builder = FastrieBuilderNode::new(IndexWidth(2));
+    builder.add(b"h?llo", 1);
+    builder.add(b"hello", 2);
+    builder.add(b"??llo world", 3);
+    builder.add(b"hel", 4);
+    let build = builder.prebuild().unwrap();
+    let trie = Fastrie::from_prebuilt(build.index_width, &build.values, &build.data).unwrap();
+    let wildcard = |text: &[u8]| trie.longest_matching_prefix_wildcard(text, b'?').map(|mat| (mat.end, *mat.value));
+    assert_eq!(wildcard(b"hello"), Some((4, 2)));
+    assert_eq!(wildcard(b"hello world"), Some((4, 2)));
+    assert_eq!(wildcard(b"hallo"), Some((4, 1)));
+    assert_eq!(wildcard(b"jallo world"), Some((10, 3)));
+    assert_eq!(wildcard(b"helm"), Some((2, 4)));
+    assert_eq!(wildcard(b"jello"), None);
+    // A wildcard in the text only matches a literal wildcard in a key.
+    assert_eq!(wildcard(b"h?llo"), Some((4, 1)));
+    // Without wildcards in the text or keys, it's the same as a literal match.
+    assert_eq!(trie.longest_matching_prefix_wildcard(b"hello", b'*').map(|mat| *mat.value), Some(2));
+    assert_eq!(trie.longest_matching_prefix_wildcard(b"hallo", b'*').map(|mat| *mat.value), None);
+}
+
+#[test]
+fn streamed_build_matches_prebuild() {
+    let mut rng = Rng(0x8CB9_2BA7_2F3D_8DD7);
+    for _ in 0..50 {
+        let mut keys: BTreeMap<Vec<u8>, u64> = BTreeMap::new();
+        for _ in 0..rng.below(200) {
+            let key = rng.key(&keys);
+            keys.insert(key, rng.next());
+        };
+        // Long keys have paths, including ones split at the maximum path length.
+        if rng.below(4) == 0 {
+            keys.insert((0..rng.below(600)).map(|i| i as u8).collect(), 0);
+        };
+        let merge_clusters = rng.below(2) == 0;
+        let new_builder = || {
+            let mut builder = FastrieBuilderNode::new(IndexWidth(3));
+            for (key, value) in keys.iter() {
+                builder.add(key, *value);
+            };
+            builder.set_merge_clusters(merge_clusters);
+            builder
+        };
+        let build = new_builder().prebuild().unwrap();
+        let mut data = Vec::new();
+        let values = new_builder().build_to_writer(&mut data).unwrap();
+        assert_eq!(data, build.data);
+        assert_eq!(values, build.values);
+    };
+}
+
+// Accepts `limit` bytes, then fails every write.
+struct FailingWriter {
+    written: Vec<u8>,
+    limit: usize,
+}
+
+impl std::io::Write for FailingWriter {
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+        let n = buf.len().min(self.limit - self.written.len());
+        if n == 0 {
+            return Err(std::io::Error::other("disk full"));
+        };
+        self.written.extend_from_slice(&buf[..n]);
+        Ok(n)
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> {
+        Ok(())
+    }
+}
+
+#[test]
+fn streamed_build_keeps_values_on_write_error() {
+    let mut builder = FastrieBuilderNode::new(IndexWidth(2));
+    builder.add(b"hell", 1);
+    builder.add(b"hello", 2);
+    builder.add(b"world", 3);
+    let mut failing = FailingWriter { written: Vec::new(), limit: 5 };
+    assert!(matches!(builder.build_to_writer(&mut failing), Err(WriteError::Io(_))));
+    assert!(!builder.is_built());
+
+    // Retrying writes the whole trie, values included.
+    let mut data = Vec::new();
+    let values = builder.build_to_writer(&mut data).unwrap();
+    assert_eq!(values, vec![1, 2, 3]);
+    let trie = Fastrie::from_prebuilt(IndexWidth(2), &values, &data).unwrap();
+    assert_eq!(trie.get(b"hello"), Some(&2));
+    assert_eq!(trie.get(b"world"), Some(&3));
+
+    assert!(matches!(builder.build_to_writer(&mut Vec::new()), Err(WriteError::AlreadyBuilt)));
+}
+
+#[test]
+fn errors_display() {
+    let errors: Vec<(Box<dyn std::error::Error>, &str)> = vec![
+        (Box::new(FastrieError::TruncatedData { pos: 7 }), "data is truncated in the node or cluster at 7"),
+        (Box::new(FastrieError::PresenceMismatch { node_pos: 3 }), "children-present byte of the node at 3 doesn't match its children"),
+        (Box::new(FastrieError::MalformedData { pos: 9 }), "data is malformed at 9"),
+        (Box::new(FastrieError::BadMagic), "buffer doesn't start with the expected magic bytes"),
+        (Box::new(FastrieError::InvalidIndexWidth(9)), "index width 9 isn't between 1 and 8"),
+        (Box::new(FastrieError::IndexWidthMismatch { given: IndexWidth(2), built: IndexWidth(3) }), "index width 2 was given, but the data was built with 3"),
+        (Box::new(FastrieError::ValueIndexOutOfRange { idx: 4, len: 2 }), "value index 4 is out of range of the 2 values"),
+        (Box::new(FastrieError::OrphanedValue { value_idx: 5 }), "value at index 5 isn't referred to by any node"),
+        (Box::new(FastrieError::ContentHashMismatch), "content hash doesn't match the data and values"),
+        (Box::new(FastrieError::UnsupportedVersion(9)), "layout version 9 isn't supported"),
+        (Box::new(FastrieError::AlreadyBuilt), "builder has already been built"),
+        (Box::new(WriteError::AlreadyBuilt), "builder has already been built"),
+        (Box::new(WriteError::Io(std::io::Error::other("disk full"))), "failed to write data: disk full"),
+        (Box::new(AddError::TooLong), "key is longer than the maximum key length"),
+        (Box::new(AlreadyBuilt), "builder has already been built"),
+    ];
+    for (err, expected) in errors {
+        assert_eq!(err.to_string(), expected);
+    };
+}
+
+#[test]
+fn segments_containing_separator_bytes() {
+    let mut builder = FastrieBuilderNode::new(IndexWidth(2));
+    builder.add_segments(&[b"a\x00b"], 1);
+    builder.add_segments(&[b"a", b"b"], 2);
+    builder.add_segments(&[b"a\x01", b"\x00"], 3);
+    builder.add_segments(&[b"", b"x"], 4);
+    builder.add_segments(&[b"a"], 5);
+    let build = builder.prebuild().unwrap();
+    let trie = Fastrie::from_prebuilt(build.index_width, &build.values, &build.data).unwrap();
+    let find = |segments: &[&[u8]]| trie.longest_matching_prefix_segments(segments).map(|mat| (mat.end, *mat.value));
+    // A segment containing the separator byte is distinct from the segments either side of it.
+    assert_eq!(find(&[b"a\x00b"]), Some((0, 1)));
+    assert_eq!(find(&[b"a", b"b"]), Some((1, 2)));
+    assert_eq!(find(&[b"a\x00b", b"c"]), Some((0, 1)));
+    assert_eq!(find(&[b"a\x00"]), None);
+    assert_eq!(find(&[b"a\x01", b"\x00", b"z"]), Some((1, 3)));
+    assert_eq!(find(&[b"a\x01"]), None);
+    assert_eq!(find(&[b"a", b"\x00"]), Some((0, 5)));
+    assert_eq!(find(&[b"", b"x"]), Some((1, 4)));
+    assert_eq!(find(&[b"", b"y"]), None);
+    assert_eq!(find(&[]), None);
+}
+
+#[test]
+fn child_widths_fit_each_node() {
+    let keys = |first: u32| (first..first + 2000).map(|i| ((i * 7919).to_string(), i)).collect::<Vec<(String, u32)>>();
+    let build_shard = |first: u32| {
+        let mut builder = FastrieBuilderNode::new(IndexWidth(2));
+        for (key, i) in keys(first) {
+            builder.add(key.as_bytes(), i);
+        };
+        builder.prebuild_with_offsets().unwrap()
+    };
+    let (build, offsets) = build_shard(0);
+    let trie = Fastrie::from_prebuilt(build.index_width, &build.values, &build.data).unwrap();
+    assert_eq!(trie.verify(), Ok(()));
+    assert!(keys(0).iter().all(|(key, i)| trie.get(key.as_bytes()) == Some(i)));
+    let layout = Layout::new(build.index_width);
+    let child_width = |node_pos: usize| layout.child_width(build.data[node_pos + layout.node_header_len() - 1]);
+    // The root's last children are beyond most of the data, but a key's node with a few longer keys below it only points past those.
+    assert_eq!(child_width(Layout::HEADER_LEN), IndexWidth(2));
+    let (_, &inner_pos) = offsets.iter().find(|(key, _)| offsets.keys().any(|other| other.len() > key.len() && other.starts_with(key))).unwrap();
+    assert_eq!(child_width(inner_pos), IndexWidth(1));
+
+    // Splicing shards moves the routing nodes' children away from them, so routing needs wider child indices than it would alone.
+    let build = FastrieBuild::concat(vec![(b"a".to_vec(), build_shard(0).0), (b"b".to_vec(), build_shard(2000).0)]);
+    assert_eq!(build.verify_values(), Ok(()));
+    let trie = Fastrie::from_prebuilt(build.index_width, &build.values, &build.data).unwrap();
+    for (prefix, first) in [("a", 0), ("b", 2000)].iter() {
+        for (key, i) in keys(*first) {
+            assert_eq!(trie.get(format!("{}{}", prefix, key).as_bytes()), Some(&i));
+        };
+    };
+}
+
+#[test]
+fn from_map_matches_entities() {
+    let json = std::fs::read_to_string(concat!(env!("CARGO_MANIFEST_DIR"), "/bench/entities.json")).unwrap();
+    let entities = json.lines()
+        .filter_map(|line| line.trim().strip_prefix('"'))
+        .filter(|line| line.starts_with('&'))
+        .map(|line| line[..line.find('"').unwrap()].to_string())
+        .enumerate()
+        .map(|(i, rep)| (rep, i))
+        .collect::<std::collections::HashMap<String, usize>>();
+    assert!(entities.len() > 2000);
+    let from_btree_map = FastrieBuilderNode::from_btree_map(entities.iter().map(|(rep, i)| (rep.clone(), *i)).collect::<BTreeMap<_, _>>(), IndexWidth(2));
+    let build = FastrieBuilderNode::from_map(entities.clone(), IndexWidth(2));
+    assert_eq!(build.data, from_btree_map.data);
+    assert_eq!(build.values, from_btree_map.values);
+
+    let trie = Fastrie::from_prebuilt(build.index_width, &build.values, &build.data).unwrap();
+    assert_eq!(trie.verify(), Ok(()));
+    assert_eq!(build.len(), entities.len());
+    for (rep, i) in entities.iter() {
+        assert_eq!(trie.get(rep.as_bytes()), Some(i));
+    };
+}
+
+#[test]
+fn get_without_value_is_none() {
+    let mut builder = FastrieBuilderNode::new(IndexWidth(2));
+    builder.add(b"hell", 1);
+    builder.add(b"hello", 2);
+    let build = builder.prebuild().unwrap();
+    let set = from_prebuilt_without_values(build.index_width, &build.data);
+    assert_eq!(set.get(b"hell"), None);
+    assert!(set.contains_key(b"hell"));
+    // Values from a build with fewer keys.
+    let stale = Fastrie::from_prebuilt(build.index_width, &build.values[..1], &build.data).unwrap();
+    assert_eq!(stale.get(b"hell"), Some(&1));
+    assert_eq!(stale.get(b"hello"), None);
+}
+
+#[test]
+fn queries_without_values_dont_panic() {
+    let mut builder = FastrieBuilderNode::new(IndexWidth(2));
+    builder.add(b"", 0);
+    builder.add(b"hell", 1);
+    builder.add(b"hello", 2);
+    let build = builder.prebuild().unwrap();
+    let trie = Fastrie::from_prebuilt(build.index_width, &build.values, &build.data).unwrap();
+    let set = trie.as_set();
+    let mut cursor = set.cursor();
+    for &c in b"hell" {
+        assert_eq!(cursor.step(c), StepResult::Descended { terminal_value: None });
+    };
+    assert_eq!(cursor.value_index(), Some(1));
+    assert!(set.longest_matching_prefix(b"hello world").is_none());
+    assert!(set.longest_matching_prefix(b"").is_none());
+    assert_eq!(set.matched_prefix_len(b"hello world"), 5);
+}
+
+#[test]
+fn truncated_data_errs_instead_of_panicking() {
+    let mut rng = Rng(0x2545_F491_4F6C_DD1D);
+    let mut keys: BTreeMap<Vec<u8>, u64> = BTreeMap::new();
+    for _ in 0..300 {
+        let key = rng.key(&keys);
+        keys.insert(key, rng.next());
+    };
+    let mut builder = FastrieBuilderNode::new(IndexWidth(2));
+    for (key, value) in keys.iter() {
+        builder.add(key, *value);
+    };
+    let build = builder.prebuild().unwrap();
+    let trie = Fastrie::from_prebuilt(build.index_width, &build.values, &build.data).unwrap();
+    for key in keys.keys() {
+        let expected = trie.longest_matching_prefix(key).map(|m| (m.end, m.value_index));
+        assert_eq!(trie.try_longest_matching_prefix(key).unwrap().map(|m| (m.end, m.value_index)), expected);
+    };
+    for len in 1..build.data.len() {
+        let truncated = Fastrie::from_prebuilt(build.index_width, &build.values, &build.data[..len]).unwrap();
+        for key in keys.keys() {
+            match truncated.try_longest_matching_prefix(key) {
+                // Walks that stay within the remaining data match as before.
+                Ok(mat) => assert_eq!(mat.map(|m| (m.end, m.value_index)), trie.longest_matching_prefix(key).map(|m| (m.end, m.value_index))),
+                Err(err) => assert!(matches!(err, FastrieError::TruncatedData { .. })),
+            };
+        };
+    };
+}
+
+#[test]
+fn overflowing_child_index_errs_instead_of_panicking() {
+    // The root has a single child for 'a' with an 8-byte child index of `usize::MAX`, so the child's position overflows.
+    let data = [0xF1, 0, 0x72, b'a', 0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF];
+    let trie = Fastrie::from_prebuilt(IndexWidth(1), &[0u8][..], &data[..]).unwrap();
+    assert!(matches!(trie.try_longest_matching_prefix(b"ab"), Err(FastrieError::TruncatedData { .. })));
+}
+
+#[test]
+fn chained_matches_across_boundary() {
+    let mut rng = Rng(0x9E37_79B9_7F4A_7C15);
+    let mut keys: BTreeMap<Vec<u8>, u64> = BTreeMap::new();
+    for _ in 0..200 {
+        let key = rng.key(&keys);
+        keys.insert(key, rng.next());
+    };
+    let mut builder = FastrieBuilderNode::new(IndexWidth(2));
+    for (key, value) in keys.iter() {
+        builder.add(key, *value);
+    };
+    let build = builder.prebuild().unwrap();
+    let trie = Fastrie::from_prebuilt(build.index_width, &build.values, &build.data).unwrap();
+    let queries = keys.keys().cloned().chain((0..50).map(|_| rng.key(&keys))).collect::<Vec<Vec<u8>>>();
+    for query in queries {
+        let expected = trie.longest_matching_prefix(&query).map(|m| (m.end, m.value_index));
+        // Split at every position, so keys straddle the boundary, start it, or end at it.
+        for split in 0..=query.len() {
+            let (first, second) = query.split_at(split);
+            assert_eq!(trie.longest_matching_prefix_chained(first, second).map(|m| (m.end, m.value_index)), expected);
+        };
+    };
+}
+
+#[test]
+fn build_options_agree_on_queries() {
+    let mut rng = Rng(0xD1B5_4A32_D192_ED03);
+    let mut keys: BTreeMap<Vec<u8>, u64> = BTreeMap::new();
+    for _ in 0..500 {
+        let key = rng.key(&keys);
+        keys.insert(key, rng.next());
+    };
+    let build_with = |max_cluster_len: usize, merge_clusters: bool| {
+        let mut builder = FastrieBuilderNode::new(IndexWidth(3));
+        for (key, value) in keys.iter() {
+            builder.add(key, *value);
+        };
+        builder.set_max_cluster_len(max_cluster_len);
+        builder.set_merge_clusters(merge_clusters);
+        builder.prebuild().unwrap()
+    };
+    let queries = keys.keys().cloned().chain((0..100).map(|_| rng.key(&keys))).collect::<Vec<Vec<u8>>>();
+    let inputs = queries.iter().map(|query| query.as_slice()).collect::<Vec<&[u8]>>();
+    let baseline = build_with(usize::MAX, false);
+    assert_eq!(build_with(8, false).queries_agree_with(&baseline, &inputs), Ok(true));
+    assert_eq!(build_with(usize::MAX, true).queries_agree_with(&baseline, &inputs), Ok(true));
+
+    let mut fewer_keys = FastrieBuilderNode::new(IndexWidth(3));
+    for (key, value) in keys.iter().skip(1) {
+        fewer_keys.add(key, *value);
+    };
+    assert_eq!(fewer_keys.prebuild().unwrap().queries_agree_with(&baseline, &inputs), Ok(false));
+}
+
+#[test]
+fn nearest_keys_share_longest_prefix() {
+    let mut builder = FastrieBuilderNode::new(IndexWidth(2));
+    for key in ["hello", "help", "helm", "hero", "world"].iter() {
+        builder.add(key.as_bytes(), ());
+    };
+    let build = builder.prebuild().unwrap();
+    let trie = from_prebuilt_without_values(build.index_width, &build.data);
+    let nearest = |query: &str, limit: usize| trie.nearest_keys(query.as_bytes(), limit).into_iter().map(|key| String::from_utf8(key).unwrap()).collect::<Vec<String>>();
+    assert_eq!(nearest("hel", 10), vec!["hello", "helm", "help"]);
+    assert_eq!(nearest("helix", 10), vec!["hello", "helm", "help"]);
+    assert_eq!(nearest("helix", 2), vec!["hello", "helm"]);
+    assert_eq!(nearest("hex", 10), vec!["hello", "helm", "help", "hero"]);
+    // Walking into the middle of a path still finds the keys along it.
+    assert_eq!(nearest("wo", 10), vec!["world"]);
+    assert_eq!(nearest("worldwide", 10), vec!["world"]);
+    assert!(nearest("hel", 0).is_empty());
+}
+
+#[test]
+fn set_segments_by_longest_match() {
+    let mut builder = FastrieBuilderNode::new(IndexWidth(3));
+    builder.add(b"hell", 1);
+    builder.add(b"hello", 2);
+    builder.add(b"world", 4);
+    let build = builder.prebuild().unwrap();
+    let set = from_prebuilt_without_values(build.index_width, &build.data);
+    let trie = Fastrie::from_prebuilt(build.index_width, &build.values, &build.data).unwrap();
+    // Split text into keys, skipping chars that don't start one.
+    let text = b"helloworld hellworldx";
+    let mut segments = Vec::new();
+    let mut pos = 0;
+    while pos < text.len() {
+        match set.longest_matching_prefix_len(&text[pos..]) {
+            Some(end) => {
+                assert_eq!(Some(end), trie.longest_matching_prefix(&text[pos..]).map(|m| m.end));
+                segments.push(&text[pos..=pos + end]);
+                pos += end + 1;
+            }
+            None => pos += 1,
+        };
+    };
+    assert_eq!(segments, vec![&b"hello"[..], b"world", b"hell", b"world"]);
+}
+
+#[test]
+fn weighted_clusters_only_reorder() {
+    let mut rng = Rng(0x9E37_79B9_7F4A_7C15);
+    let mut keys: BTreeMap<Vec<u8>, u64> = BTreeMap::new();
+    for _ in 0..500 {
+        let key = rng.key(&keys);
+        keys.insert(key, rng.next());
+    };
+    let builder = || {
+        let mut builder = FastrieBuilderNode::new(IndexWidth(2));
+        for (key, value) in keys.iter() {
+            builder.add(key, *value);
+        };
+        builder
+    };
+    let baseline = builder().prebuild().unwrap();
+    // Equal weights keep the largest first order.
+    assert_eq!(builder().prebuild_weighted(|_| 1).unwrap().data, baseline.data);
+
+    // Favour clusters of high characters, which the largest first order doesn't.
+    let weighted = builder().prebuild_weighted(|cluster| cluster.iter().flatten().map(|&c| c as u64).max().unwrap()).unwrap();
+    assert_ne!(weighted.data, baseline.data);
+    let queries = keys.keys().cloned().chain((0..100).map(|_| rng.key(&keys))).collect::<Vec<Vec<u8>>>();
+    let inputs = queries.iter().map(|query| query.as_slice()).collect::<Vec<&[u8]>>();
+    assert_eq!(weighted.queries_agree_with(&baseline, &inputs), Ok(true));
+    assert_eq!(weighted.values, baseline.values);
+}
+
+#[test]
+fn boxed_unsized_values() {
+    let words = ["a", "an", "and", "ant", "bee", "been"];
+    let mut builder = FastrieBuilderNode::new(IndexWidth(1));
+    for word in words.iter() {
+        builder.add(word.as_bytes(), Box::<str>::from(word.to_uppercase()));
+    };
+    let build = builder.prebuild().unwrap();
+    let trie: Fastrie<str, _> = Fastrie::from_prebuilt_boxed(build.index_width, &build.values, &build.data).unwrap();
+    for word in words.iter() {
+        assert_eq!(trie.get(word.as_bytes()), Some(word.to_uppercase().as_str()));
+    };
+    let mat = trie.longest_matching_prefix(b"beehive").unwrap();
+    assert_eq!((mat.end, mat.value), (2, "BEE"));
+    assert_eq!(trie.iter().map(|(_, value)| value).collect::<Vec<&str>>(), vec!["A", "AN", "AND", "ANT", "BEE", "BEEN"]);
+
+    let mut builder = FastrieBuilderNode::new(IndexWidth(1));
+    builder.add(b"k", Box::<[u8]>::from(&b"\x00\xFF"[..]));
+    let build = builder.prebuild().unwrap();
+    let trie: Fastrie<[u8], _> = Fastrie::from_prebuilt_boxed(build.index_width, &build.values, &build.data).unwrap();
+    assert_eq!(trie.get(b"k"), Some(&b"\x00\xFF"[..]));
+    assert_eq!(Fastrie::from_prebuilt_boxed(IndexWidth(2), &build.values, &build.data).err(), Some(FastrieError::IndexWidthMismatch { given: IndexWidth(2), built: IndexWidth(1) }));
+}
+
+#[test]
+fn min_and_max_keys_match_enumeration() {
+    let mut rng = Rng(0x6A09_E667_F3BC_C908);
+    let mut keys: BTreeMap<Vec<u8>, u64> = BTreeMap::new();
+    for _ in 0..300 {
+        let key = rng.key(&keys);
+        keys.insert(key, rng.next());
+    };
+    let mut builder = FastrieBuilderNode::new(IndexWidth(2));
+    for (key, value) in keys.iter() {
+        builder.add(key, *value);
+    };
+    let build = builder.prebuild().unwrap();
+    let trie = Fastrie::from_prebuilt(build.index_width, &build.values, &build.data).unwrap();
+    assert_eq!(trie.min_key().as_ref(), keys.keys().next());
+    assert_eq!(trie.max_key().as_ref(), keys.keys().next_back());
+
+    let build = FastrieBuilderNode::<()>::new(IndexWidth(1)).prebuild().unwrap();
+    let trie = from_prebuilt_without_values(build.index_width, &build.data);
+    assert_eq!((trie.min_key(), trie.max_key()), (None, None));
+}
+
+#[test]
+fn replace_subtree_drops_previous_keys() {
+    let keys_of = |builder: &mut FastrieBuilderNode<u32>| {
+        let build = builder.prebuild().unwrap();
+        let trie = Fastrie::from_prebuilt(build.index_width, &build.values, &build.data).unwrap();
+        assert_eq!(trie.verify(), Ok(()));
+        trie.iter().map(|(key, value)| (key, *value)).collect::<Vec<(Vec<u8>, u32)>>()
+    };
+    let base = || {
+        let mut builder = FastrieBuilderNode::new(IndexWidth(1));
+        builder.add(b"hel", 1);
+        builder.add(b"hello", 2);
+        builder.add(b"world", 3);
+        builder.set_max_cluster_len(4);
+        builder
+    };
+    let sub = || {
+        let mut sub = FastrieBuilderNode::new(IndexWidth(1));
+        sub.add(b"", 10);
+        sub.add(b"p", 11);
+        sub
+    };
+
+    let mut builder = base();
+    builder.replace_subtree(b"hel", sub());
+    assert_eq!(keys_of(&mut builder), vec![(b"hel".to_vec(), 10), (b"help".to_vec(), 11), (b"world".to_vec(), 3)]);
+
+    // Missing prefixes are created.
+    let mut builder = base();
+    builder.replace_subtree(b"xyz", sub());
+    assert_eq!(keys_of(&mut builder).len(), 5);
+
+    // Replacing with an empty subtree removes the prefix like `split_off`.
+    let mut builder = base();
+    builder.replace_subtree(b"he", FastrieBuilderNode::new(IndexWidth(1)));
+    assert_eq!(keys_of(&mut builder), vec![(b"world".to_vec(), 3)]);
+
+    // An empty prefix replaces the whole builder.
+    let mut builder = base();
+    builder.replace_subtree(b"", sub());
+    assert_eq!(keys_of(&mut builder), vec![(b"".to_vec(), 10), (b"p".to_vec(), 11)]);
+}
+
+#[test]
+fn split_off_prunes_and_keeps_root_settings() {
+    let configure = |builder: &mut FastrieBuilderNode<u8>| {
+        builder.set_max_cluster_len(8);
+        builder.set_merge_clusters(true);
+    };
+    let mut builder = FastrieBuilderNode::new(IndexWidth(2));
+    configure(&mut builder);
+    builder.add(b"old", 0);
+    let mut split = builder.split_off(b"").unwrap();
+    assert!(builder.is_empty());
+    let build = split.prebuild().unwrap();
+    let trie = Fastrie::from_prebuilt(build.index_width, &build.values, &build.data).unwrap();
+    assert_eq!(trie.keys().collect::<Vec<_>>(), vec![b"old".to_vec()]);
+    // The emptied root is still built with its settings, not the defaults.
+    let mut configured = FastrieBuilderNode::new(IndexWidth(2));
+    configure(&mut configured);
+    let mut default = FastrieBuilderNode::new(IndexWidth(2));
+    for c in 0..=255u8 {
+        builder.add(&[c], c);
+        configured.add(&[c], c);
+        default.add(&[c], c);
+    };
+    let data = builder.prebuild().unwrap().data;
+    assert_eq!(data, configured.prebuild().unwrap().data);
+    assert_ne!(data, default.prebuild().unwrap().data);
+
+    // Only nodes along the prefix left without a value or children are pruned.
+    let key = vec![b'a'; 100_000];
+    let mut builder = FastrieBuilderNode::new(IndexWidth(4));
+    builder.add(&key, 0);
+    builder.add(&key[..10], 1);
+    builder.add(&[&key[..20], &b"b"[..]].concat(), 2);
+    let mut split = builder.split_off(&key[..50_000]).unwrap();
+    assert!(builder.split_off(&key[..50_000]).is_none());
+    let build = split.prebuild().unwrap();
+    let trie = Fastrie::from_prebuilt(build.index_width, &build.values, &build.data).unwrap();
+    assert_eq!(trie.get(&key[50_000..]), Some(&0));
+    let build = builder.prebuild().unwrap();
+    let trie = Fastrie::from_prebuilt(build.index_width, &build.values, &build.data).unwrap();
+    assert_eq!(trie.keys().map(|key| key.len()).collect::<Vec<_>>(), vec![10, 21]);
+    assert!(!trie.contains_prefix(&key[..22]));
+}
+
+#[test]
+fn exact_gets_match_keys() {
+    let mut rng = Rng(0xBB67_AE85_84CA_A73B);
+    let mut keys: BTreeMap<Vec<u8>, u64> = BTreeMap::new();
+    for _ in 0..200 {
+        let key = rng.key(&keys);
+        keys.insert(key, rng.next());
+    };
+    let mut builder = FastrieBuilderNode::new(IndexWidth(2));
+    for (key, value) in keys.iter() {
+        builder.add(key, *value);
+    };
+    let build = builder.prebuild().unwrap();
+    let trie = Fastrie::from_prebuilt(build.index_width, &build.values, &build.data).unwrap();
+    let chunks: Vec<&[u8]> = build.data.chunks(3).collect();
+    let chunked = ChunkedFastrie::new(build.index_width, &build.values, Chunks::new(&chunks).unwrap()).unwrap();
+    let fixed = FixedFastrie::<_, 2>::from_prebuilt(&build.values, &build.data).unwrap();
+    // Every prefix of every key, which includes ones ending part way along a path, and every key extended by a byte.
+    for key in keys.keys() {
+        let mut extended = key.clone();
+        extended.push(rng.byte());
+        for query in (0..=key.len()).map(|len| &key[..len]).chain(std::iter::once(extended.as_slice())) {
+            let expected = keys.get(query);
+            assert_eq!(trie.get(query), expected);
+            assert_eq!(trie.contains_key(query), expected.is_some());
+            assert_eq!(chunked.get(query), expected);
+            assert_eq!(fixed.get(query), expected);
+        };
+    };
+}
+
+#[test]
+fn contains_keys_matches_contains_key() {
+    let mut rng = Rng(0x3C6E_F372_FE94_F82B);
+    let mut keys: BTreeMap<Vec<u8>, u64> = BTreeMap::new();
+    for _ in 0..100 {
+        let key = rng.key(&keys);
+        keys.insert(key, rng.next());
+    };
+    let mut builder = FastrieBuilderNode::new(IndexWidth(2));
+    for (key, value) in keys.iter() {
+        builder.add(key, *value);
+    };
+    let build = builder.prebuild().unwrap();
+    let trie = Fastrie::from_prebuilt(build.index_width, &build.values, &build.data).unwrap();
+    // Keys, their proper prefixes, and keys extended by a byte, so present and absent queries are mixed.
+    let mut queries: Vec<Vec<u8>> = Vec::new();
+    for key in keys.keys() {
+        queries.push(key.clone());
+        queries.push(key[..key.len() / 2].to_vec());
+        let mut extended = key.clone();
+        extended.push(rng.byte());
+        queries.push(extended);
+    };
+    let queries: Vec<&[u8]> = queries.iter().map(|q| q.as_slice()).collect();
+    let contained = trie.contains_keys(&queries);
+    assert_eq!(contained.len(), queries.len());
+    assert!(contained.iter().any(|&c| c) && contained.iter().any(|&c| !c));
+    for (query, &c) in queries.iter().zip(contained.iter()) {
+        assert_eq!(c, trie.contains_key(query));
+        assert_eq!(c, keys.contains_key(*query));
+    };
+    assert!(trie.contains_keys(&[]).is_empty());
+}
+
+/// A reference implementation of `PrefixMap` over a `BTreeMap`.
+struct BTreePrefixMap(BTreeMap<Vec<u8>, u64>);
+
+impl PrefixMap for BTreePrefixMap {
+    type Value = u64;
+
+    fn get(&self, key: &[u8]) -> Option<&u64> {
+        self.0.get(key)
+    }
+
+    fn longest_matching_prefix(&self, text: &[u8]) -> Option<FastrieMatch<'_, u64>> {
+        (0..=text.len()).rev().find_map(|len| self.0.get_key_value(&text[..len])).map(|(key, value)| FastrieMatch {
+            start: 0,
+            end: key.len().saturating_sub(1),
+            len: key.len(),
+            value,
+            value_index: self.0.range(..key.clone()).count(),
+        })
+    }
+}
+
+/// Results of `get`, `contains_key`, and `longest_matching_prefix` as `(end, value, value_index)`.
+type Lookup = (Option<u64>, bool, Option<(usize, u64, usize)>);
+
+/// Returns every lookup of `queries` through `map`, so that implementations can be compared.
+fn prefix_map_lookups<M: PrefixMap<Value = u64>>(map: &M, queries: &[Vec<u8>]) -> Vec<Lookup> {
+    queries.iter().map(|query| (
+        map.get(query).copied(),
+        map.contains_key(query),
+        map.longest_matching_prefix(query).map(|m| (m.end, *m.value, m.value_index)),
+    )).collect()
+}
+
+#[test]
+fn prefix_map_impls_agree() {
+    let mut rng = Rng(0x3C6E_F372_FE94_F82B);
+    let mut keys: BTreeMap<Vec<u8>, u64> = BTreeMap::new();
+    for _ in 0..200 {
+        let key = rng.key(&keys);
+        if !key.is_empty() {
+            keys.insert(key, rng.next());
+        };
+    };
+    let mut builder = FastrieBuilderNode::new(IndexWidth(2));
+    for (key, value) in keys.iter() {
+        builder.add(key, *value);
+    };
+    let build = builder.prebuild().unwrap();
+    let queries = keys.keys().flat_map(|key| {
+        let mut extended = key.clone();
+        extended.push(rng.byte());
+        vec![key[..key.len() / 2].to_vec(), key.clone(), extended]
+    }).collect::<Vec<Vec<u8>>>();
+
+    let expected = prefix_map_lookups(&BTreePrefixMap(keys.clone()), &queries);
+    let trie = Fastrie::from_prebuilt(build.index_width, &build.values, &build.data).unwrap();
+    assert_eq!(prefix_map_lookups(&trie, &queries), expected);
+    let chunks: Vec<&[u8]> = build.data.chunks(5).collect();
+    let chunked = ChunkedFastrie::new(build.index_width, &build.values, Chunks::new(&chunks).unwrap()).unwrap();
+    assert_eq!(prefix_map_lookups(&chunked, &queries), expected);
+    let fixed = FixedFastrie::<_, 2>::from_prebuilt(&build.values, &build.data).unwrap();
+    assert_eq!(prefix_map_lookups(&fixed, &queries), expected);
+    assert_eq!(prefix_map_lookups(&OwnedFastrie::from(build), &queries), expected);
+}
+
+#[test]
+fn single_wide_cluster_finds_every_child() {
+    let mut rng = Rng(0xA54F_F53A_5F1D_36F1);
+    for _ in 0..20 {
+        // ASCII children at most 3 apart share one cluster of up to 128 chars, with a bitmap of up to 16 bytes.
+        let mut present = Vec::new();
+        let mut c = rng.below(4);
+        while c < 128 {
+            present.push(c as u8);
+            c += 1 + rng.below(3);
+        };
+        let mut builder = FastrieBuilderNode::new(IndexWidth(2));
+        for &c in present.iter() {
+            builder.add(&[c], c);
+        };
+        let build = builder.prebuild().unwrap();
+        let trie = Fastrie::from_prebuilt(build.index_width, &build.values, &build.data).unwrap();
+        let layout = Layout::new(build.index_width);
+        // The root's only cluster is the last one, so its distance to the next cluster is zero.
+        assert_eq!(build.data[Layout::HEADER_LEN + layout.node_header_len()], 0);
+        for c in 0..=255u8 {
+            assert_eq!(trie.get(&[c]), present.binary_search(&c).ok().map(|_| &c));
+        };
+    };
+}
+
+#[test]
+fn path_cost_counts_later_clusters() {
+    let mut builder = FastrieBuilderNode::new(IndexWidth(1));
+    // Clusters of the root, checked largest first: a..=e, then m..=o, then x..=y, then 0, which has a single child z.
+    for key in ["a", "b", "c", "d", "e", "m", "n", "o", "x", "y", "0", "0z"].iter() {
+        builder.add(key.as_bytes(), *key);
+    };
+    let build = builder.prebuild().unwrap();
+    let trie = Fastrie::from_prebuilt(build.index_width, &build.values, &build.data).unwrap();
+    let clusters_examined = |key: &[u8]| trie.path_cost(key).unwrap().clusters_examined;
+    assert!(clusters_examined(b"a") < clusters_examined(b"m"));
+    assert!(clusters_examined(b"m") < clusters_examined(b"x"));
+    assert!(clusters_examined(b"x") < clusters_examined(b"0"));
+    assert_eq!(clusters_examined(b"e"), clusters_examined(b"a"));
+    // The single child of "0" is one more cluster.
+    assert_eq!(clusters_examined(b"0z"), clusters_examined(b"0") + 1);
+    assert_eq!(trie.path_cost(b"0z").unwrap().nodes_visited, 3);
+    assert!(trie.path_cost(b"e").unwrap().gaps_skipped == 0);
+    assert_eq!(trie.path_cost(b"z"), None);
+    assert_eq!(trie.path_cost(b"mn"), None);
+}
+
+#[test]
+fn set_from_valued_build_keeps_keys() {
+    let mut rng = Rng(0x510E_527F_ADE6_82D1);
+    let mut keys: BTreeMap<Vec<u8>, u64> = BTreeMap::new();
+    for _ in 0..300 {
+        let key = rng.key(&keys);
+        keys.insert(key, rng.next());
+    };
+    let mut builder = FastrieBuilderNode::new(IndexWidth(2));
+    for (key, value) in keys.iter() {
+        builder.add(key, *value);
+    };
+    let build = builder.prebuild().unwrap();
+    let data = build.data.clone();
+    let set = build.into_set();
+    assert_eq!(set.data, data);
+    assert_eq!(set.len(), keys.len());
+    assert_eq!(set.verify_values(), Ok(()));
+    let trie = from_prebuilt_without_values(set.index_width, &set.data);
+    assert_eq!(trie.keys().collect::<Vec<Vec<u8>>>(), keys.keys().cloned().collect::<Vec<Vec<u8>>>());
+    for key in keys.keys() {
+        assert!(trie.contains_key(key));
+        let mut extended = key.clone();
+        extended.push(rng.byte());
+        assert_eq!(trie.contains_key(&extended), keys.contains_key(&extended));
+    };
+}
+
+#[test]
+fn match_detail_agrees_with_separate_queries() {
+    let mut rng = Rng(0x9B05_688C_2B3E_6C1F);
+    let mut keys: BTreeMap<Vec<u8>, u64> = BTreeMap::new();
+    for _ in 0..200 {
+        let key = rng.key(&keys);
+        keys.insert(key, rng.next());
+    };
+    let mut builder = FastrieBuilderNode::new(IndexWidth(2));
+    for (key, value) in keys.iter() {
+        builder.add(key, *value);
+    };
+    let build = builder.prebuild().unwrap();
+    let trie = Fastrie::from_prebuilt(build.index_width, &build.values, &build.data).unwrap();
+    let queries = keys.keys().flat_map(|key| vec![key[..key.len() / 2].to_vec(), key.clone(), [key.as_slice(), &[rng.byte()]].concat()]).chain(std::iter::once(Vec::new()));
+    for query in queries {
+        let detail = trie.match_detail(&query);
+        assert_eq!(detail.longest.map(|m| (m.end, m.value)), trie.longest_matching_prefix(&query).map(|m| (m.end, m.value)));
+        assert_eq!(detail.exact, trie.get(&query));
+    };
+}
+
+#[test]
+fn longest_matching_prefix_read_counts_consumed_bytes() {
+    let mut rng = Rng(0x3C6E_F372_FE94_F82B);
+    let mut keys: BTreeMap<Vec<u8>, u64> = BTreeMap::new();
+    for _ in 0..200 {
+        let key = rng.key(&keys);
+        keys.insert(key, rng.next());
+    };
+    let mut builder = FastrieBuilderNode::new(IndexWidth(2));
+    for (key, value) in keys.iter() {
+        builder.add(key, *value);
+    };
+    let build = builder.prebuild().unwrap();
+    let trie = Fastrie::from_prebuilt(build.index_width, &build.values, &build.data).unwrap();
+    let queries = keys.keys().flat_map(|key| vec![key[..key.len() / 2].to_vec(), key.clone(), [key.as_slice(), &[rng.byte(), rng.byte()]].concat()]).chain(std::iter::once(Vec::new()));
+    for query in queries {
+        let mut r = std::io::Cursor::new(&query);
+        let (mat, bytes_read) = trie.longest_matching_prefix_read(&mut r).unwrap();
+        assert_eq!(r.position() as usize, bytes_read);
+        assert_eq!(mat.map(|m| (m.end, m.value)), trie.longest_matching_prefix(&query).map(|m| (m.end, m.value)));
+        // Everything walked is read, plus the byte that had no child, if the walk didn't end at the end of the query or a node without children.
+        let (_, walked) = trie.longest_matching_prefix_with_walk(&query);
+        let mut cursor = trie.cursor();
+        for &c in &query[..walked] {
+            cursor.step(c);
+        };
+        let expected = if walked < query.len() && cursor.has_children() { walked + 1 } else { walked };
+        assert_eq!(bytes_read, expected);
+    };
+}
+
+#[test]
+fn add_many_matches_add() {
+    let mut rng = Rng(0xA54F_F53A_5F1D_36F1);
+    let mut keys: BTreeMap<Vec<u8>, u64> = BTreeMap::new();
+    for _ in 0..300 {
+        let key = rng.key(&keys);
+        keys.insert(key, rng.next());
+    };
+    let mut one_by_one = FastrieBuilderNode::new(IndexWidth(2));
+    for (key, value) in keys.iter() {
+        one_by_one.add(key, *value);
+    };
+    let mut many = FastrieBuilderNode::new(IndexWidth(2));
+    many.reserve_children(3);
+    many.add_many(keys.iter().map(|(key, value)| (key, *value)));
+    let one_by_one = one_by_one.prebuild().unwrap();
+    let many = many.prebuild().unwrap();
+    assert_eq!(many.data, one_by_one.data);
+    assert_eq!(many.values, one_by_one.values);
+}
+
+#[test]
+fn bitmap_counts_agree_for_every_cluster_width() {
+    // Children at most 3 apart share one cluster, so this covers bitmaps of up to 32 bytes, and wider ones of `u16` symbols.
+    let mut rng = Rng(0x2545_F491_4F6C_DD1D);
+    for &span in [8usize, 64, 129, 256, 700].iter() {
+        for _ in 0..10 {
+            let mut present = Vec::new();
+            let mut c = rng.below(2);
+            while c < span {
+                present.push(c as u16);
+                c += 1 + rng.below(3);
+            };
+            let mut builder = FastrieBuilderNode::new_with_symbols(IndexWidth(2));
+            for &c in present.iter() {
+                builder.add(&[c], c);
+            };
+            let build = builder.prebuild().unwrap();
+            let trie = Fastrie::<_, _, u16>::from_prebuilt_with_symbols(build.index_width, &build.values, &build.data).unwrap();
+            for c in 0..span as u16 + 8 {
+                assert_eq!(trie.get(&[c]), present.binary_search(&c).ok().map(|_| &c));
+            };
+            if span <= 256 {
+                let mut builder = FastrieBuilderNode::new(IndexWidth(2));
+                for &c in present.iter() {
+                    builder.add(&[c as u8], c);
+                };
+                let build = builder.prebuild().unwrap();
+                let trie = Fastrie::from_prebuilt(build.index_width, &build.values, &build.data).unwrap();
+                for c in 0..=255u8 {
+                    assert_eq!(trie.get(&[c]).copied(), present.binary_search(&(c as u16)).ok().map(|_| c as u16));
+                };
+            };
+        };
+    };
+}